@@ -0,0 +1,99 @@
+use criterion::{
+    BatchSize,
+    Criterion,
+    criterion_group,
+    criterion_main,
+};
+use json_patch_ext::prelude::*;
+use serde_json::{
+    Value,
+    json,
+};
+
+// A document with many array elements, and a large value to add to every one of them; this is the
+// case where cloning the value for each target dominates.
+fn setup(n: usize) -> (Value, Value) {
+    let doc = json!({"items": (0..n).map(|i| json!({"id": i})).collect::<Vec<_>>()});
+    let labels: serde_json::Map<_, _> = (0..100).map(|i| (format!("key{i}"), json!(format!("value{i}")))).collect();
+    let value = json!({"labels": labels});
+    (doc, value)
+}
+
+fn bench_wildcard_add(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wildcard_add");
+    for n in [1, 100, 1000] {
+        let (doc, value) = setup(n);
+        group.bench_function(format!("{n}"), |b| {
+            b.iter_batched(
+                || (doc.clone(), add_operation(format_ptr!("/items/*/metadata"), value.clone())),
+                |(mut doc, op)| patch_ext(&mut doc, op).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_single_add(c: &mut Criterion) {
+    // With a single target, the value is moved into the document and never cloned
+    let (doc, value) = setup(1000);
+    c.bench_function("single_add", |b| {
+        b.iter_batched(
+            || (doc.clone(), add_operation(format_ptr!("/items/0/metadata"), value.clone())),
+            |(mut doc, op)| patch_ext(&mut doc, op).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+// A deeply-nested document, for measuring the cost of walking through multiple levels of wildcards
+// (and long literal paths between them)
+fn nested(n: usize) -> Value {
+    let leaf = json!({"spec": {"template": {"spec": {"containers": [{"image": "a"}, {"image": "b"}]}}}});
+    json!({"items": (0..n).map(|_| json!({"groups": vec![leaf.clone(); 10]})).collect::<Vec<_>>()})
+}
+
+fn bench_nested_wildcards(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nested_wildcards");
+    for n in [10, 100] {
+        let doc = nested(n);
+        let path = format_ptr!("/items/*/groups/*/spec/template/spec/containers/*/image");
+        group.bench_function(format!("replace/{n}"), |b| {
+            b.iter_batched(
+                || (doc.clone(), replace_operation(path.clone(), json!("c"))),
+                |(mut doc, op)| patch_ext(&mut doc, op).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(format!("matches/{n}"), |b| b.iter(|| matches(&path, &doc).len()));
+    }
+    group.finish();
+}
+
+fn bench_recursive_wildcard(c: &mut Criterion) {
+    let doc = nested(100);
+    let path = format_ptr!("/**/image");
+    c.bench_function("recursive_matches", |b| b.iter(|| matches(&path, &doc).len()));
+}
+
+// Many small documents patched with the same template, e.g., a set of objects in a reconcile loop
+fn bench_patch_many(c: &mut Criterion) {
+    let docs = vec![nested(1); 1000];
+    let p = Patch(vec![replace_operation(
+        format_ptr!("/items/*/groups/*/spec/template/spec/containers/*/image"),
+        json!("c"),
+    )]);
+    c.bench_function("patch_many", |b| {
+        b.iter_batched(|| docs.clone(), |mut docs| patch_many(&mut docs, &p), BatchSize::LargeInput)
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_wildcard_add,
+    bench_single_add,
+    bench_nested_wildcards,
+    bench_recursive_wildcard,
+    bench_patch_many
+);
+criterion_main!(benches);
@@ -0,0 +1,162 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+
+use crate::{
+    ExtPatchOperation,
+    Operation,
+    OperationKind,
+    PatchError,
+    PatchOptions,
+    PatchReport,
+    Pointer,
+    PointerBuf,
+    apply_all,
+    report_all,
+};
+
+// Metadata describing where an operation came from, e.g. the rule that generated it; none of it
+// affects how the operation is applied, but it's attached to any error that the operation causes
+// (see `PatchError::annotation`) and to its entry in a PatchReport.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Annotation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+// An operation together with its annotation; the annotation's fields are serialized alongside the
+// operation's, e.g. `{"op": "remove", "path": "/foo", "id": "rule-7"}`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AnnotatedOperation {
+    #[serde(flatten)]
+    pub op: ExtPatchOperation,
+    #[serde(flatten)]
+    pub annotation: Annotation,
+}
+
+impl AnnotatedOperation {
+    pub fn new(op: impl Into<ExtPatchOperation>, annotation: Annotation) -> AnnotatedOperation {
+        AnnotatedOperation { op: op.into(), annotation }
+    }
+}
+
+impl Operation for AnnotatedOperation {
+    fn kind(&self) -> OperationKind {
+        self.op.kind()
+    }
+
+    fn apply(&self, obj: &mut Value, opts: &PatchOptions) -> Result<Vec<PointerBuf>, PatchError> {
+        self.op.apply(obj, opts)
+    }
+
+    fn targets(&self) -> Vec<&Pointer> {
+        self.op.targets()
+    }
+
+    fn paths(&self) -> Vec<&Pointer> {
+        self.op.paths()
+    }
+
+    fn transfer(&self) -> Option<(&Pointer, &Pointer)> {
+        self.op.transfer()
+    }
+
+    fn annotation(&self) -> Option<&Annotation> {
+        Some(&self.annotation)
+    }
+}
+
+// Apply all of the annotated operations atomically, as in patch_ext_document; if one of them
+// fails, the error carries its annotation.
+pub fn patch_ext_annotated(obj: &mut Value, ops: &[AnnotatedOperation]) -> Result<(), PatchError> {
+    apply_all(obj, ops, &PatchOptions::default()).map(|_| ())
+}
+
+// Apply every annotated operation that it can, as in patch_ext_report; the report for each
+// operation includes its annotation.
+pub fn patch_ext_annotated_report(obj: &mut Value, ops: &[AnnotatedOperation]) -> PatchReport {
+    report_all(obj, ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        OperationOutcome,
+        PatchErrorKind,
+        add_operation,
+        format_ptr,
+        increment_operation,
+        remove_operation,
+    };
+
+    fn rule(id: &str) -> Annotation {
+        Annotation {
+            id: Some(id.into()),
+            source: Some("rules.yaml".into()),
+            ..Default::default()
+        }
+    }
+
+    #[fixture]
+    fn ops() -> Vec<AnnotatedOperation> {
+        vec![
+            AnnotatedOperation::new(add_operation(format_ptr!("/a"), json!(1)), rule("add-a")),
+            AnnotatedOperation::new(increment_operation(format_ptr!("/b"), 1.into()), rule("bump-b")),
+            AnnotatedOperation::new(remove_operation(format_ptr!("/c")), Annotation::default()),
+        ]
+    }
+
+    #[rstest]
+    fn test_patch_ext_annotated(ops: Vec<AnnotatedOperation>) {
+        let mut data = json!({"b": 1, "c": 0});
+        assert_ok!(patch_ext_annotated(&mut data, &ops));
+        assert_eq!(data, json!({"a": 1, "b": 2}));
+
+        let mut data = json!({"b": "x"});
+        let err = assert_err!(patch_ext_annotated(&mut data, &ops));
+        assert_matches!(err.kind(), PatchErrorKind::UnexpectedType);
+        assert_eq!(err.operation_index(), Some(1));
+        assert_eq!(err.annotation(), Some(&rule("bump-b")));
+        assert_eq!(data, json!({"b": "x"}));
+    }
+
+    #[rstest]
+    fn test_patch_ext_annotated_report(ops: Vec<AnnotatedOperation>) {
+        let mut data = json!({"b": "x"});
+        let report = patch_ext_annotated_report(&mut data, &ops);
+        let annotations: Vec<_> = report.operations.iter().map(|r| r.annotation.clone()).collect();
+        assert_eq!(annotations, vec![Some(rule("add-a")), Some(rule("bump-b")), Some(Annotation::default())]);
+
+        let failure = assert_some!(report.failures().next());
+        assert_eq!(failure.annotation(), Some(&rule("bump-b")));
+        assert_matches!(report.operations[2].outcome, OperationOutcome::Skipped);
+    }
+
+    #[rstest]
+    fn test_annotated_operation_serde(ops: Vec<AnnotatedOperation>) {
+        let json = json!([
+            {"op": "add", "path": "/a", "value": 1, "id": "add-a", "source": "rules.yaml"},
+            {"op": "increment", "path": "/b", "delta": 1, "id": "bump-b", "source": "rules.yaml"},
+            {"op": "remove", "path": "/c"},
+        ]);
+        assert_eq!(assert_ok!(serde_json::to_value(&ops)), json);
+        assert_eq!(assert_ok!(serde_json::from_value::<Vec<AnnotatedOperation>>(json)), ops);
+    }
+}
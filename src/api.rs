@@ -0,0 +1,153 @@
+//! A small string-in, string-out facade over the rest of the crate, for FFI or WASM bindings (e.g.
+//! a browser-based policy editor) where passing `serde_json::Value`s and borrowed pointers back
+//! and forth would need a lot of glue.  Documents and patches are plain JSON strings, and the
+//! patch can use any of the extended operations and path syntax; errors can be converted into an
+//! `ErrorInfo`, which has a stable `code` and serializes to JSON for the caller to inspect.
+
+use alloc::string::{
+    String,
+    ToString,
+};
+
+use serde::Serialize;
+
+use crate::{
+    ExtPatch,
+    PatchError,
+    PatchErrorKind,
+    Pointer,
+    from_str_strict,
+    patch_ext_document,
+};
+
+// Apply the (extended) patch to the document, and return the patched document as compact JSON.
+// Both strings are parsed with from_str_strict, so duplicate keys are rejected; a patch that's
+// valid JSON but not a valid patch returns an InvalidPatch error.  The patch is applied
+// atomically, so there's no partially-patched document to return if it fails.
+pub fn apply(doc_json: &str, patch_json: &str) -> Result<String, PatchError> {
+    let mut doc = from_str_strict(doc_json)?;
+    let patch: ExtPatch = serde_json::from_value(from_str_strict(patch_json)?)
+        .map_err(|err| PatchError::new(PatchErrorKind::InvalidPatch(err), Pointer::root()))?;
+    patch_ext_document(&mut doc, &patch)?;
+
+    // Serializing a Value can't fail, since its keys are always strings
+    Ok(serde_json::to_string(&doc).unwrap())
+}
+
+// A flattened, serializable description of a PatchError, e.g.
+// `{"code":"test_mismatch","message":"...","path":"/foo/0","operation":"test","operation_index":2}`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ErrorInfo {
+    // The stable identifier of the kind of error (see `PatchErrorKind::code`)
+    pub code: &'static str,
+
+    // The human-readable error message (the Display form of the PatchError)
+    pub message: String,
+
+    // The concrete path where the error occurred
+    pub path: String,
+
+    // The kind of operation that failed, e.g. "add", if the error came from an operation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+
+    // The index of the failed operation in the patch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation_index: Option<usize>,
+}
+
+impl From<&PatchError> for ErrorInfo {
+    fn from(err: &PatchError) -> ErrorInfo {
+        ErrorInfo {
+            code: err.kind().code(),
+            message: err.to_string(),
+            path: err.path().to_string(),
+            operation: err.operation().map(|op| op.to_string()),
+            operation_index: err.operation_index(),
+        }
+    }
+}
+
+impl ErrorInfo {
+    pub fn to_json(&self) -> String {
+        // This only contains strings and numbers, so it can always be serialized
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+
+    const DOC: &str = r#"{"spec": {"containers": [{"name": "app", "image": "app:1"}, {"name": "sidecar"}]}}"#;
+
+    #[rstest]
+    fn test_apply() {
+        let patch = r#"[
+            {"op": "replace", "path": "/spec/containers/[?name=app]/image", "value": "app:2"},
+            {"op": "add", "path": "/metadata/labels/team", "value": "core"}
+        ]"#;
+        let out = assert_ok!(apply(DOC, patch));
+        let v: serde_json::Value = assert_ok!(serde_json::from_str(&out));
+        assert_eq!(
+            v,
+            json!({
+                "spec": {"containers": [{"name": "app", "image": "app:2"}, {"name": "sidecar"}]},
+                "metadata": {"labels": {"team": "core"}},
+            })
+        );
+    }
+
+    #[rstest]
+    #[case::doc("{", "[]", "invalid_json")]
+    #[case::duplicate(r#"{"a": 1, "a": 2}"#, "[]", "duplicate_key")]
+    #[case::patch(DOC, r#"{"op": "add"}"#, "invalid_patch")]
+    #[case::op(DOC, r#"[{"op": "frobnicate", "path": "/a"}]"#, "invalid_patch")]
+    #[case::apply(
+        DOC,
+        r#"[{"op": "test", "path": "/spec/containers/*/name", "value": "app"}]"#,
+        "test_mismatch"
+    )]
+    fn test_apply_err(#[case] doc: &str, #[case] patch: &str, #[case] code: &str) {
+        let err = assert_err!(apply(doc, patch));
+        assert_eq!(ErrorInfo::from(&err).code, code);
+    }
+
+    #[rstest]
+    fn test_error_info() {
+        let patch = r#"[{"op": "add", "path": "/a", "value": 1}, {"op": "increment", "path": "/spec", "delta": 1}]"#;
+        let err = assert_err!(apply(DOC, patch));
+        let info = ErrorInfo::from(&err);
+        assert_eq!(
+            info,
+            ErrorInfo {
+                code: "unexpected_type",
+                message: err.to_string(),
+                path: "/spec".into(),
+                operation: Some("increment".into()),
+                operation_index: Some(1),
+            }
+        );
+        assert_eq!(
+            assert_ok!(serde_json::from_str::<serde_json::Value>(&info.to_json())),
+            json!({
+                "code": "unexpected_type",
+                "message": err.to_string(),
+                "path": "/spec",
+                "operation": "increment",
+                "operation_index": 1,
+            })
+        );
+
+        // Errors that don't come from an operation leave out the operation fields entirely
+        let err = assert_err!(apply("[", "[]"));
+        let json: serde_json::Value = assert_ok!(serde_json::from_str(&ErrorInfo::from(&err).to_json()));
+        assert_eq!(json.as_object().unwrap().keys().collect::<Vec<_>>(), ["code", "message", "path"]);
+    }
+}
@@ -0,0 +1,183 @@
+use alloc::vec::Vec;
+
+use serde_json::Value;
+
+use crate::{
+    OperationKind,
+    Patch,
+    PatchError,
+    PatchOperation,
+    PointerBuf,
+    add_operation,
+    check_wildcards,
+    copy_operation,
+    move_operation,
+    parse_ptr,
+    remove_operation,
+    replace_operation,
+    test_operation,
+};
+
+// PatchBuilder composes a multi-operation patch from pointer strings; each path is parsed and each
+// operation's wildcards are checked as they're added, and the first error (if any) is returned
+// from `build`, e.g.:
+//
+//
+//     let patch = PatchBuilder::new().add("/foo/*/baz", json!(1)).remove("/qux").build()?;
+#[derive(Debug, Default)]
+pub struct PatchBuilder {
+    ops: Vec<PatchOperation>,
+    err: Option<PatchError>,
+}
+
+impl PatchBuilder {
+    pub fn new() -> PatchBuilder {
+        PatchBuilder::default()
+    }
+
+    pub fn add(mut self, path: &str, value: Value) -> PatchBuilder {
+        let Some(path) = self.parse(path, OperationKind::Add) else {
+            return self;
+        };
+        self.push(add_operation(path, value))
+    }
+
+    pub fn remove(mut self, path: &str) -> PatchBuilder {
+        let Some(path) = self.parse(path, OperationKind::Remove) else {
+            return self;
+        };
+        self.push(remove_operation(path))
+    }
+
+    pub fn replace(mut self, path: &str, value: Value) -> PatchBuilder {
+        let Some(path) = self.parse(path, OperationKind::Replace) else {
+            return self;
+        };
+        self.push(replace_operation(path, value))
+    }
+
+    pub fn move_from(mut self, from: &str, path: &str) -> PatchBuilder {
+        let (Some(from), Some(path)) = (self.parse(from, OperationKind::Move), self.parse(path, OperationKind::Move))
+        else {
+            return self;
+        };
+        self.push(move_operation(from, path))
+    }
+
+    pub fn copy_from(mut self, from: &str, path: &str) -> PatchBuilder {
+        let (Some(from), Some(path)) = (self.parse(from, OperationKind::Copy), self.parse(path, OperationKind::Copy))
+        else {
+            return self;
+        };
+        self.push(copy_operation(from, path))
+    }
+
+    pub fn test(mut self, path: &str, value: Value) -> PatchBuilder {
+        let Some(path) = self.parse(path, OperationKind::Test) else {
+            return self;
+        };
+        self.push(test_operation(path, value))
+    }
+
+    pub fn push(mut self, op: PatchOperation) -> PatchBuilder {
+        if self.err.is_some() {
+            return self;
+        }
+
+        match check_wildcards(&op) {
+            Ok(()) => self.ops.push(op),
+            Err(err) => self.err = Some(err.with_operation_index(self.ops.len())),
+        }
+        self
+    }
+
+    pub fn build(self) -> Result<Patch, PatchError> {
+        match self.err {
+            Some(err) => Err(err),
+            None => Ok(Patch(self.ops)),
+        }
+    }
+
+    // Parse the path, recording the error if it's invalid; returns None if this or any previous
+    // step has failed.
+    fn parse(&mut self, path: &str, kind: OperationKind) -> Option<PointerBuf> {
+        if self.err.is_some() {
+            return None;
+        }
+
+        match parse_ptr(path, kind) {
+            Ok(p) => Some(p),
+            Err(err) => {
+                self.err = Some(err.with_operation_index(self.ops.len()));
+                None
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        PatchErrorKind,
+        format_ptr,
+    };
+
+    #[rstest]
+    fn test_builder() {
+        let p = PatchBuilder::new()
+            .add("/foo/*/baz", json!(1))
+            .remove("/qux/*")
+            .replace("/**/image", json!("foo"))
+            .move_from("/a", "/b")
+            .copy_from("/b", "/c")
+            .test("/c", json!(2))
+            .build()
+            .unwrap();
+        assert_eq!(
+            p,
+            Patch(vec![
+                add_operation(format_ptr!("/foo/*/baz"), json!(1)),
+                remove_operation(format_ptr!("/qux/*")),
+                replace_operation(format_ptr!("/**/image"), json!("foo")),
+                move_operation(format_ptr!("/a"), format_ptr!("/b")),
+                copy_operation(format_ptr!("/b"), format_ptr!("/c")),
+                test_operation(format_ptr!("/c"), json!(2)),
+            ])
+        );
+    }
+
+    #[rstest]
+    #[case::parse(PatchBuilder::new().add("/foo", json!(1)).remove("foo"), OperationKind::Remove)]
+    #[case::parse_move(PatchBuilder::new().add("/foo", json!(1)).move_from("/foo", "bar~2"), OperationKind::Move)]
+    #[case::wildcard_end(PatchBuilder::new().add("/foo", json!(1)).add("/foo/*", json!(1)), OperationKind::Add)]
+    #[case::wildcard_filter(
+        PatchBuilder::new().replace("/foo/[?a=b]", json!(1)).add("/foo/[?a=b]", json!(1)),
+        OperationKind::Add
+    )]
+    #[case::wildcard_copy(
+        PatchBuilder::new().add("/foo", json!(1)).copy_from("/foo/*/bar", "/baz"),
+        OperationKind::Copy
+    )]
+    #[case::wildcard_move(PatchBuilder::new().add("/foo", json!(1)).move_from("/foo", "/**/bar"), OperationKind::Move)]
+    fn test_builder_err(#[case] builder: PatchBuilder, #[case] kind: OperationKind) {
+        let err = builder.remove("/bar").build().unwrap_err();
+        assert_eq!(err.operation(), Some(kind));
+        assert_eq!(err.operation_index(), Some(1));
+    }
+
+    #[rstest]
+    fn test_builder_err_kind() {
+        let err = PatchBuilder::new().remove("foo").build().unwrap_err();
+        assert_matches!(err.kind(), PatchErrorKind::ParseError(_));
+
+        let err = PatchBuilder::new().add("/foo/*", json!(1)).build().unwrap_err();
+        assert_matches!(err.kind(), PatchErrorKind::InvalidWildcard);
+        assert_eq!(err.path(), format_ptr!("/foo/*"));
+    }
+}
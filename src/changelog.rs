@@ -0,0 +1,280 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::{
+    Value,
+    json,
+};
+
+use crate::{
+    ExtPatch,
+    Operation,
+    OperationKind,
+    Patch,
+    PatchError,
+    PatchOptions,
+    Pointer,
+    PointerBuf,
+    Snapshot,
+    apply_operation,
+    matches,
+    resolve_ext,
+    rollback,
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    // A missing parent was created (as an empty object or array) so that a value could be added
+    // inside of it
+    Created,
+    Added,
+    Replaced,
+    Removed,
+}
+
+// A single change to the document, at a concrete path (after wildcard expansion): `old_value` is
+// missing for a created or added value, and `new_value` is missing for a removed one.  As in
+// patch_ext_traced, removed array elements are reported using the index they had before the
+// operation was applied.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ChangeEvent {
+    pub path: PointerBuf,
+    pub kind: ChangeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_value: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_value: Option<Value>,
+}
+
+// Like patch_ext_all, but returns a log of every change that the patch made, in the order that the
+// operations made them; every wildcard match is reported separately, and every auto-created parent
+// is reported (as Created) before the value that was added inside of it.  If the patch fails, the
+// document is rolled back and no events are returned.
+pub fn patch_ext_logged(obj: &mut Value, p: &Patch) -> Result<Vec<ChangeEvent>, PatchError> {
+    log_all(obj, p)
+}
+
+// Like patch_ext_logged, for an extended patch (as in patch_ext_document)
+pub fn patch_ext_document_logged(obj: &mut Value, p: &ExtPatch) -> Result<Vec<ChangeEvent>, PatchError> {
+    log_all(obj, p)
+}
+
+fn log_all<O: Operation>(obj: &mut Value, ops: &[O]) -> Result<Vec<ChangeEvent>, PatchError> {
+    let mut snapshots = vec![];
+    let mut events = vec![];
+    for (i, op) in ops.iter().enumerate() {
+        let snapshot = Snapshot::new(obj, op);
+
+        // The values that a move removes, which it reports alongside the values that it adds; moving
+        // a value onto itself doesn't change anything, so it isn't reported at all
+        let sources: Vec<PointerBuf> = match (op.kind(), op.transfer()) {
+            (OperationKind::Move, Some((from, _))) => matches(from, obj).into_iter().map(|(p, _)| p).collect(),
+            _ => vec![],
+        };
+        let noop = op.kind() == OperationKind::Move && op.transfer().is_some_and(|(from, path)| from == path);
+
+        let res = apply_operation(obj, op, &PatchOptions::default());
+        if let (Ok(modified), false) = (&res, noop) {
+            log_changes(obj, op.kind(), &snapshot, modified, &sources, &mut events);
+        }
+        snapshots.push(snapshot);
+        if let Err(err) = res {
+            rollback(obj, snapshots);
+            return Err(err.with_operation_index(i));
+        }
+    }
+    Ok(events)
+}
+
+// Turn the paths modified by an operation into events, by comparing the values at each path before
+// (in the snapshot of the operation's targets) and after the operation.  Locations can't be
+// compared directly in arrays, since inserting or removing an element shifts the rest of them, so
+// the kind of operation decides whether an array element was inserted or removed instead of
+// replaced.
+fn log_changes(
+    obj: &Value,
+    kind: OperationKind,
+    snapshot: &Snapshot,
+    modified: &[PointerBuf],
+    sources: &[PointerBuf],
+    events: &mut Vec<ChangeEvent>,
+) {
+    // Every modified path is at (or inside of) one of the snapshotted targets
+    let old_at = |p: &Pointer| snapshot.old_value(p).flatten();
+    let removes = matches!(kind, OperationKind::Remove | OperationKind::RemoveIfExists | OperationKind::RemoveAll);
+    let inserts = matches!(
+        kind,
+        OperationKind::Add
+            | OperationKind::AddIfAbsent
+            | OperationKind::Copy
+            | OperationKind::Move
+            | OperationKind::AddFrom
+    );
+
+    let mut created: Vec<PointerBuf> = vec![];
+    for path in modified {
+        if removes || sources.contains(path) {
+            events.push(ChangeEvent {
+                path: path.clone(),
+                kind: ChangeKind::Removed,
+                old_value: old_at(path).cloned(),
+                new_value: None,
+            });
+            continue;
+        }
+
+        let inserted = inserts && path.parent().and_then(|p| resolve_ext(obj, p)).is_some_and(Value::is_array);
+        let old_value = if inserted { None } else { old_at(path).cloned() };
+        if old_value.is_none() {
+            for parent in missing_parents(snapshot, path) {
+                let new_value = match resolve_ext(obj, &parent) {
+                    Some(Value::Array(_)) => json!([]),
+                    Some(Value::Object(_)) => json!({}),
+                    _ => continue,
+                };
+                if !created.contains(&parent) {
+                    events.push(ChangeEvent {
+                        path: parent.clone(),
+                        kind: ChangeKind::Created,
+                        old_value: None,
+                        new_value: Some(new_value),
+                    });
+                    created.push(parent);
+                }
+            }
+        }
+
+        events.push(ChangeEvent {
+            path: path.clone(),
+            kind: if old_value.is_some() { ChangeKind::Replaced } else { ChangeKind::Added },
+            old_value,
+            new_value: resolve_ext(obj, path).cloned(),
+        });
+    }
+}
+
+// The ancestors of the path that didn't exist before the operation, from the outermost one in; the
+// ones that the snapshot doesn't cover are above the operation's targets, so they already existed
+fn missing_parents(snapshot: &Snapshot, path: &Pointer) -> Vec<PointerBuf> {
+    let mut parent = PointerBuf::root();
+    let mut missing = vec![];
+    for t in path.tokens().take(path.count().saturating_sub(1)) {
+        parent.push_back(t);
+        if snapshot.old_value(&parent) == Some(None) {
+            missing.push(parent.clone());
+        }
+    }
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        PatchErrorKind,
+        add_operation,
+        format_ptr,
+        increment_operation,
+        move_operation,
+        remove_operation,
+        replace_operation,
+    };
+
+    fn event(path: PointerBuf, kind: ChangeKind, old_value: Option<Value>, new_value: Option<Value>) -> ChangeEvent {
+        ChangeEvent { path, kind, old_value, new_value }
+    }
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"foo": [{"bar": 1}, {"baz": 2}], "a": "x"})
+    }
+
+    #[rstest]
+    fn test_patch_ext_logged_created(mut data: Value) {
+        let p = Patch(vec![add_operation(format_ptr!("/foo/*/new/qux"), json!(0))]);
+        let events = assert_ok!(patch_ext_logged(&mut data, &p));
+        assert_eq!(
+            events,
+            vec![
+                event(format_ptr!("/foo/0/new"), ChangeKind::Created, None, Some(json!({}))),
+                event(format_ptr!("/foo/0/new/qux"), ChangeKind::Added, None, Some(json!(0))),
+                event(format_ptr!("/foo/1/new"), ChangeKind::Created, None, Some(json!({}))),
+                event(format_ptr!("/foo/1/new/qux"), ChangeKind::Added, None, Some(json!(0))),
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_patch_ext_logged(mut data: Value) {
+        let p = Patch(vec![
+            replace_operation(format_ptr!("/foo/0/bar"), json!(5)),
+            add_operation(format_ptr!("/foo/0"), json!("first")),
+            remove_operation(format_ptr!("/foo/2")),
+            move_operation(format_ptr!("/a"), format_ptr!("/foo/1/a")),
+            add_operation(format_ptr!("/b/c"), json!("y")),
+        ]);
+        let events = assert_ok!(patch_ext_logged(&mut data, &p));
+        assert_eq!(data, json!({"foo": ["first", {"bar": 5, "a": "x"}], "b": {"c": "y"}}));
+        assert_eq!(
+            events,
+            vec![
+                event(format_ptr!("/foo/0/bar"), ChangeKind::Replaced, Some(json!(1)), Some(json!(5))),
+                event(format_ptr!("/foo/0"), ChangeKind::Added, None, Some(json!("first"))),
+                event(format_ptr!("/foo/2"), ChangeKind::Removed, Some(json!({"baz": 2})), None),
+                event(format_ptr!("/a"), ChangeKind::Removed, Some(json!("x")), None),
+                event(format_ptr!("/foo/1/a"), ChangeKind::Added, None, Some(json!("x"))),
+                event(format_ptr!("/b"), ChangeKind::Created, None, Some(json!({}))),
+                event(format_ptr!("/b/c"), ChangeKind::Added, None, Some(json!("y"))),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::concrete(format_ptr!("/a"))]
+    #[case::wildcard(format_ptr!("/foo/*"))]
+    fn test_patch_ext_logged_move_onto_itself(mut data: Value, #[case] path: PointerBuf) {
+        let orig = data.clone();
+        let p = Patch(vec![move_operation(path.clone(), path)]);
+        let events = assert_ok!(patch_ext_logged(&mut data, &p));
+        assert_is_empty!(events);
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_patch_ext_document_logged(mut data: Value) {
+        let p = ExtPatch(vec![increment_operation(format_ptr!("/foo/[?baz=2]/baz"), 1.into())]);
+        let events = assert_ok!(patch_ext_document_logged(&mut data, &p));
+        assert_eq!(
+            events,
+            vec![event(format_ptr!("/foo/1/baz"), ChangeKind::Replaced, Some(json!(2)), Some(json!(3)))]
+        );
+    }
+
+    #[rstest]
+    fn test_patch_ext_logged_error(mut data: Value) {
+        let orig = data.clone();
+        let p =
+            Patch(vec![add_operation(format_ptr!("/new"), json!(1)), replace_operation(format_ptr!("/a/b"), json!(2))]);
+        let err = assert_err!(patch_ext_logged(&mut data, &p));
+        assert_matches!(err.kind(), PatchErrorKind::UnexpectedType);
+        assert_eq!(err.operation_index(), Some(1));
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_change_event_serde() {
+        let e = event(format_ptr!("/foo/0"), ChangeKind::Removed, Some(json!(1)), None);
+        let json = json!({"path": "/foo/0", "kind": "removed", "old_value": 1});
+        assert_eq!(assert_ok!(serde_json::to_value(&e)), json);
+        assert_eq!(assert_ok!(serde_json::from_value::<ChangeEvent>(json)), e);
+    }
+}
@@ -0,0 +1,194 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{
+    CopyOperation,
+    ExtToken,
+    MoveOperation,
+    Patch,
+    PatchOperation,
+    Pointer,
+    PointerBuf,
+    Segment,
+    TestOperation,
+    Wildcard,
+    array_index,
+    capture_ref,
+    glob,
+    segments,
+};
+
+// Two operations (one from each patch) that reference overlapping locations in the document, where
+// at least one of them writes to that location
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Conflict {
+    // The index of the operation in the first patch, and the path that it references
+    pub a: usize,
+    pub a_path: PointerBuf,
+
+    // The index of the operation in the second patch, and the path that it references
+    pub b: usize,
+    pub b_path: PointerBuf,
+}
+
+// Find every pair of operations in the two patches that could clobber each other, without looking
+// at any document: two paths overlap if one of them could reference a value inside of (or equal
+// to) a value that the other one references.  Wildcards are compared against each other, so e.g.
+// `/foo/*/bar` overlaps with `/foo/1:3`, but not with `/foo/0/baz`.  Only the locations are
+// compared, not (for example) whether removing an array element would shift another operation's
+// index; identical operations don't conflict, and neither do two operations that only read.
+pub fn conflicts(a: &Patch, b: &Patch) -> Vec<Conflict> {
+    let mut res = vec![];
+    for (i, op_a) in a.iter().enumerate() {
+        for (j, op_b) in b.iter().enumerate() {
+            if op_a == op_b {
+                continue;
+            }
+            for (p, p_writes) in accesses(op_a) {
+                for (q, q_writes) in accesses(op_b) {
+                    if (p_writes || q_writes) && could_overlap(&segments(p), &segments(q)) {
+                        res.push(Conflict { a: i, a_path: p.to_buf(), b: j, b_path: q.to_buf() });
+                    }
+                }
+            }
+        }
+    }
+    res
+}
+
+// Every path that the operation references, and whether it's written to
+fn accesses(op: &PatchOperation) -> Vec<(&Pointer, bool)> {
+    match op {
+        PatchOperation::Move(MoveOperation { from, path }) => vec![(from, true), (path, true)],
+        PatchOperation::Copy(CopyOperation { from, path }) => vec![(from, false), (path, true)],
+        PatchOperation::Test(TestOperation { path, .. }) => vec![(path, false)],
+        op => vec![(op.path(), true)],
+    }
+}
+
+// Whether there's some document where a value referenced by one of the paths is (or is inside of) a
+// value referenced by the other, i.e., one path could be a prefix of the other
+fn could_overlap(p: &[Segment], q: &[Segment]) -> bool {
+    let (Some(((_, s), p_rest)), Some(((_, t), q_rest))) = (p.split_first(), q.split_first()) else {
+        return true;
+    };
+
+    // A recursive wildcard can match nothing, or it can absorb the next segment of the other path
+    if matches!(s, ExtToken::Wildcard(Wildcard::Recursive)) {
+        return could_overlap(p_rest, q) || could_overlap(p, q_rest);
+    }
+    if matches!(t, ExtToken::Wildcard(Wildcard::Recursive)) {
+        return could_overlap(p, q_rest) || could_overlap(p_rest, q);
+    }
+    compatible(s, t) && could_overlap(p_rest, q_rest)
+}
+
+// Whether the two (non-recursive) tokens could reference the same child; `-` references an array
+// element that doesn't exist yet, so it never overlaps with anything, and a numbered wildcard
+// reference (in a move or copy destination) could be anything.
+fn compatible(s: &ExtToken, t: &ExtToken) -> bool {
+    match (s, t) {
+        (ExtToken::Key(a), _) | (_, ExtToken::Key(a)) if a.decoded() == "-" => false,
+        (ExtToken::Key(a), _) | (_, ExtToken::Key(a)) if capture_ref(a).is_some() => true,
+        (ExtToken::Key(a), ExtToken::Key(b)) => a == b,
+        (ExtToken::Key(k), ExtToken::Wildcard(w)) | (ExtToken::Wildcard(w), ExtToken::Key(k)) => match w {
+            Wildcard::Slice(start, end) => match array_index(k) {
+                Some(i) => start.is_none_or(|s| i >= s) && end.is_none_or(|e| i < e),
+                // A negative index could reference anything
+                None => k.decoded().starts_with('-'),
+            },
+            Wildcard::Glob(pattern, ignore_case) => glob(pattern, &k.decoded(), *ignore_case),
+            _ => true,
+        },
+        // Globs only select object keys, and slices only select array elements
+        (ExtToken::Wildcard(Wildcard::Glob(..)), ExtToken::Wildcard(Wildcard::Slice(..)))
+        | (ExtToken::Wildcard(Wildcard::Slice(..)), ExtToken::Wildcard(Wildcard::Glob(..))) => false,
+        (ExtToken::Wildcard(Wildcard::Slice(s1, e1)), ExtToken::Wildcard(Wildcard::Slice(s2, e2))) => {
+            let start = s1.unwrap_or(0).max(s2.unwrap_or(0));
+            let end = match (e1, e2) {
+                (Some(a), Some(b)) => Some(*a.min(b)),
+                (a, b) => a.or(*b),
+            };
+            end.is_none_or(|e| start < e)
+        },
+        (ExtToken::Wildcard(_), ExtToken::Wildcard(_)) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        add_operation,
+        copy_operation,
+        format_ptr,
+        remove_operation,
+        replace_operation,
+        test_operation,
+    };
+
+    #[rstest]
+    #[case::same_path("/foo/bar", "/foo/bar", true)]
+    #[case::prefix("/foo", "/foo/bar/baz", true)]
+    #[case::siblings("/foo/bar", "/foo/baz", false)]
+    #[case::wildcard("/foo/*/bar", "/foo/0/bar", true)]
+    #[case::wildcard_sibling("/foo/*/bar", "/foo/0/baz", false)]
+    #[case::slice_index("/foo/1:3", "/foo/2/bar", true)]
+    #[case::slice_outside("/foo/1:3", "/foo/3/bar", false)]
+    #[case::slice_key("/foo/1:3", "/foo/bar", false)]
+    #[case::slices("/foo/:2", "/foo/1:", true)]
+    #[case::disjoint_slices("/foo/:2", "/foo/2:4", false)]
+    #[case::filter("/foo/[?name=a]/bar", "/foo/*/bar", true)]
+    #[case::recursive("/**/image", "/spec/containers/0/image", true)]
+    #[case::recursive_prefix("/spec/**/image", "/spec", true)]
+    #[case::recursive_mismatch("/spec/**/image", "/status/image", false)]
+    #[case::recursive_both("/**/a/b", "/x/**/b", true)]
+    #[case::append("/foo/-", "/foo/*", false)]
+    #[case::escaped("/foo/\\*", "/foo/bar", false)]
+    #[case::capture_ref("/foo/*1/bar", "/foo/baz", true)]
+    #[case::glob("/labels/app*", "/labels/application", true)]
+    #[case::glob_mismatch("/labels/app*", "/labels/name", false)]
+    #[case::glob_ignore_case("/labels/(?i)APP*", "/labels/application", true)]
+    #[case::glob_slice("/foo/a*", "/foo/:2", false)]
+    fn test_could_overlap(#[case] p: &str, #[case] q: &str, #[case] expected: bool) {
+        let (p, q) = (PointerBuf::parse(p).unwrap(), PointerBuf::parse(q).unwrap());
+        assert_eq!(could_overlap(&segments(&p), &segments(&q)), expected);
+        assert_eq!(could_overlap(&segments(&q), &segments(&p)), expected);
+    }
+
+    #[rstest]
+    fn test_conflicts() {
+        let a = Patch(vec![
+            replace_operation(format_ptr!("/spec/containers/*/image"), json!("a")),
+            add_operation(format_ptr!("/metadata/labels/x"), json!("1")),
+            test_operation(format_ptr!("/spec/replicas"), json!(1)),
+        ]);
+        let b = Patch(vec![
+            add_operation(format_ptr!("/metadata/labels/x"), json!("1")),
+            remove_operation(format_ptr!("/spec/containers/1")),
+            copy_operation(format_ptr!("/spec/replicas"), format_ptr!("/status/replicas")),
+            replace_operation(format_ptr!("/spec/replicas"), json!(2)),
+        ]);
+        assert_eq!(
+            conflicts(&a, &b),
+            vec![
+                Conflict {
+                    a: 0,
+                    a_path: format_ptr!("/spec/containers/*/image"),
+                    b: 1,
+                    b_path: format_ptr!("/spec/containers/1"),
+                },
+                Conflict {
+                    a: 2,
+                    a_path: format_ptr!("/spec/replicas"),
+                    b: 3,
+                    b_path: format_ptr!("/spec/replicas")
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Generate patches that transform one JSON document into another.  Unlike `json_patch::diff`, the
+//! patches generated here can (optionally) use the `*` operator to describe a change that is made
+//! identically to every element of an array; for example, if every entry in `/containers` has its
+//! `image` field replaced with the same value, the generated patch will contain a single
+//! `replace /containers/*/image` operation instead of one operation per index.
+//!
+//! All of the generated patches are intended to be applied with `patch_ext` (or `patch_ext_all`).
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde_json::{
+    Map,
+    Value,
+};
+
+use crate::{
+    Patch,
+    PatchOperation,
+    Pointer,
+    PointerBuf,
+    add_operation,
+    key_token,
+    remove_operation,
+    replace_operation,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    // If every element of an array is changed in exactly the same way, emit a single operation
+    // using the `*` operator instead of one operation per element.
+    pub collapse_wildcards: bool,
+}
+
+pub fn diff(old: &Value, new: &Value) -> Patch {
+    diff_with(old, new, &DiffOptions::default())
+}
+
+pub fn diff_with(old: &Value, new: &Value, opts: &DiffOptions) -> Patch {
+    let mut ops = vec![];
+    diff_impl(old, new, opts, &mut PointerBuf::new(), &mut ops);
+    Patch(ops)
+}
+
+pub(crate) fn diff_impl(
+    old: &Value,
+    new: &Value,
+    opts: &DiffOptions,
+    path: &mut PointerBuf,
+    ops: &mut Vec<PatchOperation>,
+) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => diff_object(old_map, new_map, opts, path, ops),
+        (Value::Array(old_vec), Value::Array(new_vec)) => diff_array(old_vec, new_vec, opts, path, ops),
+        _ if old == new => (),
+        _ => ops.push(replace_operation(path.clone(), new.clone())),
+    }
+}
+
+fn diff_object(
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+    opts: &DiffOptions,
+    path: &mut PointerBuf,
+    ops: &mut Vec<PatchOperation>,
+) {
+    for (k, old_val) in old {
+        path.push_back(key_token(k));
+        match new.get(k) {
+            Some(new_val) => diff_impl(old_val, new_val, opts, path, ops),
+            None => ops.push(remove_operation(path.clone())),
+        }
+        path.pop_back();
+    }
+
+    for (k, new_val) in new.iter().filter(|(k, _)| !old.contains_key(*k)) {
+        ops.push(add_operation(path.with_trailing_token(key_token(k)), new_val.clone()));
+    }
+}
+
+fn diff_array(old: &[Value], new: &[Value], opts: &DiffOptions, path: &mut PointerBuf, ops: &mut Vec<PatchOperation>) {
+    let common = old.len().min(new.len());
+
+    // Remove any trailing elements first, starting from the end so that the indices of the
+    // elements we haven't removed yet stay the same
+    for i in (common..old.len()).rev() {
+        ops.push(remove_operation(path.with_trailing_token(i)));
+    }
+
+    // Compute the diff of each element relative to the element itself, so that we can tell whether
+    // all of the elements changed in the same way
+    let elem_diffs: Vec<_> = old
+        .iter()
+        .zip(new)
+        .map(|(o, n)| {
+            let mut elem_ops = vec![];
+            diff_impl(o, n, opts, &mut PointerBuf::new(), &mut elem_ops);
+            elem_ops
+        })
+        .collect();
+
+    if opts.collapse_wildcards && can_collapse(&elem_diffs) {
+        let wildcard_path = path.with_trailing_token("*");
+        ops.extend(elem_diffs[0].iter().map(|op| with_prefix(op, &wildcard_path)));
+    } else {
+        for (i, elem_ops) in elem_diffs.iter().enumerate() {
+            let elem_path = path.with_trailing_token(i);
+            ops.extend(elem_ops.iter().map(|op| with_prefix(op, &elem_path)));
+        }
+    }
+
+    for v in &new[common..] {
+        ops.push(add_operation(path.with_trailing_token("-"), v.clone()));
+    }
+}
+
+// We only collapse if there's more than one element, and all of the elements changed in the same
+// way; a change to the element itself has to be a replace, since a replace can end with a `*` (and
+// replaces every element of the array), but an add can't.
+fn can_collapse(elem_diffs: &[Vec<PatchOperation>]) -> bool {
+    let Some(first) = elem_diffs.first() else {
+        return false;
+    };
+    elem_diffs.len() > 1
+        && !first.is_empty()
+        && first.iter().all(|op| !op.path().is_root() || matches!(op, PatchOperation::Replace(_)))
+        && elem_diffs.iter().all(|d| d == first)
+}
+
+fn with_prefix(op: &PatchOperation, prefix: &Pointer) -> PatchOperation {
+    match op {
+        PatchOperation::Add(op) => add_operation(prefix.concat(&op.path), op.value.clone()),
+        PatchOperation::Remove(op) => remove_operation(prefix.concat(&op.path)),
+        PatchOperation::Replace(op) => replace_operation(prefix.concat(&op.path), op.value.clone()),
+        _ => unreachable!("diff only generates add, remove, and replace operations"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        format_ptr,
+        patch_ext_all,
+    };
+
+    #[fixture]
+    fn old() -> Value {
+        json!({
+            "foo": [
+                {"baz": {"buzz": 0}, "qux": 1},
+                {"baz": {"quzz": 1}, "qux": 1},
+                {"baz": {"fixx": 2}, "qux": 1},
+            ],
+            "bar": "asdf",
+        })
+    }
+
+    #[rstest]
+    #[case::objects(json!({"foo": [{"baz": 1}], "bar": "hjkl", "quzz": true}))]
+    #[case::longer_array(json!({"foo": [{"baz": {"buzz": 0}, "qux": 1}, 1, 2, {}, {"a": "b"}], "bar": "asdf"}))]
+    #[case::shorter_array(json!({"foo": [{"baz": {"buzz": 1}, "qux": 2}], "bar": "asdf"}))]
+    #[case::root(json!([1, 2, 3]))]
+    #[case::wildcard_keys(json!({"*": 1, "**": {"1:3": 2}, "\\x": 3, "bar": "asdf"}))]
+    fn test_diff_round_trip(mut old: Value, #[case] new: Value) {
+        for opts in [DiffOptions::default(), DiffOptions { collapse_wildcards: true }] {
+            let mut doc = old.clone();
+            let p = diff_with(&doc, &new, &opts);
+            patch_ext_all(&mut doc, &p).unwrap();
+            assert_eq!(doc, new);
+        }
+
+        let p = diff(&old, &new);
+        patch_ext_all(&mut old, &p).unwrap();
+        assert_eq!(old, new);
+    }
+
+    #[rstest]
+    fn test_diff_no_changes(old: Value) {
+        assert_eq!(diff(&old, &old), Patch(vec![]));
+    }
+
+    #[rstest]
+    fn test_diff_collapse(old: Value) {
+        let mut new = old.clone();
+        for v in new["foo"].as_array_mut().unwrap() {
+            v["qux"] = json!(2);
+            v["image"] = json!("foo");
+        }
+        let opts = DiffOptions { collapse_wildcards: true };
+        assert_eq!(
+            diff_with(&old, &new, &opts),
+            Patch(vec![
+                replace_operation(format_ptr!("/foo/*/qux"), json!(2)),
+                add_operation(format_ptr!("/foo/*/image"), json!("foo")),
+            ])
+        );
+    }
+
+    #[rstest]
+    fn test_diff_no_collapse(old: Value) {
+        let mut new = old.clone();
+        new["foo"][0]["qux"] = json!(2);
+        new["foo"][1]["qux"] = json!(2);
+        let opts = DiffOptions { collapse_wildcards: true };
+        assert_eq!(
+            diff_with(&old, &new, &opts),
+            Patch(vec![
+                replace_operation(format_ptr!("/foo/0/qux"), json!(2)),
+                replace_operation(format_ptr!("/foo/1/qux"), json!(2)),
+            ])
+        );
+    }
+
+    #[rstest]
+    fn test_diff_collapse_elements() {
+        let mut old = json!({"foo": [1, 2]});
+        let new = json!({"foo": [3, 3]});
+        let opts = DiffOptions { collapse_wildcards: true };
+        let p = diff_with(&old, &new, &opts);
+        assert_eq!(p, Patch(vec![replace_operation(format_ptr!("/foo/*"), json!(3))]));
+        patch_ext_all(&mut old, &p).unwrap();
+        assert_eq!(old, new);
+    }
+}
@@ -1,21 +1,96 @@
-use jsonptr::assign;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+
+use json_patch::PatchOperation;
 use jsonptr::index::ParseIndexError;
 use jsonptr::resolve::ResolveError;
+use jsonptr::{
+    ParseError,
+    Pointer,
+    PointerBuf,
+    RichParseError,
+    assign,
+};
+use serde_json::Value;
 pub use thiserror::Error;
 
+use crate::Annotation;
+
+// PatchError carries enough context to map a failure back to the operation that caused it: the
+// concrete path (after wildcard expansion) where the failure occurred, the kind of operation that
+// was being applied, and, when applying a whole patch, the index of the failing operation.
+#[derive(Debug)]
+pub struct PatchError {
+    kind: PatchErrorKind,
+    path: PointerBuf,
+    operation: Option<OperationKind>,
+    operation_index: Option<usize>,
+    annotation: Option<Box<Annotation>>,
+}
+
+// Some of the variants only exist with the corresponding feature enabled, and features are unified
+// across the whole dependency graph, so matches on this have to include a wildcard arm
 #[derive(Debug, Error)]
-pub enum PatchError {
+#[non_exhaustive]
+pub enum PatchErrorKind {
     #[error("index out of bounds at {0}")]
     OutOfBounds(usize),
 
-    #[error("unexpected type at {0}")]
-    UnexpectedType(String),
+    #[error("negative index out of bounds at -{0}")]
+    NegativeIndexOutOfBounds(usize),
+
+    #[error("unexpected type")]
+    UnexpectedType,
+
+    #[error("the target path does not exist")]
+    TargetDoesNotExist,
+
+    #[error("wildcards are not allowed here")]
+    InvalidWildcard,
+
+    #[error("wildcard matched more than {0} values")]
+    TooManyMatches(usize),
+
+    #[error("recursive wildcard descended more than {0} levels")]
+    MaxDepthExceeded(usize),
+
+    #[error("would create more than {0} missing parents")]
+    AutocreateLimitExceeded(usize),
+
+    #[error("path has more than {0} segments")]
+    PointerTooDeep(usize),
+
+    #[error("array index {0} is larger than the limit")]
+    IndexTooLarge(usize),
+
+    #[error("document would grow by more than {0} bytes")]
+    SizeLimitExceeded(usize),
+
+    #[error("undefined template variable: {0}")]
+    UndefinedVariable(String),
+
+    #[error("numeric overflow")]
+    NumericOverflow,
 
-    #[error("the target path does not exist: {0}")]
-    TargetDoesNotExist(String),
+    #[error("conflicts with operation {0}")]
+    ConflictingOperation(usize),
+
+    #[error("savepoint was already rolled back past")]
+    StaleSavepoint,
+
+    // A test operation failed at the (concrete) path of the error: `actual` is the value that was
+    // found there, or None for a test using TestMode::Any, where none of the values that the path
+    // matched were equal to the expected value.  The values are boxed to keep PatchError small.
+    #[error("test failed: expected {expected}{}", found(.actual))]
+    TestMismatch {
+        expected: Box<Value>,
+        actual: Option<Box<Value>>,
+    },
 
     #[error("json_patch error: {0}")]
-    JsonPatchError(#[from] json_patch::PatchError),
+    JsonPatchError(#[from] json_patch::PatchErrorKind),
 
     #[error("json path resolve error: {0}")]
     ResolveError(#[from] ResolveError),
@@ -25,4 +100,240 @@ pub enum PatchError {
 
     #[error("index parse error: {0}")]
     ParseIndexError(#[from] ParseIndexError),
+
+    #[error("json path parse error: {0}")]
+    ParseError(#[from] ParseError),
+
+    #[error("could not serialize object: {0}")]
+    SerializeError(serde_json::Error),
+
+    #[error("patched document could not be deserialized: {0}")]
+    DeserializeError(serde_json::Error),
+
+    #[error("could not read or write document: {0}")]
+    StreamError(serde_json::Error),
+
+    #[error("invalid JSON: {0}")]
+    InvalidJson(serde_json::Error),
+
+    #[error("duplicate key: {0}")]
+    DuplicateKey(String),
+
+    #[error("invalid patch: {0}")]
+    InvalidPatch(serde_json::Error),
+
+    #[cfg(feature = "jsonpath")]
+    #[error("JSONPath parse error: {0}")]
+    JsonPathError(serde_json_path::ParseError),
+
+    #[cfg(feature = "regex")]
+    #[error("regex error: {0}")]
+    RegexError(regex::Error),
+
+    #[cfg(feature = "schema")]
+    #[error("patched document does not match the schema: {0}")]
+    SchemaViolation(String),
+
+    #[cfg(feature = "yaml")]
+    #[error("YAML error: {0}")]
+    YamlError(serde_yaml::Error),
+
+    #[error("the `{0}` feature is not enabled")]
+    FeatureDisabled(&'static str),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OperationKind {
+    Add,
+    Remove,
+    Replace,
+    Move,
+    Copy,
+    Test,
+    AddIfAbsent,
+    ReplaceIfEquals,
+    RemoveIfExists,
+    RemoveAll,
+    AddByKey,
+    AddFrom,
+    ReplaceFrom,
+    Increment,
+    Append,
+    Merge,
+    StrReplace,
+}
+
+impl PatchErrorKind {
+    // A short, stable identifier for the kind of error (the variant name in snake_case), for
+    // callers that can't match on the enum itself, e.g. FFI or WASM bindings (see `api`)
+    pub fn code(&self) -> &'static str {
+        match self {
+            PatchErrorKind::OutOfBounds(_) => "out_of_bounds",
+            PatchErrorKind::NegativeIndexOutOfBounds(_) => "negative_index_out_of_bounds",
+            PatchErrorKind::UnexpectedType => "unexpected_type",
+            PatchErrorKind::TargetDoesNotExist => "target_does_not_exist",
+            PatchErrorKind::InvalidWildcard => "invalid_wildcard",
+            PatchErrorKind::TooManyMatches(_) => "too_many_matches",
+            PatchErrorKind::MaxDepthExceeded(_) => "max_depth_exceeded",
+            PatchErrorKind::AutocreateLimitExceeded(_) => "autocreate_limit_exceeded",
+            PatchErrorKind::PointerTooDeep(_) => "pointer_too_deep",
+            PatchErrorKind::IndexTooLarge(_) => "index_too_large",
+            PatchErrorKind::SizeLimitExceeded(_) => "size_limit_exceeded",
+            PatchErrorKind::UndefinedVariable(_) => "undefined_variable",
+            PatchErrorKind::NumericOverflow => "numeric_overflow",
+            PatchErrorKind::ConflictingOperation(_) => "conflicting_operation",
+            PatchErrorKind::StaleSavepoint => "stale_savepoint",
+            PatchErrorKind::TestMismatch { .. } => "test_mismatch",
+            PatchErrorKind::JsonPatchError(_) => "json_patch_error",
+            PatchErrorKind::ResolveError(_) => "resolve_error",
+            PatchErrorKind::AssignError(_) => "assign_error",
+            PatchErrorKind::ParseIndexError(_) => "parse_index_error",
+            PatchErrorKind::ParseError(_) => "parse_error",
+            PatchErrorKind::SerializeError(_) => "serialize_error",
+            PatchErrorKind::DeserializeError(_) => "deserialize_error",
+            PatchErrorKind::StreamError(_) => "stream_error",
+            PatchErrorKind::InvalidJson(_) => "invalid_json",
+            PatchErrorKind::DuplicateKey(_) => "duplicate_key",
+            PatchErrorKind::InvalidPatch(_) => "invalid_patch",
+            #[cfg(feature = "jsonpath")]
+            PatchErrorKind::JsonPathError(_) => "json_path_error",
+            #[cfg(feature = "regex")]
+            PatchErrorKind::RegexError(_) => "regex_error",
+            #[cfg(feature = "schema")]
+            PatchErrorKind::SchemaViolation(_) => "schema_violation",
+            #[cfg(feature = "yaml")]
+            PatchErrorKind::YamlError(_) => "yaml_error",
+            PatchErrorKind::FeatureDisabled(_) => "feature_disabled",
+        }
+    }
+}
+
+impl PatchError {
+    pub(crate) fn new(kind: PatchErrorKind, path: &Pointer) -> PatchError {
+        PatchError {
+            kind,
+            path: path.to_buf(),
+            operation: None,
+            operation_index: None,
+            annotation: None,
+        }
+    }
+
+    // Convenience function for use with map_err, e.g., `path.resolve(v).map_err(PatchError::at(path))`
+    pub(crate) fn at<E: Into<PatchErrorKind>>(path: &Pointer) -> impl FnOnce(E) -> PatchError + '_ {
+        move |err| PatchError::new(err.into(), path)
+    }
+
+    // Errors that occur while walking a subtree of the document have paths that are relative to
+    // the root of the subtree, so this converts them to be relative to the root of the document.
+    pub(crate) fn with_prefix(mut self, prefix: &Pointer) -> PatchError {
+        self.path = prefix.concat(&self.path);
+        self
+    }
+
+    pub(crate) fn with_operation(mut self, op: OperationKind) -> PatchError {
+        self.operation = Some(op);
+        self
+    }
+
+    pub(crate) fn with_operation_index(mut self, idx: usize) -> PatchError {
+        self.operation_index = Some(idx);
+        self
+    }
+
+    pub(crate) fn with_annotation(mut self, annotation: Option<&Annotation>) -> PatchError {
+        self.annotation = annotation.cloned().map(Box::new);
+        self
+    }
+
+    pub fn kind(&self) -> &PatchErrorKind {
+        &self.kind
+    }
+
+    pub fn path(&self) -> &Pointer {
+        &self.path
+    }
+
+    pub fn operation(&self) -> Option<OperationKind> {
+        self.operation
+    }
+
+    pub fn operation_index(&self) -> Option<usize> {
+        self.operation_index
+    }
+
+    // The annotation of the operation that failed, if it was an AnnotatedOperation
+    pub fn annotation(&self) -> Option<&Annotation> {
+        self.annotation.as_deref()
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.operation, self.operation_index) {
+            (Some(op), Some(idx)) => write!(f, "{op} operation {idx} failed at path '{}': {}", self.path, self.kind),
+            (Some(op), None) => write!(f, "{op} operation failed at path '{}': {}", self.path, self.kind),
+            _ => write!(f, "patch failed at path '{}': {}", self.path, self.kind),
+        }
+    }
+}
+
+impl core::error::Error for PatchError {}
+
+fn found(actual: &Option<Box<Value>>) -> String {
+    actual.as_ref().map(|v| format!(", found {v}")).unwrap_or_default()
+}
+
+// The upstream error already knows what path it failed at; the operation index is always relative
+// to the (single-operation) slice we pass in, so we don't keep it.
+impl From<json_patch::PatchError> for PatchError {
+    fn from(err: json_patch::PatchError) -> PatchError {
+        PatchError::new(err.kind.into(), &err.path)
+    }
+}
+
+// Parse errors aren't tied to a location in the document, so they're reported at the root
+impl From<RichParseError> for PatchError {
+    fn from(err: RichParseError) -> PatchError {
+        let (err, _) = err.decompose();
+        PatchError::new(PatchErrorKind::ParseError(err), Pointer::root())
+    }
+}
+
+impl From<&PatchOperation> for OperationKind {
+    fn from(op: &PatchOperation) -> OperationKind {
+        match op {
+            PatchOperation::Add(_) => OperationKind::Add,
+            PatchOperation::Remove(_) => OperationKind::Remove,
+            PatchOperation::Replace(_) => OperationKind::Replace,
+            PatchOperation::Move(_) => OperationKind::Move,
+            PatchOperation::Copy(_) => OperationKind::Copy,
+            PatchOperation::Test(_) => OperationKind::Test,
+        }
+    }
+}
+
+impl fmt::Display for OperationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OperationKind::Add => "add",
+            OperationKind::Remove => "remove",
+            OperationKind::Replace => "replace",
+            OperationKind::Move => "move",
+            OperationKind::Copy => "copy",
+            OperationKind::Test => "test",
+            OperationKind::AddIfAbsent => "add_if_absent",
+            OperationKind::ReplaceIfEquals => "replace_if_equals",
+            OperationKind::RemoveIfExists => "remove_if_exists",
+            OperationKind::RemoveAll => "remove_all",
+            OperationKind::AddByKey => "add_by_key",
+            OperationKind::AddFrom => "add_from",
+            OperationKind::ReplaceFrom => "replace_from",
+            OperationKind::Increment => "increment",
+            OperationKind::Append => "append",
+            OperationKind::Merge => "merge",
+            OperationKind::StrReplace => "str_replace",
+        };
+        write!(f, "{name}")
+    }
 }
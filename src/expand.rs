@@ -0,0 +1,259 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde_json::Value;
+
+use crate::{
+    AddOperation,
+    CopyOperation,
+    MoveOperation,
+    PatchError,
+    PatchOperation,
+    PatchOptions,
+    PointerBuf,
+    RemoveOperation,
+    ReplaceOperation,
+    TestOperation,
+    add_operation,
+    add_or_replace,
+    aligned_matches,
+    apply_operation,
+    copy_operation,
+    document_order,
+    matches,
+    move_operation,
+    remove,
+    remove_operation,
+    replace_operation,
+    resolve_ext,
+    test_operation,
+    unescape_path,
+    wildcard_count,
+};
+
+// Rewrite an operation that uses the extended path syntax as the equivalent list of plain RFC 6902
+// operations against the current document, e.g. to send to a server that only understands standard
+// JSON patches.  Wildcards are expanded into one operation per match, negative indices and `-` are
+// resolved to concrete indices, and any parents that an add would auto-create are added explicitly.
+// Unlike every other path returned by this crate, the paths in the result are not escaped, since a
+// standard JSON patch would treat `\*` as a literal key.  If the operation doesn't apply to the
+// document, this returns the same error that applying it would.
+pub fn expand_operation(obj: &Value, op: &PatchOperation) -> Result<Vec<PatchOperation>, PatchError> {
+    let opts = PatchOptions::default();
+    let mut after = obj.clone();
+    let modified = apply_operation(&mut after, op, &opts)?;
+
+    let ops = match op {
+        PatchOperation::Add(AddOperation { value, .. }) => {
+            add_operations(obj, &after, modified.into_iter().map(|p| (p, value.clone())))
+        },
+        PatchOperation::Replace(ReplaceOperation { value, .. }) => {
+            modified.into_iter().map(|p| replace_operation(unescape_path(&p), value.clone())).collect()
+        },
+        // The removed paths are reported using the indices they had in the original document, so
+        // remove them in reverse document order; that way removing an array element doesn't shift
+        // the indices of the ones that come before it
+        PatchOperation::Remove(RemoveOperation { .. }) => {
+            let mut removed = modified;
+            removed.sort_by(|a, b| document_order(b, a));
+            removed.dedup();
+            removed.into_iter().map(|p| remove_operation(unescape_path(&p))).collect()
+        },
+        PatchOperation::Test(TestOperation { path, value }) => matches(path, obj)
+            .into_iter()
+            .map(|(p, _)| test_operation(unescape_path(&p), value.clone()))
+            .collect(),
+        PatchOperation::Move(MoveOperation { from, path }) | PatchOperation::Copy(CopyOperation { from, path })
+            if wildcard_count(from) == 0 && wildcard_count(path) == 0 =>
+        {
+            let (from, path) = (unescape_path(from), unescape_path(path));
+            match op {
+                PatchOperation::Move(_) => vec![move_operation(from, path)],
+                _ => vec![copy_operation(from, path)],
+            }
+        },
+        // This mirrors move_or_copy: every source is read (and, for a move, removed) before any of
+        // the destinations are written.  The destinations are written as adds with the copied
+        // value, since a standard move or copy could read a source that an earlier destination
+        // already overwrote.
+        PatchOperation::Move(MoveOperation { from, path }) | PatchOperation::Copy(CopyOperation { from, path }) => {
+            let transfers: Vec<_> = aligned_matches(obj, from, path, &opts)?
+                .into_iter()
+                .map(|(src, dest, v)| (src, dest, v.clone()))
+                .collect();
+
+            let mut ops = vec![];
+            let mut before = obj.clone();
+            if matches!(op, PatchOperation::Move(_)) {
+                for (src, ..) in transfers.iter().rev() {
+                    remove(&mut before, src, &opts)?;
+                    ops.push(remove_operation(unescape_path(src)));
+                }
+            }
+
+            let mut after = before.clone();
+            let mut writes = vec![];
+            for (_, dest, v) in transfers {
+                for p in add_or_replace(&mut after, &dest, v.clone(), false, &opts)? {
+                    writes.push((p, v.clone()));
+                }
+            }
+            ops.extend(add_operations(&before, &after, writes));
+            ops
+        },
+    };
+    Ok(ops)
+}
+
+// Turn the concrete paths written by an add (which transformed `before` into `after`) into standard
+// add operations: if some of the target's parents were auto-created, the outermost one is added
+// instead (with everything that was created inside of it), and any later targets inside of it are
+// skipped.
+fn add_operations(
+    before: &Value,
+    after: &Value,
+    writes: impl IntoIterator<Item = (PointerBuf, Value)>,
+) -> Vec<PatchOperation> {
+    let mut created: Vec<PointerBuf> = vec![];
+    let mut ops = vec![];
+    for (path, value) in writes {
+        if created.iter().any(|c| path.starts_with(c)) {
+            continue;
+        }
+
+        let mut parent = PointerBuf::root();
+        let mut missing = None;
+        for t in path.tokens().take(path.tokens().count().saturating_sub(1)) {
+            parent.push_back(t);
+            if resolve_ext(before, &parent).is_none() {
+                missing = Some(parent);
+                break;
+            }
+        }
+
+        match missing {
+            Some(parent) => {
+                let value = resolve_ext(after, &parent).cloned().unwrap_or_default();
+                ops.push(add_operation(unescape_path(&parent), value));
+                created.push(parent);
+            },
+            None => ops.push(add_operation(unescape_path(&path), value)),
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        PatchErrorKind,
+        format_ptr,
+        patch_ext,
+        patch_upstream,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({
+            "foo": [{"bar": 1}, {"bar": 2, "baz": 3}, {"qux": 4}],
+            "*": {"bar": 5},
+        })
+    }
+
+    #[rstest]
+    #[case::add(
+        add_operation(format_ptr!("/foo/*/bar"), json!(0)),
+        vec![
+            add_operation(format_ptr!("/foo/0/bar"), json!(0)),
+            add_operation(format_ptr!("/foo/1/bar"), json!(0)),
+            add_operation(format_ptr!("/foo/2/bar"), json!(0)),
+        ],
+    )]
+    #[case::add_creates_parents(
+        add_operation(format_ptr!("/foo/1:/new/a"), json!(0)),
+        vec![
+            add_operation(format_ptr!("/foo/1/new"), json!({"a": 0})),
+            add_operation(format_ptr!("/foo/2/new"), json!({"a": 0})),
+        ],
+    )]
+    #[case::add_append(
+        add_operation(format_ptr!("/foo/-/bar"), json!(0)),
+        vec![add_operation(format_ptr!("/foo/3"), json!({"bar": 0}))],
+    )]
+    #[case::replace(
+        replace_operation(format_ptr!("/foo/[?bar=2]/baz"), json!(0)),
+        vec![replace_operation(format_ptr!("/foo/1/baz"), json!(0))],
+    )]
+    #[case::replace_negative(
+        replace_operation(format_ptr!("/foo/-1"), json!(0)),
+        vec![replace_operation(format_ptr!("/foo/2"), json!(0))],
+    )]
+    #[case::remove(
+        remove_operation(format_ptr!("/**/bar")),
+        vec![
+            remove_operation(format_ptr!("/foo/1/bar")),
+            remove_operation(format_ptr!("/foo/0/bar")),
+            remove_operation(format_ptr!("/*/bar")),
+        ],
+    )]
+    #[case::remove_negative(
+        remove_operation(format_ptr!("/foo/-1")),
+        vec![remove_operation(format_ptr!("/foo/2"))],
+    )]
+    #[case::remove_elements(
+        remove_operation(format_ptr!("/foo/:2")),
+        vec![remove_operation(format_ptr!("/foo/1")), remove_operation(format_ptr!("/foo/0"))],
+    )]
+    #[case::test(
+        test_operation(format_ptr!("/foo/[?baz=3]/bar"), json!(2)),
+        vec![test_operation(format_ptr!("/foo/1/bar"), json!(2))],
+    )]
+    #[case::escaped(
+        replace_operation(format_ptr!("/\\*/bar"), json!(0)),
+        vec![replace_operation(format_ptr!("/*/bar"), json!(0))],
+    )]
+    #[case::copy(
+        copy_operation(format_ptr!("/foo/*/bar"), format_ptr!("/copies/*")),
+        vec![
+            add_operation(format_ptr!("/copies"), json!({"0": 1, "1": 2})),
+        ],
+    )]
+    #[case::move_elements(
+        move_operation(format_ptr!("/foo/*/bar"), format_ptr!("/foo/*/moved")),
+        vec![
+            remove_operation(format_ptr!("/foo/1/bar")),
+            remove_operation(format_ptr!("/foo/0/bar")),
+            add_operation(format_ptr!("/foo/0/moved"), json!(1)),
+            add_operation(format_ptr!("/foo/1/moved"), json!(2)),
+        ],
+    )]
+    #[case::plain_move(
+        move_operation(format_ptr!("/foo/0"), format_ptr!("/first")),
+        vec![move_operation(format_ptr!("/foo/0"), format_ptr!("/first"))],
+    )]
+    fn test_expand_operation(data: Value, #[case] op: PatchOperation, #[case] expected: Vec<PatchOperation>) {
+        let ops = assert_ok!(expand_operation(&data, &op));
+        assert_eq!(ops, expected);
+
+        // The expanded operations are a standard JSON patch that does the same thing
+        let mut doc = data.clone();
+        for op in &ops {
+            assert_ok!(patch_upstream(&mut doc, op));
+        }
+        let mut expected_doc = data;
+        assert_ok!(patch_ext(&mut expected_doc, op));
+        assert_eq!(doc, expected_doc);
+    }
+
+    #[rstest]
+    fn test_expand_operation_error(data: Value) {
+        let err = assert_err!(expand_operation(&data, &test_operation(format_ptr!("/foo/*/bar"), json!(1))));
+        assert_matches!(err.kind(), PatchErrorKind::TestMismatch { .. });
+    }
+}
@@ -0,0 +1,945 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{
+    format,
+    vec,
+};
+use core::ops::Deref;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::{
+    Map,
+    Number,
+    Value,
+};
+
+use crate::merge_key::{
+    AddByKeyOperation,
+    add_by_key,
+};
+use crate::merge_patch::apply_merge_patch;
+use crate::{
+    AddOperation,
+    CopyOperation,
+    Index,
+    MoveOperation,
+    Operation,
+    OperationKind,
+    Patch,
+    PatchError,
+    PatchErrorKind,
+    PatchMode,
+    PatchOperation,
+    PatchOptions,
+    Pointer,
+    PointerBuf,
+    RemoveOperation,
+    ReplaceOperation,
+    TestMode,
+    TestOperation,
+    add_or_replace,
+    aligned_matches,
+    apply_all,
+    bounded_matches,
+    document_order,
+    ends_with_wildcard,
+    insert_value,
+    is_recursive,
+    literal,
+    move_or_copy,
+    patch_ext_helper,
+    remove,
+    resolve_ext_mut,
+    resolve_index,
+    template,
+    test_values,
+    wildcard_count,
+};
+
+// An extended patch document; this uses the same wire format as an RFC 6902 patch (and so can be
+// deserialized from any standard patch), but paths may use all of the extensions supported by this
+// crate, and additional (non-standard) operation types may be added here in the future.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ExtPatch(pub Vec<ExtPatchOperation>);
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum ExtPatchOperation {
+    Add(AddOperation),
+    Remove(RemoveOperation),
+    Replace(ReplaceOperation),
+    Move(MoveOperation),
+    Copy(CopyOperation),
+    Test(ExtTestOperation),
+
+    // Conditional operations: these skip any target where the condition doesn't hold, instead of
+    // failing the whole patch the way a failed test would
+    #[serde(rename = "add_if_absent")]
+    AddIfAbsent(AddOperation),
+    #[serde(rename = "replace_if_equals")]
+    ReplaceIfEquals(ReplaceIfEqualsOperation),
+    #[serde(rename = "remove_if_exists")]
+    RemoveIfExists(RemoveOperation),
+
+    // Remove everything that any of the paths match, as a single operation
+    #[serde(rename = "remove_all")]
+    RemoveAll(RemoveAllOperation),
+
+    // Upsert an array element identified by a merge key (see the `merge_key` module)
+    #[serde(rename = "add_by_key")]
+    AddByKey(AddByKeyOperation),
+
+    // Operations whose value is computed from another part of the document
+    #[serde(rename = "add_from")]
+    AddFrom(AddFromOperation),
+    #[serde(rename = "replace_from")]
+    ReplaceFrom(AddFromOperation),
+
+    // Operations that modify the existing values at the path in place
+    Increment(IncrementOperation),
+    Append(AppendOperation),
+    Merge(MergeOperation),
+    #[serde(rename = "str_replace")]
+    StrReplace(StrReplaceOperation),
+}
+
+// Replace the value at the path, but only if it's currently equal to `expected`
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ReplaceIfEqualsOperation {
+    pub path: PointerBuf,
+    pub expected: Value,
+    pub value: Value,
+}
+
+// Remove every value that any of the paths match (skipping paths that don't match anything, as in
+// remove_if_exists).  All of the paths are expanded against the document before anything is
+// removed, so e.g. `/foo/*/deprecated` and `/foo/[?name=old]` can be removed together without an
+// earlier removal shifting the array indices that a later path refers to, which a sequence of
+// separate remove operations can't do.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RemoveAllOperation {
+    pub paths: Vec<PointerBuf>,
+}
+
+// Add (or replace) the value at `path` with one fetched from `from`.  Like a copy, each wildcard in
+// `path` is filled in with whatever the corresponding wildcard in `from` matched; if a `value` is
+// given, it's used as a template (see the `template` module) in which `{{from}}` is the fetched
+// value, so the fetched value can be embedded inside of a larger one.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AddFromOperation {
+    pub from: PointerBuf,
+    pub path: PointerBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+// Add `delta` to the number at the path; integers stay integers (and overflowing is an error), but
+// if either number is a float, so is the result.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct IncrementOperation {
+    pub path: PointerBuf,
+    pub delta: Number,
+}
+
+// Append all of the values to the end of the array at the path
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AppendOperation {
+    pub path: PointerBuf,
+    pub values: Vec<Value>,
+}
+
+// Deep-merge the value into the value at the path, using JSON Merge Patch (RFC 7386) semantics:
+// objects are merged recursively, `null` removes a field, and anything else is replaced.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MergeOperation {
+    pub path: PointerBuf,
+    pub value: Value,
+}
+
+// Edit the string at the path, replacing the part(s) of it that match `pattern` (see StrMatch); a
+// string that doesn't match is left alone.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StrReplaceOperation {
+    pub path: PointerBuf,
+    pub pattern: String,
+    pub replacement: String,
+    #[serde(default, skip_serializing_if = "is_default_match")]
+    pub mode: StrMatch,
+}
+
+// How the pattern in a StrReplaceOperation is matched: `Substring` replaces every occurrence of it,
+// `Prefix` and `Suffix` only replace it at the start or end of the string, and `Regex` treats it as
+// a regular expression (replacing every match, with `$1` etc. in the replacement referring to the
+// capture groups).  `Regex` requires the `regex` feature.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StrMatch {
+    #[default]
+    Substring,
+    Prefix,
+    Suffix,
+    Regex,
+}
+
+// Like a TestOperation, but with control over how paths containing wildcards are evaluated; the
+// mode is omitted from the serialized form when it's the default, so that standard test operations
+// round-trip unchanged.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ExtTestOperation {
+    pub path: PointerBuf,
+    pub value: Value,
+    #[serde(default, skip_serializing_if = "is_default_mode")]
+    pub mode: TestMode,
+}
+
+pub fn add_if_absent_operation(path: PointerBuf, value: Value) -> ExtPatchOperation {
+    ExtPatchOperation::AddIfAbsent(AddOperation { path, value })
+}
+
+pub fn replace_if_equals_operation(path: PointerBuf, expected: Value, value: Value) -> ExtPatchOperation {
+    ExtPatchOperation::ReplaceIfEquals(ReplaceIfEqualsOperation { path, expected, value })
+}
+
+pub fn remove_if_exists_operation(path: PointerBuf) -> ExtPatchOperation {
+    ExtPatchOperation::RemoveIfExists(RemoveOperation { path })
+}
+
+pub fn remove_all_operation(paths: Vec<PointerBuf>) -> ExtPatchOperation {
+    ExtPatchOperation::RemoveAll(RemoveAllOperation { paths })
+}
+
+pub fn add_from_operation(path: PointerBuf, from: PointerBuf) -> ExtPatchOperation {
+    ExtPatchOperation::AddFrom(AddFromOperation { from, path, value: None })
+}
+
+pub fn replace_from_operation(path: PointerBuf, from: PointerBuf) -> ExtPatchOperation {
+    ExtPatchOperation::ReplaceFrom(AddFromOperation { from, path, value: None })
+}
+
+pub fn increment_operation(path: PointerBuf, delta: Number) -> ExtPatchOperation {
+    ExtPatchOperation::Increment(IncrementOperation { path, delta })
+}
+
+pub fn append_operation(path: PointerBuf, values: Vec<Value>) -> ExtPatchOperation {
+    ExtPatchOperation::Append(AppendOperation { path, values })
+}
+
+pub fn merge_operation(path: PointerBuf, value: Value) -> ExtPatchOperation {
+    ExtPatchOperation::Merge(MergeOperation { path, value })
+}
+
+pub fn str_replace_operation(path: PointerBuf, pattern: &str, replacement: &str, mode: StrMatch) -> ExtPatchOperation {
+    ExtPatchOperation::StrReplace(StrReplaceOperation {
+        path,
+        pattern: pattern.into(),
+        replacement: replacement.into(),
+        mode,
+    })
+}
+
+// Apply all of the operations in the extended patch, in order; like patch_ext_all, if any of
+// them fail, the document is rolled back to its original state.
+pub fn patch_ext_document(obj: &mut Value, p: &ExtPatch) -> Result<(), PatchError> {
+    apply_all(obj, p, &PatchOptions::default()).map(|_| ())
+}
+
+impl Deref for ExtPatch {
+    type Target = [ExtPatchOperation];
+
+    fn deref(&self) -> &[ExtPatchOperation] {
+        &self.0
+    }
+}
+
+impl From<Patch> for ExtPatch {
+    fn from(p: Patch) -> ExtPatch {
+        ExtPatch(p.0.into_iter().map(|op| op.into()).collect())
+    }
+}
+
+impl From<PatchOperation> for ExtPatchOperation {
+    fn from(op: PatchOperation) -> ExtPatchOperation {
+        match op {
+            PatchOperation::Add(op) => ExtPatchOperation::Add(op),
+            PatchOperation::Remove(op) => ExtPatchOperation::Remove(op),
+            PatchOperation::Replace(op) => ExtPatchOperation::Replace(op),
+            PatchOperation::Move(op) => ExtPatchOperation::Move(op),
+            PatchOperation::Copy(op) => ExtPatchOperation::Copy(op),
+            PatchOperation::Test(op) => ExtPatchOperation::Test(op.into()),
+        }
+    }
+}
+
+impl From<TestOperation> for ExtTestOperation {
+    fn from(op: TestOperation) -> ExtTestOperation {
+        ExtTestOperation {
+            path: op.path,
+            value: op.value,
+            mode: TestMode::All,
+        }
+    }
+}
+
+impl Operation for ExtPatchOperation {
+    fn kind(&self) -> OperationKind {
+        match self {
+            ExtPatchOperation::Add(_) => OperationKind::Add,
+            ExtPatchOperation::Remove(_) => OperationKind::Remove,
+            ExtPatchOperation::Replace(_) => OperationKind::Replace,
+            ExtPatchOperation::Move(_) => OperationKind::Move,
+            ExtPatchOperation::Copy(_) => OperationKind::Copy,
+            ExtPatchOperation::Test(_) => OperationKind::Test,
+            ExtPatchOperation::AddIfAbsent(_) => OperationKind::AddIfAbsent,
+            ExtPatchOperation::ReplaceIfEquals(_) => OperationKind::ReplaceIfEquals,
+            ExtPatchOperation::RemoveIfExists(_) => OperationKind::RemoveIfExists,
+            ExtPatchOperation::RemoveAll(_) => OperationKind::RemoveAll,
+            ExtPatchOperation::AddByKey(_) => OperationKind::AddByKey,
+            ExtPatchOperation::AddFrom(_) => OperationKind::AddFrom,
+            ExtPatchOperation::ReplaceFrom(_) => OperationKind::ReplaceFrom,
+            ExtPatchOperation::Increment(_) => OperationKind::Increment,
+            ExtPatchOperation::Append(_) => OperationKind::Append,
+            ExtPatchOperation::Merge(_) => OperationKind::Merge,
+            ExtPatchOperation::StrReplace(_) => OperationKind::StrReplace,
+        }
+    }
+
+    fn apply(&self, obj: &mut Value, opts: &PatchOptions) -> Result<Vec<PointerBuf>, PatchError> {
+        match self {
+            ExtPatchOperation::Add(op) => add_or_replace(obj, &op.path, op.value.clone(), false, opts),
+            ExtPatchOperation::Remove(op) => remove(obj, &op.path, opts),
+            ExtPatchOperation::Replace(op) => add_or_replace(obj, &op.path, op.value.clone(), true, opts),
+            ExtPatchOperation::Move(op) => move_or_copy(obj, &op.from, &op.path, true, opts),
+            ExtPatchOperation::Copy(op) => move_or_copy(obj, &op.from, &op.path, false, opts),
+            ExtPatchOperation::Test(op) => test_values(obj, &op.path, &op.value, op.mode, opts).map(|_| vec![]),
+            ExtPatchOperation::AddIfAbsent(op) => add_if_absent(obj, &op.path, &op.value, opts),
+            ExtPatchOperation::ReplaceIfEquals(op) => {
+                let targets = bounded_matches(&op.path, obj, opts)?.into_iter().filter(|(_, v)| **v == op.expected);
+                let targets: Vec<_> = targets.map(|(p, _)| p).collect();
+                let mut modified = vec![];
+                for p in targets {
+                    modified.extend(add_or_replace(obj, &p, op.value.clone(), true, opts)?);
+                }
+                Ok(modified)
+            },
+            ExtPatchOperation::RemoveIfExists(op) => {
+                // Remove the matches in reverse order, so that removing an array element doesn't
+                // shift the indices of the matches that come after it
+                let targets: Vec<_> = bounded_matches(&op.path, obj, opts)?.into_iter().rev().map(|(p, _)| p).collect();
+                let mut modified = vec![];
+                for p in targets {
+                    modified.extend(remove(obj, &p, opts)?);
+                }
+                Ok(modified)
+            },
+            ExtPatchOperation::RemoveAll(op) => remove_all(obj, &op.paths, opts),
+            ExtPatchOperation::AddByKey(op) => add_by_key(obj, op, opts),
+            ExtPatchOperation::AddFrom(op) => add_from(obj, op, false, opts),
+            ExtPatchOperation::ReplaceFrom(op) => add_from(obj, op, true, opts),
+            ExtPatchOperation::Increment(op) => modify_matches(obj, &op.path, opts, |v| increment(v, &op.delta)),
+            ExtPatchOperation::Append(op) => modify_matches(obj, &op.path, opts, |v| match v {
+                Value::Array(vec) => {
+                    vec.extend(op.values.iter().cloned());
+                    Ok(())
+                },
+                _ => Err(PatchErrorKind::UnexpectedType),
+            }),
+            ExtPatchOperation::Merge(op) => modify_matches(obj, &op.path, opts, |v| {
+                apply_merge_patch(v, &op.value);
+                Ok(())
+            }),
+            ExtPatchOperation::StrReplace(op) => str_replace(obj, op, opts),
+        }
+    }
+
+    fn targets(&self) -> Vec<&Pointer> {
+        match self {
+            ExtPatchOperation::Add(AddOperation { path, .. })
+            | ExtPatchOperation::Remove(RemoveOperation { path })
+            | ExtPatchOperation::Replace(ReplaceOperation { path, .. })
+            | ExtPatchOperation::Copy(CopyOperation { path, .. })
+            | ExtPatchOperation::AddIfAbsent(AddOperation { path, .. })
+            | ExtPatchOperation::ReplaceIfEquals(ReplaceIfEqualsOperation { path, .. })
+            | ExtPatchOperation::RemoveIfExists(RemoveOperation { path })
+            | ExtPatchOperation::AddByKey(AddByKeyOperation { path, .. })
+            | ExtPatchOperation::AddFrom(AddFromOperation { path, .. })
+            | ExtPatchOperation::ReplaceFrom(AddFromOperation { path, .. })
+            | ExtPatchOperation::Increment(IncrementOperation { path, .. })
+            | ExtPatchOperation::Append(AppendOperation { path, .. })
+            | ExtPatchOperation::Merge(MergeOperation { path, .. })
+            | ExtPatchOperation::StrReplace(StrReplaceOperation { path, .. }) => vec![path],
+            ExtPatchOperation::Move(MoveOperation { from, path }) => vec![from, path],
+            ExtPatchOperation::RemoveAll(RemoveAllOperation { paths }) => paths.iter().map(|p| p.as_ref()).collect(),
+            ExtPatchOperation::Test(_) => vec![],
+        }
+    }
+
+    fn paths(&self) -> Vec<&Pointer> {
+        match self {
+            ExtPatchOperation::Copy(CopyOperation { from, path })
+            | ExtPatchOperation::AddFrom(AddFromOperation { from, path, .. })
+            | ExtPatchOperation::ReplaceFrom(AddFromOperation { from, path, .. }) => vec![from, path],
+            ExtPatchOperation::Test(ExtTestOperation { path, .. }) => vec![path],
+            op => op.targets(),
+        }
+    }
+
+    fn transfer(&self) -> Option<(&Pointer, &Pointer)> {
+        match self {
+            ExtPatchOperation::Move(MoveOperation { from, path })
+            | ExtPatchOperation::Copy(CopyOperation { from, path })
+            | ExtPatchOperation::AddFrom(AddFromOperation { from, path, .. })
+            | ExtPatchOperation::ReplaceFrom(AddFromOperation { from, path, .. }) => Some((from, path)),
+            _ => None,
+        }
+    }
+}
+
+// Add the value at every target of the path that doesn't exist yet (creating parents as needed); an
+// array element is only absent if the index is past the end of the array (or is `-`), so a negative
+// index that's in bounds counts as present.  Like add, the path can't end with a wildcard.  Since a
+// recursive wildcard only ever matches existing values, it never adds anything.
+fn add_if_absent(
+    obj: &mut Value,
+    path: &Pointer,
+    value: &Value,
+    opts: &PatchOptions,
+) -> Result<Vec<PointerBuf>, PatchError> {
+    if ends_with_wildcard(path) {
+        return Err(PatchError::new(PatchErrorKind::InvalidWildcard, path));
+    }
+    let Some((subpath, tail)) = path.split_back() else {
+        return Ok(vec![]);
+    };
+    if is_recursive(path) {
+        return Ok(vec![]);
+    }
+
+    let mode = if opts.allow_autocreate_parents { PatchMode::Create } else { PatchMode::Error };
+    let mut modified = vec![];
+    for (parent, v) in patch_ext_helper(subpath, obj, mode, opts)? {
+        let key = literal(&tail);
+        let exists = match v {
+            Value::Object(map) => map.contains_key(key.decoded().as_ref()),
+            Value::Array(vec) => {
+                resolve_index(&key, vec.len()).is_ok_and(|idx| matches!(idx, Index::Num(i) if i < vec.len()))
+            },
+            _ => false,
+        };
+        if !exists {
+            modified.push(insert_value(parent, v, &tail, value.clone(), false, opts)?);
+        }
+    }
+    Ok(modified)
+}
+
+// The matches are removed in reverse document order, so that removing an array element doesn't
+// shift the indices of the matches that come before it; a match inside of another match is
+// removed along with it, so it isn't removed (or reported) separately.
+fn remove_all(obj: &mut Value, paths: &[PointerBuf], opts: &PatchOptions) -> Result<Vec<PointerBuf>, PatchError> {
+    let mut matched = vec![];
+    for path in paths {
+        matched.extend(bounded_matches(path, obj, opts)?.into_iter().map(|(p, _)| p));
+    }
+    matched.sort_by(|a, b| document_order(a, b));
+
+    let mut targets: Vec<PointerBuf> = vec![];
+    for p in matched {
+        if !targets.last().is_some_and(|t| p.starts_with(t)) {
+            targets.push(p);
+        }
+    }
+
+    let mut modified = vec![];
+    for p in targets.into_iter().rev() {
+        modified.extend(remove(obj, &p, opts)?);
+    }
+    Ok(modified)
+}
+
+// All of the values are fetched (and rendered) before any of them are written, so a target can't
+// affect what gets written to the targets after it.  A `from` path without wildcards must exist.
+fn add_from(
+    obj: &mut Value,
+    op: &AddFromOperation,
+    replace: bool,
+    opts: &PatchOptions,
+) -> Result<Vec<PointerBuf>, PatchError> {
+    let matched = aligned_matches(obj, &op.from, &op.path, opts)?;
+    if matched.is_empty() && wildcard_count(&op.from) == 0 {
+        return Err(PatchError::new(PatchErrorKind::TargetDoesNotExist, &op.from));
+    }
+
+    let mut writes = vec![];
+    for (_, dest, v) in matched {
+        let value = match &op.value {
+            Some(value) => {
+                let vars = Map::from_iter([(String::from("from"), v.clone())]);
+                template::render_value(value, &vars, obj).map_err(|kind| PatchError::new(kind, &dest))?
+            },
+            None => v.clone(),
+        };
+        writes.push((dest, value));
+    }
+
+    let mut modified = vec![];
+    for (dest, value) in writes {
+        modified.extend(add_or_replace(obj, &dest, value, replace, opts)?);
+    }
+    Ok(modified)
+}
+
+// Modify every value that the path matches in place; a path without wildcards must exist.  A
+// recursive wildcard can match a value inside of another match, which may no longer exist by the
+// time we get to it, so those are skipped.
+fn modify_matches<F>(
+    obj: &mut Value,
+    path: &Pointer,
+    opts: &PatchOptions,
+    mut f: F,
+) -> Result<Vec<PointerBuf>, PatchError>
+where
+    F: FnMut(&mut Value) -> Result<(), PatchErrorKind>,
+{
+    let targets: Vec<_> = bounded_matches(path, obj, opts)?.into_iter().map(|(p, _)| p).collect();
+    if targets.is_empty() && wildcard_count(path) == 0 {
+        return Err(PatchError::new(PatchErrorKind::TargetDoesNotExist, path));
+    }
+
+    let mut modified = vec![];
+    for p in targets {
+        if let Some(v) = resolve_ext_mut(obj, &p) {
+            f(v).map_err(PatchError::at(&p))?;
+            modified.push(p);
+        }
+    }
+    Ok(modified)
+}
+
+fn increment(v: &mut Value, delta: &Number) -> Result<(), PatchErrorKind> {
+    let Value::Number(n) = v else {
+        return Err(PatchErrorKind::UnexpectedType);
+    };
+
+    let sum = match (as_integer(n), as_integer(delta)) {
+        (Some(a), Some(b)) => {
+            let sum = a.checked_add(b).ok_or(PatchErrorKind::NumericOverflow)?;
+            i64::try_from(sum)
+                .map(Number::from)
+                .or_else(|_| u64::try_from(sum).map(Number::from))
+                .map_err(|_| PatchErrorKind::NumericOverflow)?
+        },
+        // Every number can be represented as an f64 (possibly with some loss of precision)
+        _ => {
+            let sum = n.as_f64().unwrap_or_default() + delta.as_f64().unwrap_or_default();
+            Number::from_f64(sum).ok_or(PatchErrorKind::NumericOverflow)?
+        },
+    };
+    *n = sum;
+    Ok(())
+}
+
+fn str_replace(obj: &mut Value, op: &StrReplaceOperation, opts: &PatchOptions) -> Result<Vec<PointerBuf>, PatchError> {
+    // Compile the regex once up front, rather than once per target
+    #[cfg(feature = "regex")]
+    let re = match op.mode {
+        StrMatch::Regex => Some(
+            regex::Regex::new(&op.pattern).map_err(|err| PatchError::new(PatchErrorKind::RegexError(err), &op.path))?,
+        ),
+        _ => None,
+    };
+    #[cfg(not(feature = "regex"))]
+    if op.mode == StrMatch::Regex {
+        return Err(PatchError::new(PatchErrorKind::FeatureDisabled("regex"), &op.path));
+    }
+
+    modify_matches(obj, &op.path, opts, |v| {
+        let Value::String(s) = v else {
+            return Err(PatchErrorKind::UnexpectedType);
+        };
+        let edited = match op.mode {
+            StrMatch::Substring => Some(s.replace(&op.pattern, &op.replacement)),
+            StrMatch::Prefix => s.strip_prefix(&op.pattern).map(|rest| format!("{}{rest}", op.replacement)),
+            StrMatch::Suffix => s.strip_suffix(&op.pattern).map(|rest| format!("{rest}{}", op.replacement)),
+            #[cfg(feature = "regex")]
+            StrMatch::Regex => re.as_ref().map(|re| re.replace_all(s, op.replacement.as_str()).into_owned()),
+            #[cfg(not(feature = "regex"))]
+            StrMatch::Regex => None,
+        };
+        if let Some(edited) = edited {
+            *s = edited;
+        }
+        Ok(())
+    })
+}
+
+fn as_integer(n: &Number) -> Option<i128> {
+    n.as_i64().map(i128::from).or_else(|| n.as_u64().map(i128::from))
+}
+
+fn is_default_mode(mode: &TestMode) -> bool {
+    *mode == TestMode::default()
+}
+
+fn is_default_match(mode: &StrMatch) -> bool {
+    *mode == StrMatch::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        add_operation,
+        format_ptr,
+        remove_operation,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"containers": [{"name": "app", "image": "a"}, {"name": "sidecar", "image": "b"}]})
+    }
+
+    #[rstest]
+    fn test_ext_patch_deserialize(mut data: Value) {
+        let p: ExtPatch = serde_json::from_value(json!([
+            {"op": "replace", "path": "/containers/*/image", "value": "c"},
+            {"op": "remove", "path": "/containers/[?name=sidecar]"},
+            {"op": "copy", "from": "/containers/0", "path": "/main"},
+            {"op": "test", "path": "/main/name", "value": "app"},
+        ]))
+        .unwrap();
+        let res = patch_ext_document(&mut data, &p);
+        assert_ok!(res);
+        assert_eq!(data, json!({"containers": [{"name": "app", "image": "c"}], "main": {"name": "app", "image": "c"}}));
+    }
+
+    #[rstest]
+    fn test_ext_patch_round_trip() {
+        let p = ExtPatch::from(Patch(vec![
+            add_operation(format_ptr!("/foo/*/bar"), json!(1)),
+            remove_operation(format_ptr!("/**/baz")),
+        ]));
+        let s = serde_json::to_value(&p).unwrap();
+        assert_eq!(
+            s,
+            json!([
+                {"op": "add", "path": "/foo/*/bar", "value": 1},
+                {"op": "remove", "path": "/**/baz"},
+            ])
+        );
+        assert_eq!(serde_json::from_value::<ExtPatch>(s).unwrap(), p);
+    }
+
+    #[rstest]
+    fn test_ext_patch_rollback(mut data: Value) {
+        let orig = data.clone();
+        let p: ExtPatch = serde_json::from_value(json!([
+            {"op": "add", "path": "/containers/*/tag", "value": "latest"},
+            {"op": "move", "from": "/containers/1", "path": "/sidecar"},
+            {"op": "replace", "path": "/containers/*/missing", "value": "c"},
+        ]))
+        .unwrap();
+        let err = patch_ext_document(&mut data, &p).unwrap_err();
+        assert_eq!(err.operation_index(), Some(2));
+        assert_eq!(err.operation(), Some(OperationKind::Replace));
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    #[case::any(json!({"op": "test", "path": "/containers/*/name", "value": "sidecar", "mode": "any"}), true)]
+    #[case::any_fails(json!({"op": "test", "path": "/containers/*/name", "value": "foo", "mode": "any"}), false)]
+    #[case::all(json!({"op": "test", "path": "/containers/*/name", "value": "sidecar"}), false)]
+    #[case::all_filter(json!({"op": "test", "path": "/containers/[?name=app]/image", "value": "a"}), true)]
+    fn test_ext_patch_test_mode(mut data: Value, #[case] op: Value, #[case] ok: bool) {
+        let p: ExtPatch = serde_json::from_value(json!([op, {"op": "remove", "path": "/containers/0"}])).unwrap();
+        let res = patch_ext_document(&mut data, &p);
+        assert_eq!(res.is_ok(), ok);
+        assert_eq!(data["containers"].as_array().unwrap().len(), if ok { 1 } else { 2 });
+    }
+
+    #[rstest]
+    fn test_ext_test_operation_round_trip() {
+        let op = json!({"op": "test", "path": "/foo", "value": 1});
+        let p: ExtPatchOperation = serde_json::from_value(op.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&p).unwrap(), op);
+
+        let op = json!({"op": "test", "path": "/foo/*", "value": 1, "mode": "any"});
+        let p: ExtPatchOperation = serde_json::from_value(op.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&p).unwrap(), op);
+    }
+
+    #[rstest]
+    #[case::add_missing(add_if_absent_operation(format_ptr!("/containers/*/tag"), json!("x")), true)]
+    #[case::add_present(add_if_absent_operation(format_ptr!("/containers/*/image"), json!("x")), false)]
+    #[case::add_array_end(add_if_absent_operation(format_ptr!("/containers/-"), json!({})), true)]
+    #[case::add_array_present(add_if_absent_operation(format_ptr!("/containers/1"), json!({})), false)]
+    #[case::add_array_negative(add_if_absent_operation(format_ptr!("/containers/-1"), json!({})), false)]
+    #[case::replace_eq(replace_if_equals_operation(format_ptr!("/containers/*/name"), json!("app"), json!(1)), true)]
+    #[case::replace_ne(replace_if_equals_operation(format_ptr!("/containers/*/name"), json!("c"), json!(1)), false)]
+    #[case::remove_present(remove_if_exists_operation(format_ptr!("/containers/[?name=app]")), true)]
+    #[case::remove_missing(remove_if_exists_operation(format_ptr!("/containers/*/tag")), false)]
+    fn test_conditional_operations(mut data: Value, #[case] op: ExtPatchOperation, #[case] changed: bool) {
+        let orig = data.clone();
+        let p = ExtPatch(vec![op, add_operation(format_ptr!("/done"), json!(true)).into()]);
+        assert_ok!(patch_ext_document(&mut data, &p));
+        assert_eq!(data["done"], json!(true));
+
+        data.as_object_mut().unwrap().remove("done");
+        assert_eq!(data != orig, changed);
+    }
+
+    #[rstest]
+    #[case::negative_out_of_bounds(format_ptr!("/containers/-10"), PatchErrorKind::NegativeIndexOutOfBounds(10))]
+    #[case::wildcard_object(format_ptr!("/containers/0/*"), PatchErrorKind::InvalidWildcard)]
+    #[case::wildcard_array(format_ptr!("/containers/*"), PatchErrorKind::InvalidWildcard)]
+    fn test_add_if_absent_err(mut data: Value, #[case] path: PointerBuf, #[case] kind: PatchErrorKind) {
+        let orig = data.clone();
+        let p = ExtPatch(vec![add_if_absent_operation(path.clone(), json!("x"))]);
+        let err = assert_err!(patch_ext_document(&mut data, &p));
+        assert_eq!(err.kind().code(), kind.code());
+        assert_eq!(err.path(), path);
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_replace_if_equals_partial(mut data: Value) {
+        let p = ExtPatch(vec![replace_if_equals_operation(format_ptr!("/containers/*/image"), json!("b"), json!("c"))]);
+        assert_ok!(patch_ext_document(&mut data, &p));
+        assert_eq!(data, json!({"containers": [{"name": "app", "image": "a"}, {"name": "sidecar", "image": "c"}]}));
+    }
+
+    #[rstest]
+    #[case::fields_and_elements(
+        vec![format_ptr!("/containers/*/image"), format_ptr!("/containers/[?name=app]")],
+        json!({"containers": [{"name": "sidecar"}]}),
+    )]
+    #[case::indices(
+        vec![format_ptr!("/containers/1"), format_ptr!("/containers/0")],
+        json!({"containers": []}),
+    )]
+    #[case::overlapping(
+        vec![format_ptr!("/**/name"), format_ptr!("/containers/1")],
+        json!({"containers": [{"image": "a"}]}),
+    )]
+    #[case::missing(
+        vec![format_ptr!("/containers/5"), format_ptr!("/foo/*")],
+        json!({"containers": [{"name": "app", "image": "a"}, {"name": "sidecar", "image": "b"}]}),
+    )]
+    fn test_remove_all(mut data: Value, #[case] paths: Vec<PointerBuf>, #[case] expected: Value) {
+        assert_ok!(patch_ext_document(&mut data, &ExtPatch(vec![remove_all_operation(paths)])));
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    fn test_remove_all_modified(mut data: Value) {
+        let op = remove_all_operation(vec![format_ptr!("/containers/*/name"), format_ptr!("/containers/0")]);
+        let modified = assert_ok!(op.apply(&mut data, &PatchOptions::default()));
+        assert_eq!(modified, vec![format_ptr!("/containers/1/name"), format_ptr!("/containers/0")]);
+
+        let op: ExtPatchOperation =
+            serde_json::from_value(json!({"op": "remove_all", "paths": ["/foo", "/bar/*"]})).unwrap();
+        assert_eq!(op, remove_all_operation(vec![format_ptr!("/foo"), format_ptr!("/bar/*")]));
+        assert_eq!(op.kind().to_string(), "remove_all");
+    }
+
+    #[rstest]
+    fn test_conditional_operation_round_trip() {
+        let p: ExtPatch = serde_json::from_value(json!([
+            {"op": "add_if_absent", "path": "/foo", "value": 1},
+            {"op": "replace_if_equals", "path": "/foo", "expected": 1, "value": 2},
+            {"op": "remove_if_exists", "path": "/bar"},
+        ]))
+        .unwrap();
+        assert_eq!(
+            p,
+            ExtPatch(vec![
+                add_if_absent_operation(format_ptr!("/foo"), json!(1)),
+                replace_if_equals_operation(format_ptr!("/foo"), json!(1), json!(2)),
+                remove_if_exists_operation(format_ptr!("/bar")),
+            ])
+        );
+        assert_eq!(p.0[0].kind().to_string(), "add_if_absent");
+    }
+
+    #[rstest]
+    #[case::copy(
+        add_from_operation(format_ptr!("/main"), format_ptr!("/containers/0/name")),
+        json!({"containers": [{"name": "app", "image": "a"}, {"name": "sidecar", "image": "b"}], "main": "app"}),
+    )]
+    #[case::wildcard(
+        add_from_operation(format_ptr!("/containers/*/tag"), format_ptr!("/containers/*/name")),
+        json!({"containers": [
+            {"name": "app", "image": "a", "tag": "app"},
+            {"name": "sidecar", "image": "b", "tag": "sidecar"},
+        ]}),
+    )]
+    #[case::template(
+        ExtPatchOperation::ReplaceFrom(AddFromOperation {
+            from: format_ptr!("/containers/*/name"),
+            path: format_ptr!("/containers/*/image"),
+            value: Some(json!({"repo": "registry/{{from}}", "names": ["{{from}}", "{{/containers/0/name}}"]})),
+        }),
+        json!({"containers": [
+            {"name": "app", "image": {"repo": "registry/app", "names": ["app", "app"]}},
+            {"name": "sidecar", "image": {"repo": "registry/sidecar", "names": ["sidecar", "app"]}},
+        ]}),
+    )]
+    #[case::no_matches(
+        add_from_operation(format_ptr!("/containers/*/tag"), format_ptr!("/containers/*/missing")),
+        json!({"containers": [{"name": "app", "image": "a"}, {"name": "sidecar", "image": "b"}]}),
+    )]
+    fn test_add_from(mut data: Value, #[case] op: ExtPatchOperation, #[case] expected: Value) {
+        assert_ok!(patch_ext_document(&mut data, &ExtPatch(vec![op])));
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    #[case::missing_from(add_from_operation(format_ptr!("/main"), format_ptr!("/missing")), format_ptr!("/missing"))]
+    #[case::missing_target(
+        replace_from_operation(format_ptr!("/containers/*/tag"), format_ptr!("/containers/*/name")),
+        format_ptr!("/containers/0/tag"),
+    )]
+    #[case::wildcards(add_from_operation(format_ptr!("/main"), format_ptr!("/containers/*")), format_ptr!("/main"))]
+    fn test_add_from_err(mut data: Value, #[case] op: ExtPatchOperation, #[case] path: PointerBuf) {
+        let orig = data.clone();
+        let err = assert_err!(patch_ext_document(&mut data, &ExtPatch(vec![op])));
+        assert_eq!(err.path(), path);
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_add_from_round_trip() {
+        let op = json!({"op": "add_from", "from": "/foo/*", "path": "/bar/*"});
+        let p: ExtPatchOperation = serde_json::from_value(op.clone()).unwrap();
+        assert_eq!(p, add_from_operation(format_ptr!("/bar/*"), format_ptr!("/foo/*")));
+        assert_eq!(serde_json::to_value(&p).unwrap(), op);
+
+        let op = json!({"op": "replace_from", "from": "/foo", "path": "/bar", "value": {"a": "{{from}}"}});
+        let p: ExtPatchOperation = serde_json::from_value(op.clone()).unwrap();
+        assert_eq!(p.kind(), OperationKind::ReplaceFrom);
+        assert_eq!(serde_json::to_value(&p).unwrap(), op);
+    }
+
+    #[rstest]
+    #[case::increment(increment_operation(format_ptr!("/a"), 2.into()), json!({"a": 3, "b": [1.5, -1], "c": {"d": 1}}))]
+    #[case::decrement(
+        increment_operation(format_ptr!("/b/*"), (-2).into()),
+        json!({"a": 1, "b": [-0.5, -3], "c": {"d": 1}}),
+    )]
+    #[case::float(
+        increment_operation(format_ptr!("/a"), Number::from_f64(0.5).unwrap()),
+        json!({"a": 1.5, "b": [1.5, -1], "c": {"d": 1}}),
+    )]
+    #[case::append(
+        append_operation(format_ptr!("/b"), vec![json!(2), json!({})]),
+        json!({"a": 1, "b": [1.5, -1, 2, {}], "c": {"d": 1}}),
+    )]
+    #[case::merge(
+        merge_operation(format_ptr!("/c"), json!({"d": null, "e": {"f": 2}})),
+        json!({"a": 1, "b": [1.5, -1], "c": {"e": {"f": 2}}}),
+    )]
+    #[case::recursive(
+        increment_operation(format_ptr!("/**/d"), 1.into()),
+        json!({"a": 1, "b": [1.5, -1], "c": {"d": 2}}),
+    )]
+    fn test_mutation_operations(#[case] op: ExtPatchOperation, #[case] expected: Value) {
+        let mut data = json!({"a": 1, "b": [1.5, -1], "c": {"d": 1}});
+        assert_ok!(patch_ext_document(&mut data, &ExtPatch(vec![op])));
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    #[case::not_a_number(increment_operation(format_ptr!("/c"), 1.into()), PatchErrorKind::UnexpectedType)]
+    #[case::overflow(increment_operation(format_ptr!("/a"), u64::MAX.into()), PatchErrorKind::NumericOverflow)]
+    #[case::not_an_array(append_operation(format_ptr!("/a"), vec![]), PatchErrorKind::UnexpectedType)]
+    #[case::missing(merge_operation(format_ptr!("/missing"), json!({})), PatchErrorKind::TargetDoesNotExist)]
+    fn test_mutation_operations_err(#[case] op: ExtPatchOperation, #[case] kind: PatchErrorKind) {
+        let mut data = json!({"a": 1, "c": {"d": 1}});
+        let err = assert_err!(patch_ext_document(&mut data, &ExtPatch(vec![op])));
+        assert_eq!(err.kind().to_string(), kind.to_string());
+    }
+
+    #[rstest]
+    #[case::substring("a", "x", StrMatch::Substring, ["docker.io/xpp:1", "docker.io/sidecxr:xbc"])]
+    #[case::prefix("docker.io/", "ghcr.io/", StrMatch::Prefix, ["ghcr.io/app:1", "ghcr.io/sidecar:abc"])]
+    #[case::suffix(":1", ":2", StrMatch::Suffix, ["docker.io/app:2", "docker.io/sidecar:abc"])]
+    #[cfg_attr(feature = "regex", case::regex(
+        r"^docker\.io/(\w+):.*$",
+        "ghcr.io/$1:latest",
+        StrMatch::Regex,
+        ["ghcr.io/app:latest", "ghcr.io/sidecar:latest"],
+    ))]
+    fn test_str_replace(
+        #[case] pattern: &str,
+        #[case] replacement: &str,
+        #[case] mode: StrMatch,
+        #[case] images: [&str; 2],
+    ) {
+        let mut data = json!({"containers": [{"image": "docker.io/app:1"}, {"image": "docker.io/sidecar:abc"}]});
+        let op = str_replace_operation(format_ptr!("/containers/*/image"), pattern, replacement, mode);
+        assert_ok!(patch_ext_document(&mut data, &ExtPatch(vec![op])));
+        assert_eq!(data, json!({"containers": [{"image": images[0]}, {"image": images[1]}]}));
+    }
+
+    #[rstest]
+    #[case::not_a_string(
+        str_replace_operation(format_ptr!("/containers"), "a", "b", StrMatch::Substring),
+        PatchErrorKind::UnexpectedType,
+    )]
+    #[cfg_attr(not(feature = "regex"), case::regex_disabled(
+        str_replace_operation(format_ptr!("/containers/*/name"), "a", "b", StrMatch::Regex),
+        PatchErrorKind::FeatureDisabled("regex"),
+    ))]
+    fn test_str_replace_err(mut data: Value, #[case] op: ExtPatchOperation, #[case] kind: PatchErrorKind) {
+        let err = assert_err!(patch_ext_document(&mut data, &ExtPatch(vec![op])));
+        assert_eq!(err.kind().to_string(), kind.to_string());
+    }
+
+    #[cfg(feature = "regex")]
+    #[rstest]
+    fn test_str_replace_invalid_regex(mut data: Value) {
+        let op = str_replace_operation(format_ptr!("/containers/*/name"), "(", "b", StrMatch::Regex);
+        let err = assert_err!(patch_ext_document(&mut data, &ExtPatch(vec![op])));
+        assert_matches!(err.kind(), PatchErrorKind::RegexError(_));
+        assert_eq!(err.path(), format_ptr!("/containers/*/name"));
+    }
+
+    #[rstest]
+    fn test_str_replace_round_trip() {
+        let op = json!({"op": "str_replace", "path": "/foo", "pattern": "a", "replacement": "b"});
+        let p: ExtPatchOperation = serde_json::from_value(op.clone()).unwrap();
+        assert_eq!(p, str_replace_operation(format_ptr!("/foo"), "a", "b", StrMatch::Substring));
+        assert_eq!(serde_json::to_value(&p).unwrap(), op);
+
+        let op = json!({"op": "str_replace", "path": "/foo", "pattern": "a", "replacement": "b", "mode": "prefix"});
+        let p: ExtPatchOperation = serde_json::from_value(op.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&p).unwrap(), op);
+    }
+
+    #[rstest]
+    fn test_mutation_operation_round_trip() {
+        let p: ExtPatch = serde_json::from_value(json!([
+            {"op": "increment", "path": "/foo", "delta": 1},
+            {"op": "append", "path": "/bar", "values": [1, 2]},
+            {"op": "merge", "path": "/baz", "value": {"a": 1}},
+        ]))
+        .unwrap();
+        assert_eq!(
+            p,
+            ExtPatch(vec![
+                increment_operation(format_ptr!("/foo"), 1.into()),
+                append_operation(format_ptr!("/bar"), vec![json!(1), json!(2)]),
+                merge_operation(format_ptr!("/baz"), json!({"a": 1})),
+            ])
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use alloc::vec;
+
+use serde_json::Value;
+
+use crate::{
+    Patch,
+    PatchError,
+    PatchOptions,
+    Snapshot,
+    apply_operation,
+};
+
+// Compute a patch that undoes the given patch: applying the result to the patched document gives
+// back `obj`.  Each operation is applied (to a copy of `obj`) in turn, and inverted from the values
+// at its concrete targets (see Snapshot), so wildcards are expanded against the document as it was
+// before that operation, and the inverse patch only references concrete paths.  An add is undone
+// with a remove (and vice versa), and a replace with a replace; only when the targets of a wildcard
+// path overlap is the subtree containing them diffed against its previous contents instead.
+pub fn invert_patch(obj: &Value, p: &Patch) -> Result<Patch, PatchError> {
+    let mut doc = obj.clone();
+    let mut inverse = vec![];
+    for (i, op) in p.iter().enumerate() {
+        let snapshot = Snapshot::new(&doc, op);
+        apply_operation(&mut doc, op, &PatchOptions::default()).map_err(|err| err.with_operation_index(i))?;
+
+        let mut ops = vec![];
+        snapshot.invert(&doc, &mut ops);
+        inverse.push(ops);
+    }
+    Ok(Patch(inverse.into_iter().rev().flatten().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        PatchOperation,
+        add_operation,
+        copy_operation,
+        format_ptr,
+        move_operation,
+        patch_ext_all,
+        remove_operation,
+        replace_operation,
+        test_operation,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({
+            "foo": [
+                {"baz": {"buzz": 0}},
+                {"baz": {"quzz": 1}},
+                {"baz": {"fixx": 2}},
+            ],
+            "bar": {"image": "a"},
+        })
+    }
+
+    #[rstest]
+    #[case::add(vec![add_operation(format_ptr!("/foo/*/baz/buzz"), json!(42))])]
+    #[case::add_vec(vec![
+        add_operation(format_ptr!("/foo/0"), json!(42)),
+        add_operation(format_ptr!("/foo/-"), json!(1)),
+    ])]
+    #[case::add_new_parent(vec![add_operation(format_ptr!("/qux/quzz"), json!(1))])]
+    #[case::replace(vec![replace_operation(format_ptr!("/**/image"), json!("b"))])]
+    #[case::remove(vec![remove_operation(format_ptr!("/foo/*")), remove_operation(format_ptr!("/bar/image"))])]
+    #[case::move_copy(vec![
+        move_operation(format_ptr!("/foo/*/baz"), format_ptr!("/foo/*/qux")),
+        copy_operation(format_ptr!("/bar"), format_ptr!("/foo/0")),
+    ])]
+    #[case::root(vec![replace_operation(format_ptr!(""), json!([1, 2, 3])), remove_operation(format_ptr!("/1"))])]
+    #[case::test(vec![test_operation(format_ptr!("/bar/image"), json!("a"))])]
+    fn test_invert_patch(data: Value, #[case] ops: Vec<PatchOperation>) {
+        let p = Patch(ops);
+        let inverse = assert_ok!(invert_patch(&data, &p));
+
+        let mut doc = data.clone();
+        assert_ok!(patch_ext_all(&mut doc, &p));
+        assert_ok!(patch_ext_all(&mut doc, &inverse));
+        assert_eq!(doc, data);
+    }
+
+    #[rstest]
+    fn test_invert_patch_concrete(data: Value) {
+        let p = Patch(vec![replace_operation(format_ptr!("/foo/*/baz"), json!(1))]);
+        let inverse = assert_ok!(invert_patch(&data, &p));
+        assert_eq!(
+            inverse,
+            Patch(vec![
+                replace_operation(format_ptr!("/foo/0/baz"), json!({"buzz": 0})),
+                replace_operation(format_ptr!("/foo/1/baz"), json!({"quzz": 1})),
+                replace_operation(format_ptr!("/foo/2/baz"), json!({"fixx": 2})),
+            ])
+        );
+    }
+
+    #[rstest]
+    #[case::front_insert(
+        add_operation(format_ptr!("/foo/0"), json!(42)),
+        vec![remove_operation(format_ptr!("/foo/0"))],
+    )]
+    #[case::append(add_operation(format_ptr!("/foo/-"), json!(42)), vec![remove_operation(format_ptr!("/foo/3"))])]
+    #[case::new_parent(
+        add_operation(format_ptr!("/qux/quzz"), json!(1)),
+        vec![remove_operation(format_ptr!("/qux"))],
+    )]
+    #[case::remove(
+        remove_operation(format_ptr!("/foo/1")),
+        vec![add_operation(format_ptr!("/foo/1"), json!({"baz": {"quzz": 1}}))],
+    )]
+    #[case::replace(
+        replace_operation(format_ptr!("/bar"), json!(1)),
+        vec![replace_operation(format_ptr!("/bar"), json!({"image": "a"}))],
+    )]
+    #[case::move_op(
+        move_operation(format_ptr!("/bar/image"), format_ptr!("/image")),
+        vec![remove_operation(format_ptr!("/image")), add_operation(format_ptr!("/bar/image"), json!("a"))],
+    )]
+    fn test_invert_patch_exact(data: Value, #[case] op: PatchOperation, #[case] expected: Vec<PatchOperation>) {
+        assert_eq!(assert_ok!(invert_patch(&data, &Patch(vec![op]))), Patch(expected));
+    }
+
+    #[rstest]
+    fn test_invert_patch_err(data: Value) {
+        let p = Patch(vec![add_operation(format_ptr!("/qux"), json!(1)), remove_operation(format_ptr!("/foo/7"))]);
+        let err = assert_err!(invert_patch(&data, &p));
+        assert_eq!(err.operation_index(), Some(1));
+    }
+}
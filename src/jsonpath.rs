@@ -0,0 +1,99 @@
+//! Select patch targets with [JSONPath](https://datatracker.ietf.org/doc/html/rfc9535) expressions
+//! (requires the `jsonpath` feature).  The wildcard operators supported in pointers cover many
+//! cases, but JSONPath filters can express arbitrary predicates, e.g., `$.foo[?@.baz > 1].buzz`.
+//! Expressions are evaluated against the document when the patch is applied, and compiled down to
+//! concrete pointers.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use crate::{
+    Patch,
+    PatchError,
+    PatchErrorKind,
+    PatchOperation,
+    Pointer,
+    PointerBuf,
+    key_token,
+    patch_ext_all,
+};
+
+// Evaluate the JSONPath expression against the document, returning the concrete pointers to every
+// selected value, in document order
+pub fn select(expr: &str, obj: &Value) -> Result<Vec<PointerBuf>, PatchError> {
+    let path =
+        JsonPath::parse(expr).map_err(|err| PatchError::new(PatchErrorKind::JsonPathError(err), Pointer::root()))?;
+    let mut res = vec![];
+    for loc in path.query_located(obj).locations() {
+        // The location is a plain JSON pointer, so any keys that look like wildcards need escaping
+        let ptr = PointerBuf::parse(loc.to_json_pointer())?;
+        res.push(PointerBuf::from_tokens(ptr.tokens().map(|t| key_token(&t.decoded()).into_owned())));
+    }
+    Ok(res)
+}
+
+// Apply the operation built by `op` to every location that the JSONPath expression selects; e.g.,
+// `patch_jsonpath(obj, "$.containers[?@.name == 'app'].image", |p| replace_operation(p, image))`.
+// The operations are applied in reverse document order (so that removing an array element doesn't
+// change the location of the elements that are selected after it), and atomically, as with
+// patch_ext_all.
+pub fn patch_jsonpath<F>(obj: &mut Value, expr: &str, op: F) -> Result<(), PatchError>
+where
+    F: Fn(PointerBuf) -> PatchOperation,
+{
+    let ops = select(expr, obj)?.into_iter().rev().map(op).collect();
+    patch_ext_all(obj, &Patch(ops))
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        format_ptr,
+        remove_operation,
+        replace_operation,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({
+            "foo": [
+                {"baz": 1, "buzz": "a"},
+                {"baz": 2, "buzz": "b"},
+                {"baz": 3, "buzz": "c"},
+            ],
+        })
+    }
+
+    #[rstest]
+    fn test_select(data: Value) {
+        let paths = assert_ok!(select("$.foo[?@.baz > 1].buzz", &data));
+        assert_eq!(paths, vec![format_ptr!("/foo/1/buzz"), format_ptr!("/foo/2/buzz")]);
+    }
+
+    #[rstest]
+    fn test_select_err(data: Value) {
+        let err = assert_err!(select("$.foo[", &data));
+        assert_matches!(err.kind(), PatchErrorKind::JsonPathError(_));
+    }
+
+    #[rstest]
+    fn test_patch_jsonpath_replace(mut data: Value) {
+        assert_ok!(patch_jsonpath(&mut data, "$.foo[?@.baz > 1].buzz", |p| replace_operation(p, json!("z"))));
+        assert_eq!(data["foo"], json!([{"baz": 1, "buzz": "a"}, {"baz": 2, "buzz": "z"}, {"baz": 3, "buzz": "z"}]));
+    }
+
+    #[rstest]
+    fn test_patch_jsonpath_remove(mut data: Value) {
+        assert_ok!(patch_jsonpath(&mut data, "$.foo[?@.baz != 2]", remove_operation));
+        assert_eq!(data["foo"], json!([{"baz": 2, "buzz": "b"}]));
+    }
+}
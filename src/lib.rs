@@ -1,7 +1,8 @@
 //! This module provides some unofficial "extensions" to the [jsonpatch](https://jsonpatch.com)
 //! format for describing changes to a JSON document.  In particular, it adds the `*` operator as a
-//! valid token for arrays in a JSON document.  It means: apply this change to all elements of this
-//! array.  For example, consider the following document:
+//! valid token for arrays and objects in a JSON document.  It means: apply this change to all
+//! elements of this array (or all values of this object).  For example, consider the following
+//! document:
 //!
 //! ```json
 //! {
@@ -16,8 +17,52 @@
 //! ```
 //!
 //! The pathspec `/foo/bar/*/baz` would reference the `baz` field of all three array entries in the
-//! `bar` array.  It is an error to use `*` to reference a field that is not an array.  It is an
-//! error to use `*` at the end of a path, e.g., `/foo/*`.
+//! `bar` array.  If `bar` were instead an object, e.g., `{"a": {"baz": 1}, "b": {"baz": 2}}`, the
+//! same pathspec would reference the `baz` field of every value in the object.  It is an error to
+//! use `*` to reference a field that is not an array or an object.  A `*` at the end of a path,
+//! e.g., `/foo/*`, references every element of the array (or every value of the object): a
+//! ReplaceOperation sets each of them to the value, a RemoveOperation removes all of them (leaving
+//! an empty array or object), and a TestOperation tests each of them.  Since there's no sensible
+//! place to insert a new value, it is an error to use `*` at the end of a path in an AddOperation.
+//!
+//! A RemoveOperation can also reference individual array elements by index, e.g., `/foo/bar/1`;
+//! the remaining elements are shifted down, as in RFC 6902.  Since `-` references the (nonexistent)
+//! element after the end of the array, it is an error to use it at the end of a path in a
+//! RemoveOperation.
+//!
+//! This crate also supports the `**` operator, which means "zero or more path segments".  The
+//! pathspec `/foo/**/baz` would reference every `baz` field at any depth underneath `foo`,
+//! including `/foo/baz` itself, in both arrays and objects.  Unlike `*`, the `**` operator only
+//! matches values that already exist in the document; it will never auto-create anything, so an
+//! AddOperation using `**` will only ever overwrite existing fields (or insert into existing
+//! arrays).
+//!
+//! Lastly, the `[?key=value]` operator is a filtered wildcard: it behaves like `*`, but only
+//! references the array elements (or object values) whose `key` field is equal to `value`.  For
+//! example, `/containers/[?name=sidecar]/image` references the `image` field of only the containers
+//! named `sidecar`.  Non-string fields are compared against their JSON representation, so
+//! `[?replicas=3]` and `[?enabled=true]` work as expected.  Using a filtered wildcard at the end of
+//! a path in a RemoveOperation (or a ReplaceOperation) removes (or replaces) all of the matching
+//! elements.
+//!
+//! Array slices like `/foo/1:3/baz` behave like `*`, but only reference the array elements whose
+//! index is in the given (half-open) range; either end of the range can be omitted, so `/foo/:2`
+//! references the first two elements of `foo`, and `/foo/2:` references every element after them.
+//! Slices never match the values of an object.
+//!
+//! All of these operators can also be used in a TestOperation; by default, every value that the
+//! path matches must be equal to the expected value, but an `ExtTestOperation` can instead require
+//! that at least one of them is (see `TestMode`).
+//!
+//! MoveOperations and CopyOperations can use wildcards too, as long as `from` and `path` contain
+//! the same number of them: each wildcard in `path` is filled in with whatever the corresponding
+//! wildcard in `from` matched, so copying `/foo/*/baz` to `/backup/*/baz` copies `/foo/0/baz` to
+//! `/backup/0/baz`, `/foo/1/baz` to `/backup/1/baz`, and so on.
+//!
+//! The last segment of the path in an AddOperation, ReplaceOperation, or RemoveOperation can also
+//! be a negative array index, which counts from the end of the array: `/foo/-1` references the last
+//! element of `foo`, `/foo/-2` the second-to-last, and so on.  Adding at a negative index inserts
+//! the new value before the referenced element.
 //!
 //! Additionally, this crate will auto-create parent paths for the AddOperation only, e.g., the
 //! result of applying `AddOperation{ path: "/foo/bar", value: 1 }` to the empty document will be
@@ -25,9 +70,81 @@
 //! ```json
 //! { "foo": {"bar": 1}}
 //! ```
+//!
+//! A `-` in the middle of the path of an AddOperation appends a new element to the array, so adding
+//! `/foo/-/bar` appends `{"bar": 1}` to `foo` (creating `foo` as an array if it doesn't exist yet).
+//!
+//! To reference a key that looks like one of the operators above (e.g., a field that's actually
+//! named `*`), prefix the token with a backslash: `/foo/\*` references the `*` field of `foo`,
+//! and `/foo/\1:3` references the `1:3` field.  The backslash is stripped from any token that
+//! starts with one, so `/foo/\\bar` references the `\bar` field.  The `escape` function
+//! adds the backslash where it's needed.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// The test dependencies (rstest and assertables) expand to `std` paths and to `format!`, which
+// aren't in scope without std, so the tests link std even when the library itself doesn't
+#[cfg_attr(test, macro_use)]
+extern crate alloc;
+#[cfg(test)]
+extern crate std;
 
+mod annotate;
+pub mod api;
+mod builder;
+mod changelog;
+mod conflict;
+pub mod diff;
 mod errors;
+mod expand;
+mod ext;
+mod invert;
+#[cfg(feature = "jsonpath")]
+pub mod jsonpath;
 mod macros;
+pub mod merge3;
+pub mod merge_key;
+pub mod merge_patch;
+mod observer;
+mod optimize;
+mod options;
+mod order;
+mod parse;
+mod pointer;
+mod preview;
+#[cfg(test)]
+mod proptests;
+mod rebase;
+mod report;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "std")]
+mod stream;
+pub mod template;
+mod trace;
+mod transaction;
+mod typed;
+mod undo;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+
+use alloc::boxed::Box;
+use alloc::string::{
+    String,
+    ToString,
+};
+use alloc::vec::Vec;
+use alloc::{
+    format,
+    vec,
+};
+use core::cmp::Ordering;
+use core::time::Duration;
+use core::{
+    mem,
+    slice,
+};
+#[cfg(feature = "std")]
+use std::time::Instant;
 
 use json_patch::patch;
 // mark these as re-exports in the generated docs (maybe related to
@@ -51,38 +168,230 @@ pub use jsonptr::{
     PointerBuf,
     Token,
 };
+use serde::{
+    Deserialize,
+    Serialize,
+};
 use serde_json::{
+    Map,
     Value,
     json,
 };
 
-pub use crate::errors::PatchError;
+pub use crate::annotate::{
+    AnnotatedOperation,
+    Annotation,
+    patch_ext_annotated,
+    patch_ext_annotated_report,
+};
+pub use crate::builder::PatchBuilder;
+pub use crate::changelog::{
+    ChangeEvent,
+    ChangeKind,
+    patch_ext_document_logged,
+    patch_ext_logged,
+};
+pub use crate::conflict::{
+    Conflict,
+    conflicts,
+};
+pub use crate::errors::{
+    OperationKind,
+    PatchError,
+    PatchErrorKind,
+};
+pub use crate::expand::expand_operation;
+pub use crate::ext::{
+    AddFromOperation,
+    AppendOperation,
+    ExtPatch,
+    ExtPatchOperation,
+    ExtTestOperation,
+    IncrementOperation,
+    MergeOperation,
+    RemoveAllOperation,
+    ReplaceIfEqualsOperation,
+    StrMatch,
+    StrReplaceOperation,
+    add_from_operation,
+    add_if_absent_operation,
+    append_operation,
+    increment_operation,
+    merge_operation,
+    patch_ext_document,
+    remove_all_operation,
+    remove_if_exists_operation,
+    replace_from_operation,
+    replace_if_equals_operation,
+    str_replace_operation,
+};
+pub use crate::invert::invert_patch;
+#[doc(hidden)]
+pub use crate::macros::{
+    __format,
+    is_valid_ptr_template,
+    push_ptr_segment,
+};
+pub use crate::observer::{
+    PatchObserver,
+    patch_ext_observed,
+};
+pub use crate::optimize::optimize_patch;
+pub use crate::options::{
+    ContainerKind,
+    ErrorPolicy,
+    PatchOptions,
+};
+pub use crate::order::order_patch;
+pub use crate::parse::from_str_strict;
+pub use crate::pointer::{
+    child,
+    parent,
+    replace_wildcards,
+    sibling,
+    with_index,
+};
+pub use crate::preview::{
+    preview,
+    preview_diff,
+    unified_diff,
+};
+pub use crate::rebase::rebase;
+pub use crate::report::{
+    OperationOutcome,
+    OperationReport,
+    PatchReport,
+};
+#[cfg(feature = "std")]
+pub use crate::stream::patch_stream;
+use crate::trace::{
+    trace_event,
+    trace_span,
+};
+pub use crate::transaction::{
+    PatchTransaction,
+    Savepoint,
+};
+pub use crate::typed::patch_ext_typed;
+pub(crate) use crate::undo::{
+    Snapshot,
+    rollback,
+};
 
 pub mod prelude {
+    #[cfg(feature = "std")]
+    pub use super::patch_stream;
     pub use super::{
+        AddFromOperation,
         AddOperation,
+        AnnotatedOperation,
+        Annotation,
+        AppendOperation,
+        ChangeEvent,
+        ChangeKind,
+        Conflict,
+        ContainerKind,
         CopyOperation,
+        ErrorPolicy,
+        ExtPatch,
+        ExtPatchOperation,
+        ExtTestOperation,
+        IncrementOperation,
+        MergeOperation,
         MoveOperation,
+        OperationKind,
+        OperationOutcome,
+        OperationReport,
         Patch,
+        PatchBuilder,
         PatchError,
+        PatchErrorKind,
+        PatchObserver,
         PatchOperation,
+        PatchOptions,
+        PatchReport,
+        PatchTransaction,
         Pointer,
         PointerBuf,
+        RemoveAllOperation,
         RemoveOperation,
+        ReplaceIfEqualsOperation,
         ReplaceOperation,
         ResolveError,
+        Savepoint,
+        StrMatch,
+        StrReplaceOperation,
+        TestMode,
         TestOperation,
         Token,
+        add_from_operation,
+        add_if_absent_operation,
         add_operation,
+        add_serialized_operation,
+        append_operation,
+        child,
+        conflicts,
         copy_operation,
         escape,
+        expand_operation,
         format_ptr,
+        from_str_strict,
+        get_all,
+        get_first,
+        increment_operation,
+        invert_patch,
         matches,
+        merge_operation,
         move_operation,
+        optimize_patch,
+        order_patch,
+        parent,
         patch_ext,
+        patch_ext_all,
+        patch_ext_all_with,
+        patch_ext_annotated,
+        patch_ext_annotated_report,
+        patch_ext_changed,
+        patch_ext_document,
+        patch_ext_document_logged,
+        patch_ext_lenient,
+        patch_ext_logged,
+        patch_ext_observed,
+        patch_ext_report,
+        patch_ext_test,
+        patch_ext_traced,
+        patch_ext_typed,
+        patch_ext_with,
+        patch_many,
+        preview,
+        preview_diff,
+        ptr,
+        rebase,
+        remove_all_operation,
+        remove_ext,
+        remove_if_exists_operation,
         remove_operation,
+        replace_from_operation,
+        replace_if_equals_operation,
         replace_operation,
+        replace_serialized_operation,
+        replace_wildcards,
+        resolve_matches,
+        resolve_matches_mut,
+        sibling,
+        str_replace_operation,
         test_operation,
+        test_serialized_operation,
+        try_add_operation,
+        try_copy_operation,
+        try_format_ptr,
+        try_move_operation,
+        try_remove_operation,
+        try_replace_operation,
+        try_test_operation,
+        unified_diff,
+        validate_patch,
+        with_index,
     };
 }
 
@@ -91,6 +400,9 @@ pub mod prelude {
 enum PatchMode {
     Error,
     Create,
+    // Like Create, but the element at the end of the path is created as an array instead of an
+    // object
+    CreateArray,
     Skip,
 }
 
@@ -118,202 +430,1587 @@ pub fn test_operation(path: PointerBuf, value: Value) -> PatchOperation {
     PatchOperation::Test(TestOperation { path, value })
 }
 
+// Like the constructors above, but accept any serializable value (e.g., a struct from your own
+// code) instead of a serde_json::Value, and return a SerializeError if it can't be converted
+pub fn add_serialized_operation(path: PointerBuf, value: impl Serialize) -> Result<PatchOperation, PatchError> {
+    let value = serialize(value, &path, OperationKind::Add)?;
+    Ok(add_operation(path, value))
+}
+
+pub fn replace_serialized_operation(path: PointerBuf, value: impl Serialize) -> Result<PatchOperation, PatchError> {
+    let value = serialize(value, &path, OperationKind::Replace)?;
+    Ok(replace_operation(path, value))
+}
+
+pub fn test_serialized_operation(path: PointerBuf, value: impl Serialize) -> Result<PatchOperation, PatchError> {
+    let value = serialize(value, &path, OperationKind::Test)?;
+    Ok(test_operation(path, value))
+}
+
+fn serialize(value: impl Serialize, path: &Pointer, kind: OperationKind) -> Result<Value, PatchError> {
+    serde_json::to_value(value)
+        .map_err(|err| PatchError::new(PatchErrorKind::SerializeError(err), path).with_operation(kind))
+}
+
+// Fallible versions of the constructors above, which parse the path(s) from strings instead of
+// panicking (like format_ptr! does) if they're invalid; the values can be anything serializable,
+// as in add_serialized_operation.
+pub fn try_add_operation(path: &str, value: impl Serialize) -> Result<PatchOperation, PatchError> {
+    add_serialized_operation(parse_ptr(path, OperationKind::Add)?, value)
+}
+
+pub fn try_copy_operation(from: &str, path: &str) -> Result<PatchOperation, PatchError> {
+    Ok(copy_operation(parse_ptr(from, OperationKind::Copy)?, parse_ptr(path, OperationKind::Copy)?))
+}
+
+pub fn try_move_operation(from: &str, path: &str) -> Result<PatchOperation, PatchError> {
+    Ok(move_operation(parse_ptr(from, OperationKind::Move)?, parse_ptr(path, OperationKind::Move)?))
+}
+
+pub fn try_remove_operation(path: &str) -> Result<PatchOperation, PatchError> {
+    Ok(remove_operation(parse_ptr(path, OperationKind::Remove)?))
+}
+
+pub fn try_replace_operation(path: &str, value: impl Serialize) -> Result<PatchOperation, PatchError> {
+    replace_serialized_operation(parse_ptr(path, OperationKind::Replace)?, value)
+}
+
+pub fn try_test_operation(path: &str, value: impl Serialize) -> Result<PatchOperation, PatchError> {
+    test_serialized_operation(parse_ptr(path, OperationKind::Test)?, value)
+}
+
+pub(crate) fn parse_ptr(path: &str, kind: OperationKind) -> Result<PointerBuf, PatchError> {
+    PointerBuf::parse(path).map_err(|err| PatchError::from(err).with_operation(kind))
+}
+
 pub fn escape(input: &str) -> String {
-    Token::new(input).encoded().into()
+    key_token(input).encoded().into()
 }
 
 pub fn matches<'a>(path: &Pointer, value: &'a Value) -> Vec<(PointerBuf, &'a Value)> {
-    let Some(idx) = path.as_str().find("/*") else {
-        // Base case -- no stars;
-        // If we can't resolve, there's no match to be found
-        if let Ok(v) = path.resolve(value) {
-            return vec![(path.to_buf(), v)];
-        } else {
-            return vec![];
-        }
-    };
+    // The default options don't limit the expansion, so this can't fail
+    bounded_matches(path, value, &PatchOptions::default()).unwrap_or_default()
+}
 
-    // we checked the index above so unwrap is safe here
-    let (head, cons) = path.split_at(idx).unwrap();
+// Like matches, but stops with an error as soon as the expansion exceeds the `max_matches` or
+// `max_depth` limits in the options.
+pub(crate) fn bounded_matches<'a>(
+    path: &Pointer,
+    value: &'a Value,
+    opts: &PatchOptions,
+) -> Result<Vec<(PointerBuf, &'a Value)>, PatchError> {
     let mut res = vec![];
+    collect_matches(Pointer::root(), &segments(path), value, 0, opts, &mut res)?;
+    Ok(res)
+}
+
+// Find all of the values under `value` that match the path, and push them (along with their
+// concrete paths, relative to the document root) onto `res`; `prefix` is the path to `value`, and
+// `depth` is the number of levels that recursive wildcards have descended so far.
+fn collect_matches<'a>(
+    prefix: &Pointer,
+    segs: &[Segment],
+    value: &'a Value,
+    depth: usize,
+    opts: &PatchOptions,
+    res: &mut Vec<(PointerBuf, &'a Value)>,
+) -> Result<(), PatchError> {
+    let (head, wildcard) = split_segments(segs);
+
+    // If we can't resolve the head, no match found
+    let Some(head_val) = descend(value, head) else {
+        trace_event!(trace, path = %join(prefix, head), "path not found");
+        return Ok(());
+    };
+    let head_path = join(prefix, head);
 
-    // If we can't resolve the head, or it's not an array, no match found
-    let Ok(head_val) = head.resolve(value) else {
-        return vec![];
+    // Base case -- no stars
+    let Some((wildcard, tail)) = wildcard else {
+        check_max_matches(res.len() + 1, &head_path, opts)?;
+        res.push((head_path, head_val));
+        return Ok(());
     };
-    let Some(next_array_val) = head_val.as_array() else {
-        return vec![];
+
+    // A recursive wildcard can match zero path segments, so check the tail against the head, and
+    // then keep the wildcard around when we recurse so that it can match at every depth
+    let (next, next_depth) = match wildcard {
+        Wildcard::Recursive => {
+            collect_matches(&head_path, tail, head_val, depth, opts, res)?;
+            (&segs[head.len()..], depth + 1)
+        },
+        _ => (tail, depth),
     };
 
-    for (i, v) in next_array_val.iter().enumerate() {
-        // /1 is a valid pointer so the unwrap below is fine
-        let idx_str = format!("/{i}");
-        let idx_path = PointerBuf::parse(&idx_str).unwrap();
-
-        // The cons pointer either looks like /* or /*/something, so we need to split_front
-        // to get the array marker out, and either return the current path if there's nothing
-        // else, or recurse and concatenate the subpath(s) to the head
-        if let Some((_, c)) = cons.split_front() {
-            let subpaths = matches(c, v);
-            res.extend(subpaths.iter().map(|(p, v)| (head.concat(&idx_path.concat(p)), *v)));
-        } else {
-            unreachable!("cons can't be root");
-        }
+    // If the head isn't an array or an object, there are no children to match
+    let children: Vec<(Token, Option<usize>, &Value)> = match head_val {
+        Value::Array(vec) => vec.iter().enumerate().map(|(i, v)| (Token::from(i), Some(i), v)).collect(),
+        Value::Object(map) => map.iter().map(|(k, v)| (key_token(k), None, v)).collect(),
+        _ => vec![],
+    };
+    if let Some(max) = opts.max_depth.filter(|max| next_depth > *max && !children.is_empty()) {
+        return Err(PatchError::new(PatchErrorKind::MaxDepthExceeded(max), &head_path));
     }
-    res
+
+    // Recurse into every selected child, with the path to the child as the new prefix
+    let selected: Vec<_> = children.into_iter().filter(|(t, i, v)| wildcard.selects(t, *i, v)).collect();
+    trace_event!(trace, path = %head_path, matched = selected.len(), "expanded wildcard");
+    for (t, _, v) in selected {
+        let child_path = head_path.with_trailing_token(t);
+        collect_matches(&child_path, next, v, next_depth, opts, res)?;
+    }
+    Ok(())
+}
+
+fn check_max_matches(count: usize, path: &Pointer, opts: &PatchOptions) -> Result<(), PatchError> {
+    match opts.max_matches {
+        Some(max) if count > max => Err(PatchError::new(PatchErrorKind::TooManyMatches(max), path)),
+        _ => Ok(()),
+    }
+}
+
+// Like matches, but returns each matching value along with its concrete path
+pub fn resolve_matches<'a>(path: &Pointer, value: &'a Value) -> Vec<(&'a Value, PointerBuf)> {
+    matches(path, value).into_iter().map(|(p, v)| (v, p)).collect()
+}
+
+// Like resolve_matches, but returns mutable references to the matching values.  Since a recursive
+// wildcard can match both a value and one of its descendants (and we can't hand out mutable
+// references to both), paths containing `**` are an error here.
+pub fn resolve_matches_mut<'a>(
+    path: &Pointer,
+    value: &'a mut Value,
+) -> Result<Vec<(&'a mut Value, PointerBuf)>, PatchError> {
+    if is_recursive(path) {
+        return Err(PatchError::new(PatchErrorKind::InvalidWildcard, path));
+    }
+    let found = patch_ext_helper(path, value, PatchMode::Skip, &PatchOptions::default())?;
+    Ok(found.into_iter().map(|(p, v)| (v, p)).collect())
+}
+
+// Look up every value that the path references, using the same path syntax as the patch
+// operations.  As with the operations, a path without wildcards has to exist (or it's a
+// TargetDoesNotExist error), but a path with wildcards can match nothing.
+pub fn get_all<'a>(obj: &'a Value, path: &Pointer) -> Result<Vec<&'a Value>, PatchError> {
+    let found: Vec<_> = matches(path, obj).into_iter().map(|(_, v)| v).collect();
+    if found.is_empty() && wildcard_count(path) == 0 {
+        return Err(PatchError::new(PatchErrorKind::TargetDoesNotExist, path));
+    }
+    Ok(found)
+}
+
+// Look up the first value (in document order) that the path references; it's a TargetDoesNotExist
+// error if the path doesn't match anything.
+pub fn get_first<'a>(obj: &'a Value, path: &Pointer) -> Result<&'a Value, PatchError> {
+    get_all(obj, path)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| PatchError::new(PatchErrorKind::TargetDoesNotExist, path))
 }
 
 pub fn patch_ext(obj: &mut Value, p: PatchOperation) -> Result<(), PatchError> {
-    match p {
-        PatchOperation::Add(op) => add_or_replace(obj, &op.path, &op.value, false)?,
-        PatchOperation::Remove(op) => remove(obj, &op.path)?,
-        PatchOperation::Replace(op) => add_or_replace(obj, &op.path, &op.value, true)?,
-        x => patch(obj, &[x])?,
+    patch_ext_with(obj, p, &PatchOptions::default())
+}
+
+// Like patch_ext, but with control over how strictly the operation is applied
+pub fn patch_ext_with(obj: &mut Value, p: PatchOperation, opts: &PatchOptions) -> Result<(), PatchError> {
+    // The document's growth can only be checked after the operation is applied, so it needs to be
+    // rolled back if it grew too much
+    if opts.max_size_growth.is_some() {
+        return apply_all(obj, slice::from_ref(&p), opts).map(|_| ());
     }
+    apply_owned(obj, p, opts)?;
     Ok(())
 }
 
-fn add_or_replace(obj: &mut Value, path: &Pointer, value: &Value, replace: bool) -> Result<(), PatchError> {
-    let Some((subpath, tail)) = path.split_back() else {
-        return Ok(());
+// How a test is evaluated when its path contains wildcards: with `All`, every value that the path
+// matches must be equal to the expected value (which is vacuously true if nothing matches); with
+// `Any`, at least one of them must be.  A TestOperation in a standard patch always uses `All`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestMode {
+    #[default]
+    All,
+    Any,
+}
+
+// Test that the value(s) referenced by the path are equal to the expected value, without modifying
+// the document.  Paths without wildcards behave exactly like an RFC 6902 test.
+pub fn patch_ext_test(obj: &Value, path: &Pointer, value: &Value, mode: TestMode) -> Result<(), PatchError> {
+    test_values(obj, path, value, mode, &PatchOptions::default())
+}
+
+pub(crate) fn test_values(
+    obj: &Value,
+    path: &Pointer,
+    value: &Value,
+    mode: TestMode,
+    opts: &PatchOptions,
+) -> Result<(), PatchError> {
+    let failed = |path: &Pointer, actual: Option<&Value>| {
+        let kind = PatchErrorKind::TestMismatch {
+            expected: Box::new(value.clone()),
+            actual: actual.cloned().map(Box::new),
+        };
+        PatchError::new(kind, path)
     };
+    if wildcard_count(path) == 0 {
+        let actual = unescape_path(path).resolve(obj).map_err(PatchError::at(path))?;
+        return if actual == value { Ok(()) } else { Err(failed(path, Some(actual))) };
+    }
 
-    // "replace" requires that the path you're replacing already exist, therefore we set
-    // create_if_not_exists = !replace.  We don't want to skip missing elements.
-    let mode = if replace { PatchMode::Error } else { PatchMode::Create };
-    for v in patch_ext_helper(subpath, obj, mode)? {
-        match v {
-            Value::Object(map) => {
-                let key = tail.decoded().into();
-                if replace && !map.contains_key(&key) {
-                    return Err(PatchError::TargetDoesNotExist(path.to_string()));
+    let mut found = bounded_matches(path, obj, opts)?.into_iter();
+    match mode {
+        TestMode::All => match found.find(|(_, v)| *v != value) {
+            Some((p, actual)) => Err(failed(&p, Some(actual))),
+            None => Ok(()),
+        },
+        TestMode::Any => match found.any(|(_, v)| v == value) {
+            true => Ok(()),
+            false => Err(failed(path, None)),
+        },
+    }
+}
+
+// Operations that this crate knows how to apply; this lets us share the machinery for applying
+// (or validating) a whole patch between the standard json_patch operations and our extended ones.
+pub(crate) trait Operation {
+    fn kind(&self) -> OperationKind;
+
+    // Apply the operation to the document, returning the concrete paths (after wildcard expansion)
+    // that were written to or removed
+    fn apply(&self, obj: &mut Value, opts: &PatchOptions) -> Result<Vec<PointerBuf>, PatchError>;
+
+    // The (unexpanded) paths that the operation writes to or removes; this is empty if the
+    // operation doesn't modify the document
+    fn targets(&self) -> Vec<&Pointer>;
+
+    // Every path that the operation references, including the ones that it only reads from
+    fn paths(&self) -> Vec<&Pointer>;
+
+    // The `from` and `path` of an operation that copies values from one place to another, whose
+    // destinations are filled in from what the wildcards in `from` matched (see aligned_matches)
+    fn transfer(&self) -> Option<(&Pointer, &Pointer)> {
+        None
+    }
+
+    // The annotation attached to the operation, which is propagated to errors and reports
+    fn annotation(&self) -> Option<&Annotation> {
+        None
+    }
+}
+
+impl Operation for PatchOperation {
+    fn kind(&self) -> OperationKind {
+        self.into()
+    }
+
+    fn apply(&self, obj: &mut Value, opts: &PatchOptions) -> Result<Vec<PointerBuf>, PatchError> {
+        match self {
+            PatchOperation::Add(op) => add_or_replace(obj, &op.path, op.value.clone(), false, opts),
+            PatchOperation::Remove(op) => remove(obj, &op.path, opts),
+            PatchOperation::Replace(op) => add_or_replace(obj, &op.path, op.value.clone(), true, opts),
+            PatchOperation::Move(op) => move_or_copy(obj, &op.from, &op.path, true, opts),
+            PatchOperation::Copy(op) => move_or_copy(obj, &op.from, &op.path, false, opts),
+            PatchOperation::Test(op) => test_values(obj, &op.path, &op.value, TestMode::All, opts).map(|_| vec![]),
+        }
+    }
+
+    fn targets(&self) -> Vec<&Pointer> {
+        match self {
+            PatchOperation::Add(AddOperation { path, .. })
+            | PatchOperation::Remove(RemoveOperation { path })
+            | PatchOperation::Replace(ReplaceOperation { path, .. })
+            | PatchOperation::Copy(CopyOperation { path, .. }) => vec![path],
+            PatchOperation::Move(MoveOperation { from, path }) => vec![from, path],
+            PatchOperation::Test(_) => vec![],
+        }
+    }
+
+    fn paths(&self) -> Vec<&Pointer> {
+        match self {
+            PatchOperation::Copy(CopyOperation { from, path }) => vec![from, path],
+            PatchOperation::Test(TestOperation { path, .. }) => vec![path],
+            op => op.targets(),
+        }
+    }
+
+    fn transfer(&self) -> Option<(&Pointer, &Pointer)> {
+        match self {
+            PatchOperation::Move(MoveOperation { from, path }) | PatchOperation::Copy(CopyOperation { from, path }) => {
+                Some((from, path))
+            },
+            _ => None,
+        }
+    }
+}
+
+// Like patch_ext, but returns all of the concrete paths (after wildcard expansion) that were
+// written to or removed by the operation.  Removed array elements are reported using the index
+// they had before the operation was applied.
+pub fn patch_ext_traced(obj: &mut Value, p: PatchOperation) -> Result<Vec<PointerBuf>, PatchError> {
+    apply_owned(obj, p, &PatchOptions::default())
+}
+
+// Apply all of the operations in the patch, in order; if any of them fail, the document is rolled
+// back to its original state.  Instead of cloning the entire document up front, we snapshot the
+// values that each operation could modify before applying it (see Snapshot), and undo the
+// snapshots in reverse order on failure.
+pub fn patch_ext_all(obj: &mut Value, p: &Patch) -> Result<(), PatchError> {
+    apply_all(obj, p, &PatchOptions::default()).map(|_| ())
+}
+
+// Like patch_ext_all, but with control over how strictly the operations are applied (as in
+// patch_ext_with); the `max_size_growth` option limits the growth of the document over the whole
+// patch.
+pub fn patch_ext_all_with(obj: &mut Value, p: &Patch, opts: &PatchOptions) -> Result<(), PatchError> {
+    apply_all(obj, p, opts).map(|_| ())
+}
+
+// Like patch_ext_all, but skips any add or replace that wouldn't change the document (as in the
+// `skip_unchanged_writes` option), and returns whether anything actually changed; e.g., a reconcile
+// loop can use this to decide whether the updated document needs to be saved.
+pub fn patch_ext_changed(obj: &mut Value, p: &Patch) -> Result<bool, PatchError> {
+    apply_all(obj, p, &PatchOptions { skip_unchanged_writes: true, ..Default::default() })
+}
+
+// Apply the same patch to every document, as in patch_ext_all, returning the result for each
+// document in order; a failure only rolls back the document that it happened in.  With the `rayon`
+// feature enabled, the documents are patched in parallel.
+pub fn patch_many(docs: &mut [Value], p: &Patch) -> Vec<Result<(), PatchError>> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        docs.par_iter_mut().map(|doc| patch_ext_all(doc, p)).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    docs.iter_mut().map(|doc| patch_ext_all(doc, p)).collect()
+}
+
+// Check whether every operation in the patch would succeed, without modifying the document; each
+// operation is checked against the result of applying all of the previous operations, so this has
+// to make a copy of the document to work on.
+pub fn validate_patch(obj: &Value, p: &Patch) -> Result<PatchReport, PatchError> {
+    validate_all(obj, p)
+}
+
+// Apply every operation in the patch, in order, and report what happened to each of them.  Unlike
+// patch_ext_all, this keeps going when an operation fails: the failed operation is rolled back (so
+// it doesn't leave a partial change behind), and the rest of the patch is applied to the document
+// as it was before that operation.
+pub fn patch_ext_report(obj: &mut Value, p: &Patch) -> PatchReport {
+    report_all(obj, p)
+}
+
+pub(crate) fn report_all<O: Operation>(obj: &mut Value, ops: &[O]) -> PatchReport {
+    let mut report = PatchReport::default();
+    for (i, op) in ops.iter().enumerate() {
+        let snapshot = Snapshot::new(obj, op);
+
+        let (res, duration) = timed(|| apply_operation(obj, op, &PatchOptions::default()));
+        let op_report = match res {
+            Ok(modified) => OperationReport {
+                outcome: outcome(op, &modified),
+                modified: modified.len(),
+                duration,
+                annotation: op.annotation().cloned(),
+            },
+            Err(err) => {
+                rollback(obj, vec![snapshot]);
+                let outcome = OperationOutcome::Failed(err.with_operation_index(i));
+                OperationReport {
+                    outcome,
+                    modified: 0,
+                    duration,
+                    annotation: op.annotation().cloned(),
                 }
-                map.insert(key, value.clone());
             },
-            Value::Array(vec) => match tail.to_index()? {
+        };
+        report.operations.push(op_report);
+    }
+    report
+}
+
+// Apply every operation in the patch, in order, skipping over any operation that fails with one of
+// the kinds of errors that the policy allows (the failed operation is rolled back, as in
+// patch_ext_report); returns the errors from all of the skipped operations.  Any other error
+// aborts the patch, and rolls back the entire document, as in patch_ext_all.
+pub fn patch_ext_lenient(obj: &mut Value, p: &Patch, policy: &ErrorPolicy) -> Result<Vec<PatchError>, PatchError> {
+    let mut snapshots = vec![];
+    let mut skipped = vec![];
+    for (i, op) in p.iter().enumerate() {
+        let n = snapshots.len();
+        snapshots.push(Snapshot::new(obj, op));
+
+        if let Err(err) = apply_operation(obj, op, &PatchOptions::default()) {
+            let err = err.with_operation_index(i);
+            if !policy.skips(err.kind()) {
+                rollback(obj, snapshots);
+                return Err(err);
+            }
+            rollback(obj, snapshots.split_off(n));
+            skipped.push(err);
+        }
+    }
+    Ok(skipped)
+}
+
+// Run the closure, and measure how long it took; without `std` there's no clock to read, so the
+// duration is always zero.
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    #[cfg(feature = "std")]
+    {
+        let start = Instant::now();
+        let res = f();
+        (res, start.elapsed())
+    }
+    #[cfg(not(feature = "std"))]
+    (f(), Duration::ZERO)
+}
+
+fn outcome<O: Operation>(op: &O, modified: &[PointerBuf]) -> OperationOutcome {
+    if modified.is_empty() && op.kind() != OperationKind::Test {
+        OperationOutcome::Skipped
+    } else {
+        OperationOutcome::Applied
+    }
+}
+
+// Apply all of the operations atomically, and return whether any of them modified the document
+pub(crate) fn apply_all<O: Operation>(obj: &mut Value, ops: &[O], opts: &PatchOptions) -> Result<bool, PatchError> {
+    let mut snapshots = vec![];
+    let mut changed = false;
+    let mut growth = 0;
+    for (i, op) in ops.iter().enumerate() {
+        let snapshot = Snapshot::new(obj, op);
+        let res = apply_operation(obj, op, opts).and_then(|modified| {
+            if let Some(max) = opts.max_size_growth {
+                growth += snapshot.size_delta(obj);
+                check_size_growth(growth, max)
+                    .map_err(|err| err.with_operation(op.kind()).with_annotation(op.annotation()))?;
+            }
+            Ok(modified)
+        });
+        snapshots.push(snapshot);
+        match res {
+            Ok(modified) => changed |= !modified.is_empty(),
+            Err(err) => {
+                rollback(obj, snapshots);
+                return Err(err.with_operation_index(i));
+            },
+        }
+    }
+    Ok(changed)
+}
+
+pub(crate) fn validate_all<O: Operation>(obj: &Value, ops: &[O]) -> Result<PatchReport, PatchError> {
+    let mut doc = obj.clone();
+    let mut report = PatchReport::default();
+    for (i, op) in ops.iter().enumerate() {
+        let (res, duration) = timed(|| apply_operation(&mut doc, op, &PatchOptions::default()));
+        let modified = res.map_err(|err| err.with_operation_index(i))?;
+        report.operations.push(OperationReport {
+            outcome: outcome(op, &modified),
+            modified: modified.len(),
+            duration,
+            annotation: op.annotation().cloned(),
+        });
+    }
+    Ok(report)
+}
+
+// Apply a single operation to the document, returning the concrete paths (after wildcard
+// expansion) that were modified.
+pub(crate) fn apply_operation<O: Operation>(
+    obj: &mut Value,
+    op: &O,
+    opts: &PatchOptions,
+) -> Result<Vec<PointerBuf>, PatchError> {
+    trace_span!("patch_operation", op = %op.kind());
+    let res = check_pointer_depth(op, opts).and_then(|_| op.apply(obj, opts));
+    trace_result(&res);
+    res.map_err(|err| err.with_operation(op.kind()).with_annotation(op.annotation()))
+}
+
+// Like apply_operation, but since we own the operation, the value in an add or replace operation
+// can be moved into the document instead of cloned
+pub(crate) fn apply_owned(
+    obj: &mut Value,
+    p: PatchOperation,
+    opts: &PatchOptions,
+) -> Result<Vec<PointerBuf>, PatchError> {
+    let kind = p.kind();
+    trace_span!("patch_operation", op = %kind);
+    let res = check_pointer_depth(&p, opts).and_then(|_| match p {
+        PatchOperation::Add(op) => add_or_replace(obj, &op.path, op.value, false, opts),
+        PatchOperation::Replace(op) => add_or_replace(obj, &op.path, op.value, true, opts),
+        op => op.apply(obj, opts),
+    });
+    trace_result(&res);
+    res.map_err(|err| err.with_operation(kind))
+}
+
+fn trace_result(res: &Result<Vec<PointerBuf>, PatchError>) {
+    #[cfg(feature = "tracing")]
+    match res {
+        Ok(modified) => tracing::debug!(modified = modified.len(), "applied operation"),
+        Err(err) => tracing::debug!(error = %err, "operation failed"),
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = res;
+}
+
+fn check_pointer_depth<O: Operation>(op: &O, opts: &PatchOptions) -> Result<(), PatchError> {
+    let Some(max) = opts.max_pointer_depth else {
+        return Ok(());
+    };
+    match op.paths().into_iter().find(|p| p.count() > max) {
+        Some(path) => Err(PatchError::new(PatchErrorKind::PointerTooDeep(max), path)),
+        None => Ok(()),
+    }
+}
+
+fn check_size_growth(growth: isize, max: usize) -> Result<(), PatchError> {
+    if usize::try_from(growth).is_ok_and(|growth| growth > max) {
+        return Err(PatchError::new(PatchErrorKind::SizeLimitExceeded(max), Pointer::root()));
+    }
+    Ok(())
+}
+
+// The approximate size in bytes of the value's compact JSON serialization (ignoring any escaping
+// in strings), without actually serializing it
+pub(crate) fn json_size(v: &Value) -> usize {
+    let separators = |n: usize| 2 + n.saturating_sub(1);
+    match v {
+        Value::Null | Value::Bool(true) => 4,
+        Value::Bool(false) => 5,
+        Value::Number(n) => n.to_string().len(),
+        Value::String(s) => s.len() + 2,
+        Value::Array(vec) => separators(vec.len()) + vec.iter().map(json_size).sum::<usize>(),
+        Value::Object(map) => {
+            separators(map.len()) + map.iter().map(|(k, v)| k.len() + 3 + json_size(v)).sum::<usize>()
+        },
+    }
+}
+
+pub(crate) fn patch_upstream(obj: &mut Value, p: &PatchOperation) -> Result<(), PatchError> {
+    Ok(patch(obj, slice::from_ref(p))?)
+}
+
+// Compute the deepest existing path in the document which contains everything that the operation
+// could modify; returns None if the operation doesn't modify the document.  The operation can't
+// remove the subtree itself (only modify its contents), so the path still exists afterwards.
+pub(crate) fn affected_subtree<O: Operation>(obj: &Value, op: &O) -> Option<PointerBuf> {
+    let parent = |p: &Pointer| p.parent().unwrap_or(Pointer::root()).to_buf();
+    let mut targets = op.targets().into_iter();
+    let mut path = parent(targets.next()?);
+    for t in targets {
+        path = path.intersection(&parent(t)).to_buf();
+    }
+
+    // Everything after a wildcard could be modified, and anything that doesn't exist yet may get
+    // auto-created, so walk up the tree until we find something concrete
+    let mut subtree = PointerBuf::from_tokens(path.tokens().take_while(|t| Wildcard::parse(t).is_none()));
+    while resolve_ext(obj, &subtree).is_none() {
+        subtree.pop_back();
+    }
+    Some(subtree)
+}
+
+// The value is moved into the last target, so it's only cloned when the path references more than
+// one location in the document.
+pub(crate) fn add_or_replace(
+    obj: &mut Value,
+    path: &Pointer,
+    value: Value,
+    replace: bool,
+    opts: &PatchOptions,
+) -> Result<Vec<PointerBuf>, PatchError> {
+    // There's no sensible place to insert a new value at the end of a wildcard; a key that's
+    // actually named `*` (or looks like a filter or a slice) has to be escaped, e.g. `/foo/\*`
+    if !replace && ends_with_wildcard(path) {
+        return Err(PatchError::new(PatchErrorKind::InvalidWildcard, path));
+    }
+
+    if is_recursive(path) {
+        let mut modified = vec![];
+        for (p, value) in with_values(recursive_targets(obj, path, opts)?, value) {
+            modified.extend(add_or_replace(obj, &p, value, replace, opts)?);
+        }
+        return Ok(modified);
+    }
+
+    // Adding or replacing the root replaces the whole document
+    let Some((subpath, tail)) = path.split_back() else {
+        if opts.skip_unchanged_writes && *obj == value {
+            return Ok(vec![]);
+        }
+        *obj = value;
+        return Ok(vec![PointerBuf::root()]);
+    };
+
+    // A replace that ends with a wildcard replaces every value that the wildcard matches (so e.g.
+    // `/foo/*` sets every element of the array to the value)
+    if replace && Wildcard::parse(&tail).is_some() {
+        let mut modified = vec![];
+        for ((p, v), value) in with_values(patch_ext_helper(path, obj, PatchMode::Error, opts)?, value) {
+            if opts.skip_unchanged_writes && *v == value {
+                continue;
+            }
+            *v = value;
+            trace_event!(trace, path = %p, "replaced value");
+            modified.push(p);
+        }
+        return Ok(modified);
+    }
+
+    // "replace" requires that the path you're replacing already exist, therefore we only create
+    // missing parents for "add" (if the options allow it).  We don't want to skip missing elements.
+    let mode = match (replace || !opts.allow_autocreate_parents, is_index(&tail)) {
+        (true, _) => PatchMode::Error,
+        (false, true) if opts.autocreate_arrays_for_numeric_tokens => PatchMode::CreateArray,
+        (false, _) => PatchMode::Create,
+    };
+    let mut modified = vec![];
+    for ((parent, v), value) in with_values(patch_ext_helper(subpath, obj, mode, opts)?, value) {
+        if opts.skip_unchanged_writes && is_unchanged(v, &tail, &value, replace) {
+            continue;
+        }
+        let p = insert_value(parent, v, &tail, value, replace, opts)?;
+        if replace {
+            trace_event!(trace, path = %p, "replaced value");
+        } else {
+            trace_event!(trace, path = %p, "added value");
+        }
+        modified.push(p);
+    }
+
+    Ok(modified)
+}
+
+// Whether writing the value into the parent at the location given by the token would leave the
+// parent as it is
+fn is_unchanged(parent: &Value, tail: &Token, value: &Value, replace: bool) -> bool {
+    let tail = literal(tail);
+    match parent {
+        Value::Object(map) => map.get(tail.decoded().as_ref()) == Some(value),
+        Value::Array(vec) if replace => {
+            matches!(resolve_index(&tail, vec.len()), Ok(Index::Num(idx)) if vec.get(idx) == Some(value))
+        },
+        _ => false,
+    }
+}
+
+// Insert the value into the parent (which must be an array or an object) at the location given by
+// the token, and return the concrete path to the new value
+fn insert_value(
+    parent: PointerBuf,
+    v: &mut Value,
+    tail: &Token,
+    value: Value,
+    replace: bool,
+    opts: &PatchOptions,
+) -> Result<PointerBuf, PatchError> {
+    let literal_tail = literal(tail);
+    match v {
+        Value::Object(map) => {
+            let key = literal_tail.decoded().into();
+            let target = parent.with_trailing_token(tail.clone());
+            if replace && !map.contains_key(&key) {
+                return Err(PatchError::new(PatchErrorKind::TargetDoesNotExist, &target));
+            }
+            map.insert(key, value);
+            Ok(target)
+        },
+        Value::Array(vec) => {
+            match resolve_index(&literal_tail, vec.len())
+                .map_err(PatchError::at(&parent.with_trailing_token(tail.clone())))?
+            {
                 Index::Num(idx) => {
-                    vec.get(idx).ok_or(PatchError::OutOfBounds(idx))?;
+                    // As in RFC 6902, adding at the index one past the end appends the value
+                    let len = if replace { vec.len() } else { vec.len() + 1 };
+                    if idx >= len {
+                        return Err(PatchError::new(PatchErrorKind::OutOfBounds(idx), &parent));
+                    }
                     if replace {
-                        vec[idx] = value.clone();
+                        vec[idx] = value;
                     } else {
-                        vec.insert(idx, value.clone());
+                        check_max_index(idx, &parent, opts)?;
+                        vec.insert(idx, value);
                     }
+                    Ok(parent.with_trailing_token(idx))
                 },
                 Index::Next => {
-                    vec.push(value.clone());
+                    check_max_index(vec.len(), &parent, opts)?;
+                    vec.push(value);
+                    Ok(parent.with_trailing_token(vec.len() - 1))
                 },
-            },
-            _ => {
-                return Err(PatchError::UnexpectedType(path.to_string()));
-            },
-        }
+            }
+        },
+        _ => Err(PatchError::new(PatchErrorKind::UnexpectedType, &parent)),
     }
+}
 
-    Ok(())
+fn check_max_index(idx: usize, path: &Pointer, opts: &PatchOptions) -> Result<(), PatchError> {
+    match opts.max_array_index {
+        Some(max) if idx > max => Err(PatchError::new(PatchErrorKind::IndexTooLarge(idx), path)),
+        _ => Ok(()),
+    }
+}
+
+// Pair each target with its own copy of the value; the value itself is moved into the last target,
+// so that n targets only need n - 1 clones.
+fn with_values<T>(targets: Vec<T>, value: Value) -> impl Iterator<Item = (T, Value)> {
+    let n = targets.len();
+    let mut value = Some(value);
+    targets.into_iter().enumerate().map(move |(i, t)| {
+        let v = if i + 1 == n { value.take() } else { value.clone() };
+        (t, v.unwrap_or_default())
+    })
+}
+
+pub(crate) fn remove(obj: &mut Value, path: &Pointer, opts: &PatchOptions) -> Result<Vec<PointerBuf>, PatchError> {
+    Ok(remove_values(obj, path, opts)?.into_iter().map(|(p, _)| p).collect())
 }
 
-fn remove(obj: &mut Value, path: &Pointer) -> Result<(), PatchError> {
+// Remove every value that the path references (as in a remove operation), and return the removed
+// values, e.g. to record them in an audit trail.  As with the default PatchOptions, any part of the
+// path that doesn't exist is skipped.  If any of the removals fail, the document may have been
+// partially modified.
+pub fn remove_ext(obj: &mut Value, path: &Pointer) -> Result<Vec<Value>, PatchError> {
+    Ok(remove_values(obj, path, &PatchOptions::default())?.into_iter().map(|(_, v)| v).collect())
+}
+
+// Like remove, but also returns the removed values along with their (original) concrete paths
+fn remove_values(obj: &mut Value, path: &Pointer, opts: &PatchOptions) -> Result<Vec<(PointerBuf, Value)>, PatchError> {
+    if is_recursive(path) {
+        let mut removed = vec![];
+        for p in recursive_targets(obj, path, opts)? {
+            removed.extend(remove_values(obj, &p, opts)?);
+        }
+        return Ok(removed);
+    }
+
     let Some((subpath, key)) = path.split_back() else {
-        *obj = Value::Null;
-        return Ok(());
+        return Ok(vec![(PointerBuf::root(), mem::take(obj))]);
     };
 
-    for v in patch_ext_helper(subpath, obj, PatchMode::Skip)? {
+    // A wildcard at the end of the path is expanded as we remove, so check the limits up front
+    if opts.max_matches.is_some() && Wildcard::parse(&key).is_some() {
+        bounded_matches(path, obj, opts)?;
+    }
+
+    let mode = if opts.skip_missing_on_remove { PatchMode::Skip } else { PatchMode::Error };
+    let mut removed = vec![];
+    for (parent, v) in patch_ext_helper(subpath, obj, mode, opts)? {
         match v {
             Value::Object(map) => {
-                map.remove(key.decoded().as_ref());
+                if let Some(wildcard) = Wildcard::parse(&key) {
+                    for (k, v) in mem::take(map) {
+                        let t = key_token(&k);
+                        if wildcard.selects(&t, None, &v) {
+                            removed.push((parent.with_trailing_token(t.into_owned()), v));
+                        } else {
+                            map.insert(k, v);
+                        }
+                    }
+                } else if let Some(v) = remove_key(map, literal(&key).decoded().as_ref()) {
+                    removed.push((parent.with_trailing_token(key.clone()), v));
+                } else if !opts.skip_missing_on_remove {
+                    return Err(PatchError::new(PatchErrorKind::TargetDoesNotExist, &parent.with_trailing_token(key)));
+                }
             },
             Value::Array(vec) => {
-                if key.decoded() == "*" {
-                    vec.clear();
-                } else if let Index::Num(idx) = key.to_index()? {
-                    vec.get(idx).ok_or(PatchError::OutOfBounds(idx))?;
-                    vec.remove(idx);
+                if let Some(wildcard) = Wildcard::parse(&key) {
+                    for (i, v) in mem::take(vec).into_iter().enumerate() {
+                        if wildcard.selects(&Token::from(i), Some(i), &v) {
+                            removed.push((parent.with_trailing_token(i), v));
+                        } else {
+                            vec.push(v);
+                        }
+                    }
+                } else if let Index::Num(idx) = resolve_index(&literal(&key), vec.len())
+                    .map_err(PatchError::at(&parent.with_trailing_token(key.clone())))?
+                {
+                    vec.get(idx).ok_or(PatchError::new(PatchErrorKind::OutOfBounds(idx), &parent))?;
+                    removed.push((parent.with_trailing_token(idx), vec.remove(idx)));
                 } else {
-                    return Err(PatchError::UnexpectedType(key.to_string()));
+                    return Err(PatchError::new(PatchErrorKind::TargetDoesNotExist, &parent.with_trailing_token(key)));
                 }
             },
             _ => {
-                return Err(PatchError::UnexpectedType(path.to_string()));
+                return Err(PatchError::new(PatchErrorKind::UnexpectedType, &parent));
             },
         }
     }
 
-    Ok(())
+    Ok(removed)
+}
+
+// Compare two concrete paths by where they appear in a (pre-order) walk of the document: parents
+// come before their children, and siblings are ordered by array index or key
+pub(crate) fn document_order(a: &Pointer, b: &Pointer) -> Ordering {
+    for (s, t) in a.tokens().zip(b.tokens()) {
+        let ord = match (array_index(&s), array_index(&t)) {
+            (Some(i), Some(j)) => i.cmp(&j),
+            _ => literal(&s).decoded().cmp(&literal(&t).decoded()),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.tokens().count().cmp(&b.tokens().count())
+}
+
+// Parse an array index token; in addition to the usual indices (and `-`), this accepts negative
+// indices that count from the end of the array, so `-1` refers to the last element, `-2` to the
+// second-to-last, and so on.
+pub(crate) fn resolve_index(t: &Token, len: usize) -> Result<Index, PatchErrorKind> {
+    match t.decoded().strip_prefix('-').and_then(|n| n.parse::<usize>().ok()).filter(|n| *n > 0) {
+        Some(n) => len.checked_sub(n).map(Index::Num).ok_or(PatchErrorKind::NegativeIndexOutOfBounds(n)),
+        None => Ok(t.to_index()?),
+    }
+}
+
+// Move or copy values element-wise between wildcard paths: each wildcard in `path` is a
+// placeholder for the segment(s) matched by the corresponding wildcard in `from`, so copying
+// `/foo/*/baz` to `/backup/*/baz` copies `/foo/0/baz` to `/backup/0/baz`, `/foo/1/baz` to
+// `/backup/1/baz`, and so on.  Both paths must contain the same number of wildcards, unless `path`
+// references the wildcards by number instead (see aligned_matches).  Destinations
+// are written using add semantics (so missing parents are created), after all of the sources have
+// been read (and, for a move, removed).  Paths without any wildcards are passed through to
+// json_patch unchanged.
+pub(crate) fn move_or_copy(
+    obj: &mut Value,
+    from: &Pointer,
+    path: &Pointer,
+    is_move: bool,
+    opts: &PatchOptions,
+) -> Result<Vec<PointerBuf>, PatchError> {
+    match (wildcard_count(from), wildcard_count(path)) {
+        (0, 0) if is_move => {
+            #[cfg(feature = "preserve_order")]
+            move_ordered(obj, &unescape_path(from), &unescape_path(path))?;
+            #[cfg(not(feature = "preserve_order"))]
+            patch_upstream(obj, &move_operation(unescape_path(from), unescape_path(path)))?;
+            return Ok(vec![from.to_buf(), path.to_buf()]);
+        },
+        (0, 0) => {
+            patch_upstream(obj, &copy_operation(unescape_path(from), unescape_path(path)))?;
+            return Ok(vec![path.to_buf()]);
+        },
+        _ => (),
+    }
+
+    let mut transfers = vec![];
+    for (src, dest, v) in aligned_matches(obj, from, path, opts)? {
+        if is_move && dest.starts_with(&src) && dest != src {
+            let kind = json_patch::PatchErrorKind::CannotMoveInsideItself.into();
+            return Err(PatchError::new(kind, &dest));
+        }
+        transfers.push((src, dest, v.clone()));
+    }
+
+    let mut modified = vec![];
+    if is_move {
+        // Remove in reverse order so that removing an array element doesn't shift the indices of
+        // the sources that come after it
+        for (src, ..) in transfers.iter().rev() {
+            modified.extend(remove(obj, src, opts)?);
+        }
+    }
+    for (_, dest, v) in transfers {
+        modified.extend(add_or_replace(obj, &dest, v, false, opts)?);
+    }
+    Ok(modified)
+}
+
+// json_patch removes the source of a move with `Map::remove`, which (with the `preserve_order`
+// feature) moves the last key of the source object into the removed key's place; so if the source
+// is a key in an object, we remove it ourselves and then add it at the destination.  Everything
+// else, including reporting any errors, is left to json_patch.
+#[cfg(feature = "preserve_order")]
+fn move_ordered(obj: &mut Value, from: &Pointer, path: &Pointer) -> Result<(), PatchError> {
+    let inside = path.starts_with(from) && path.len() != from.len();
+    let source = match from.split_back() {
+        Some((parent, key)) if !inside => match obj.pointer_mut(parent.as_str()) {
+            // Moving a key onto itself doesn't do anything
+            Some(Value::Object(map)) if from == path && map.contains_key(key.decoded().as_ref()) => return Ok(()),
+            Some(Value::Object(map)) => remove_key(map, key.decoded().as_ref()),
+            _ => None,
+        },
+        _ => None,
+    };
+    match source {
+        Some(v) => patch_upstream(obj, &add_operation(path.to_buf(), v)),
+        None => patch_upstream(obj, &move_operation(from.to_buf(), path.to_buf())),
+    }
+}
+
+// Expand `from`, and pair each match with the destination that it maps to in `path` (whose
+// wildcards are filled in with whatever the corresponding wildcards in `from` matched); both paths
+// must contain the same number of wildcards.  Alternatively, `path` can reference the wildcards in
+// `from` by number, in any order: `*1` is filled in with whatever the first wildcard matched, `*2`
+// with the second, and so on, so copying `/foo/*/bar/*` to `/baz/*2/*1` swaps the two levels.
+pub(crate) fn aligned_matches<'a>(
+    obj: &'a Value,
+    from: &Pointer,
+    path: &Pointer,
+    opts: &PatchOptions,
+) -> Result<Vec<(PointerBuf, PointerBuf, &'a Value)>, PatchError> {
+    if !is_aligned(from, path) {
+        return Err(PatchError::new(PatchErrorKind::InvalidWildcard, path));
+    }
+
+    let pattern: Vec<Token> = from.tokens().collect();
+    let mut res = vec![];
+    for (src, v) in bounded_matches(from, obj, opts)? {
+        let tokens: Vec<Token> = src.tokens().collect();
+        // every match was produced by the pattern, so the captures always exist
+        let Some(caps) = captures(&pattern, &tokens) else {
+            continue;
+        };
+        let dest = fill_wildcards(path, caps);
+        res.push((src, dest, v));
+    }
+    Ok(res)
+}
+
+// Whether every wildcard in `path` can be filled in from the wildcards in `from`: either they have
+// the same number of wildcards, or `path` only contains numbered references to existing ones
+pub(crate) fn is_aligned(from: &Pointer, path: &Pointer) -> bool {
+    let refs: Vec<usize> = path.tokens().filter_map(|t| capture_ref(&t)).collect();
+    if refs.is_empty() {
+        return wildcard_count(from) == wildcard_count(path);
+    }
+    wildcard_count(path) == 0 && refs.into_iter().all(|n| n <= wildcard_count(from))
+}
+
+// The number of a wildcard reference token like `*1` (wildcards are numbered from 1)
+pub(crate) fn capture_ref(t: &Token) -> Option<usize> {
+    t.decoded().strip_prefix('*')?.parse().ok().filter(|n| *n > 0)
+}
+
+// Find the segment(s) of a concrete path that were matched by each of the wildcards in the pattern;
+// for a recursive wildcard, this is the shortest sequence of segments that lets the rest of the
+// pattern match.
+pub(crate) fn captures(pattern: &[Token], path: &[Token]) -> Option<Vec<PointerBuf>> {
+    let Some((t, rest)) = pattern.split_first() else {
+        return path.is_empty().then(Vec::new);
+    };
+
+    match Wildcard::parse(t) {
+        Some(Wildcard::Recursive) => (0..=path.len()).find_map(|i| {
+            let mut caps = captures(rest, &path[i..])?;
+            caps.insert(0, PointerBuf::from_tokens(path[..i].iter().cloned()));
+            Some(caps)
+        }),
+        Some(_) => {
+            let (first, tail) = path.split_first()?;
+            let mut caps = captures(rest, tail)?;
+            caps.insert(0, PointerBuf::from_tokens([first.clone()]));
+            Some(caps)
+        },
+        None => {
+            let (first, tail) = path.split_first()?;
+            if literal(first) != literal(t) {
+                return None;
+            }
+            captures(rest, tail)
+        },
+    }
+}
+
+// Replace each wildcard (or numbered wildcard reference) in the path with the corresponding
+// captured segment(s)
+fn fill_wildcards(path: &Pointer, caps: Vec<PointerBuf>) -> PointerBuf {
+    let mut next = caps.iter();
+    let mut res = PointerBuf::new();
+    for t in path.tokens() {
+        if let Some(n) = capture_ref(&t) {
+            res.append(caps.get(n - 1).cloned().unwrap_or_default());
+        } else if Wildcard::parse(&t).is_some() {
+            res.append(next.next().cloned().unwrap_or_default());
+        } else {
+            res.push_back(t);
+        }
+    }
+    res
+}
+
+pub(crate) fn wildcard_count(path: &Pointer) -> usize {
+    path.tokens().filter(|t| Wildcard::parse(t).is_some()).count()
+}
+
+// The different kinds of wildcard tokens that can appear in a path: `*` selects every child of an
+// array or object, `**` selects zero or more path segments, `[?key=value]` selects every child
+// whose `key` field is equal to `value`, and `start:end` selects the array elements whose index is
+// in the (half-open) range; either end of the range can be omitted.  Any other token containing a
+// `*` is a glob, which selects the object keys that match it (with `*` matching any sequence of
+// characters, e.g. `example.com/*`), and a token starting with `(?i)` selects the object keys that
+// match the rest of it (which can also be a glob) ignoring case.
+enum Wildcard {
+    All,
+    Recursive,
+    Filter(String, String),
+    Slice(Option<usize>, Option<usize>),
+    // The pattern, and whether it's case-insensitive
+    Glob(String, bool),
+}
+
+impl Wildcard {
+    fn parse(t: &Token) -> Option<Wildcard> {
+        let t = t.decoded();
+        if t.starts_with('\\') {
+            return None;
+        }
+        match t.as_ref() {
+            "*" => Some(Wildcard::All),
+            "**" => Some(Wildcard::Recursive),
+            _ => {
+                let bound = |s: &str| if s.is_empty() { Some(None) } else { s.parse().ok().map(Some) };
+                if let Some((start, end)) = t.split_once(':')
+                    && let (Some(start), Some(end)) = (bound(start), bound(end))
+                {
+                    return Some(Wildcard::Slice(start, end));
+                }
+                if let Some(filter) = t.strip_prefix("[?").and_then(|f| f.strip_suffix(']'))
+                    && let Some((key, value)) = filter.split_once('=')
+                {
+                    return Some(Wildcard::Filter(key.into(), value.into()));
+                }
+                if let Some(pattern) = t.strip_prefix("(?i)") {
+                    return Some(Wildcard::Glob(pattern.into(), true));
+                }
+                let is_glob = t.contains('*') && capture_ref(&Token::new(t.as_ref())).is_none();
+                is_glob.then(|| Wildcard::Glob(t.into(), false))
+            },
+        }
+    }
+
+    // Whether the wildcard selects the given child, whose token is `t`; `idx` is the index of the
+    // child if its parent is an array, and None if its parent is an object.
+    fn selects(&self, t: &Token, idx: Option<usize>, v: &Value) -> bool {
+        match self {
+            Wildcard::Glob(pattern, ignore_case) => idx.is_none() && glob(pattern, &literal(t).decoded(), *ignore_case),
+            Wildcard::Slice(start, end) => {
+                idx.is_some_and(|i| start.is_none_or(|s| i >= s) && end.is_none_or(|e| i < e))
+            },
+            Wildcard::Filter(key, expected) => match v.get(key) {
+                Some(Value::String(s)) => s == expected,
+                Some(other) => serde_json::from_str::<Value>(expected).is_ok_and(|e| e == *other),
+                None => false,
+            },
+            _ => true,
+        }
+    }
+}
+
+// Whether the key matches the glob pattern, in which `*` matches any sequence of characters
+pub(crate) fn glob(pattern: &str, key: &str, ignore_case: bool) -> bool {
+    if ignore_case {
+        return glob(&pattern.to_lowercase(), &key.to_lowercase(), false);
+    }
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = key.strip_prefix(first) else {
+        return false;
+    };
+    let Some(last) = parts.next_back() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+// How a single token of a path is interpreted: either as a wildcard, or as a literal key (or array
+// index) in the document, with any escaping backslash removed.
+enum ExtToken<'t> {
+    Wildcard(Wildcard),
+    Key(Token<'t>),
+}
+
+impl<'t> ExtToken<'t> {
+    fn parse(t: &Token<'t>) -> ExtToken<'t> {
+        match Wildcard::parse(t) {
+            Some(wildcard) => ExtToken::Wildcard(wildcard),
+            None => ExtToken::Key(literal(t)),
+        }
+    }
+}
+
+// The key (or index) that a non-wildcard token references in the document; a leading backslash
+// escapes the rest of the token, so it's only cloned when there's something to strip.
+pub(crate) fn literal<'t>(t: &Token<'t>) -> Token<'t> {
+    match t.decoded().strip_prefix('\\') {
+        Some(key) => Token::new(key.to_string()),
+        None => t.clone(),
+    }
+}
+
+// The inverse of `literal`: the token that references a key from the document, escaped if the key
+// would otherwise be mistaken for a wildcard (or starts with a backslash itself)
+pub(crate) fn key_token(key: &str) -> Token<'_> {
+    let t = Token::new(key);
+    if key.starts_with('\\') || Wildcard::parse(&t).is_some() || capture_ref(&t).is_some() {
+        Token::new(format!("\\{key}"))
+    } else {
+        Token::new(key)
+    }
+}
+
+// Strip the escapes from a path that doesn't contain any wildcards, so that it can be passed to
+// jsonptr or json_patch directly
+pub(crate) fn unescape_path(path: &Pointer) -> PointerBuf {
+    PointerBuf::from_tokens(path.tokens().map(|t| literal(&t).into_owned()))
+}
+
+// Remove the key from the object; with the `preserve_order` feature, the remaining keys stay in
+// their original order, instead of the last one taking the removed key's place
+pub(crate) fn remove_key(map: &mut Map<String, Value>, key: &str) -> Option<Value> {
+    #[cfg(feature = "preserve_order")]
+    return map.shift_remove(key);
+    #[cfg(not(feature = "preserve_order"))]
+    map.remove(key)
+}
+
+// Resolve a path that doesn't contain any wildcards, honoring escaped tokens
+pub(crate) fn resolve_ext<'a>(value: &'a Value, path: &Pointer) -> Option<&'a Value> {
+    descend(value, &segments(path))
+}
+
+pub(crate) fn resolve_ext_mut<'a>(value: &'a mut Value, path: &Pointer) -> Option<&'a mut Value> {
+    descend_mut(value, &segments(path))
+}
+
+// A single token of a path, along with how it's interpreted; paths are split into segments once up
+// front, so that walking the document doesn't need to re-parse the rest of the path at every level.
+type Segment<'p> = (Token<'p>, ExtToken<'p>);
+
+fn segments(path: &Pointer) -> Vec<Segment<'_>> {
+    path.tokens()
+        .map(|t| {
+            let ext = ExtToken::parse(&t);
+            (t, ext)
+        })
+        .collect()
+}
+
+// Split the segments at the first wildcard, returning the head (everything before the wildcard),
+// and the wildcard and the tail (everything after the wildcard), if there is one
+fn split_segments<'s, 'p>(segs: &'s [Segment<'p>]) -> (&'s [Segment<'p>], Option<(&'s Wildcard, &'s [Segment<'p>])>) {
+    let i = segs.iter().position(|(_, e)| matches!(e, ExtToken::Wildcard(_))).unwrap_or(segs.len());
+    let (head, rest) = segs.split_at(i);
+    match rest.split_first() {
+        Some(((_, ExtToken::Wildcard(wildcard)), tail)) => (head, Some((wildcard, tail))),
+        _ => (head, None),
+    }
+}
+
+fn join(prefix: &Pointer, segs: &[Segment]) -> PointerBuf {
+    let mut path = prefix.to_buf();
+    for (t, _) in segs {
+        path.push_back(t.clone());
+    }
+    path
+}
+
+// Follow the (non-wildcard) segments down from `value`, the same way that Pointer::resolve does
+fn descend<'a>(value: &'a Value, segs: &[Segment]) -> Option<&'a Value> {
+    segs.iter().try_fold(value, |v, (_, e)| match (v, e) {
+        (Value::Object(map), ExtToken::Key(k)) => map.get(k.decoded().as_ref()),
+        (Value::Array(vec), ExtToken::Key(k)) => vec.get(array_index(k)?),
+        _ => None,
+    })
+}
+
+fn descend_mut<'a>(value: &'a mut Value, segs: &[Segment]) -> Option<&'a mut Value> {
+    segs.iter().try_fold(value, |v, (_, e)| match (v, e) {
+        (Value::Object(map), ExtToken::Key(k)) => map.get_mut(k.decoded().as_ref()),
+        (Value::Array(vec), ExtToken::Key(k)) => vec.get_mut(array_index(k)?),
+        _ => None,
+    })
+}
+
+pub(crate) fn array_index(t: &Token) -> Option<usize> {
+    match t.to_index().ok()? {
+        Index::Num(idx) => Some(idx),
+        Index::Next => None,
+    }
+}
+
+// Check that the wildcards in the operation are somewhere we know how to handle them: add
+// operations can't end with a wildcard (other than `**`), and the paths in move and copy
+// operations must contain the same number of wildcards (or numbered references to them).
+pub(crate) fn check_wildcards(op: &PatchOperation) -> Result<(), PatchError> {
+    let bad_path = match op {
+        PatchOperation::Add(AddOperation { path, .. }) => ends_with_wildcard(path).then_some(path),
+        PatchOperation::Move(MoveOperation { from, path }) | PatchOperation::Copy(CopyOperation { from, path }) => {
+            (!is_aligned(from, path)).then_some(path)
+        },
+        PatchOperation::Remove(_) | PatchOperation::Replace(_) | PatchOperation::Test(_) => None,
+    };
+
+    match bad_path {
+        Some(path) => Err(PatchError::new(PatchErrorKind::InvalidWildcard, path).with_operation(op.kind())),
+        None => Ok(()),
+    }
+}
+
+// Whether the last token of the path is a (non-recursive) wildcard, which an add can't end with
+pub(crate) fn ends_with_wildcard(path: &Pointer) -> bool {
+    path.back().is_some_and(|t| {
+        matches!(
+            Wildcard::parse(&t),
+            Some(Wildcard::All | Wildcard::Filter(..) | Wildcard::Slice(..) | Wildcard::Glob(..))
+        )
+    })
+}
+
+fn is_recursive(path: &Pointer) -> bool {
+    path.tokens().any(|t| matches!(Wildcard::parse(&t), Some(Wildcard::Recursive)))
+}
+
+// Recursive wildcards can match overlapping parts of the document (e.g., `/**/foo` matches both
+// `/foo` and `/foo/foo`), so we can't hand out mutable references to all the matches at once the
+// way that patch_ext_helper does.  Instead we compute all of the (existing) concrete paths that
+// match, and return them in reverse order, so that children are modified before their parents and
+// removing an array element doesn't shift the indices of the elements we haven't visited yet.
+fn recursive_targets(obj: &Value, path: &Pointer, opts: &PatchOptions) -> Result<Vec<PointerBuf>, PatchError> {
+    Ok(bounded_matches(path, obj, opts)?.into_iter().rev().map(|(p, _)| p).collect())
 }
 
 // Given JSON pointer, recursively walk through all the possible "end" values that the path
-// references; return a mutable reference so we can make modifications at those points.
+// references; return a mutable reference so we can make modifications at those points, along with
+// the concrete path to each of them.
 fn patch_ext_helper<'a>(
     path: &Pointer,
     value: &'a mut Value,
     mode: PatchMode,
-) -> Result<Vec<&'a mut Value>, PatchError> {
-    let Some(idx) = path.as_str().find("/*") else {
-        if path.resolve(value).is_err() {
-            match mode {
-                PatchMode::Error => return Err(PatchError::TargetDoesNotExist(path.as_str().into())),
-                PatchMode::Create => {
-                    path.assign(value, json!({}))?;
-                },
-                PatchMode::Skip => return Ok(vec![]),
-            }
+    opts: &PatchOptions,
+) -> Result<Vec<(PointerBuf, &'a mut Value)>, PatchError> {
+    let mut res = vec![];
+    collect_targets(Pointer::root(), &segments(path), value, mode, opts, &mut res)?;
+    Ok(res)
+}
+
+// Push all of the values under `value` that the segments reference onto `res`, along with their
+// concrete paths (relative to the document root); `prefix` is the path to `value`.
+fn collect_targets<'a>(
+    prefix: &Pointer,
+    segs: &[Segment],
+    value: &'a mut Value,
+    mode: PatchMode,
+    opts: &PatchOptions,
+    res: &mut Vec<(PointerBuf, &'a mut Value)>,
+) -> Result<(), PatchError> {
+    let (head, wildcard) = split_segments(segs);
+    let (head_path, head_val) = if descend(value, head).is_some() {
+        // we just checked that the head exists, so the unwrap is safe
+        (join(prefix, head), descend_mut(value, head).unwrap())
+    } else {
+        let missing = join(Pointer::root(), head);
+        match create_missing(value, prefix, &missing, wildcard.is_none(), mode, opts)
+            .map_err(|err| err.with_prefix(prefix))?
+        {
+            Some((path, v)) => (prefix.concat(&path), v),
+            None => return Ok(()),
         }
-        return Ok(vec![path.resolve_mut(value)?]);
     };
 
-    // we checked the index above so unwrap is safe here
-    let (head, cons) = path.split_at(idx).unwrap();
-    let mut res = vec![];
+    let Some((wildcard, tail)) = wildcard else {
+        check_max_matches(res.len() + 1, &head_path, opts)?;
+        res.push((head_path, head_val));
+        return Ok(());
+    };
+
+    // Head now points at what we believe is an array or an object; if not, it's an error.
+    let children: Vec<(Token, Option<usize>, &mut Value)> = match head_val {
+        Value::Array(vec) => vec.iter_mut().enumerate().map(|(i, v)| (Token::from(i), Some(i), v)).collect(),
+        Value::Object(map) => map.iter_mut().map(|(k, v)| (key_token(k), None, v)).collect(),
+        _ => return Err(PatchError::new(PatchErrorKind::UnexpectedType, &head_path)),
+    };
+    if children.is_empty() && opts.wildcard_on_empty_array_is_error {
+        return Err(PatchError::new(PatchErrorKind::TargetDoesNotExist, &head_path));
+    }
+
+    // Iterate over all the selected child values and recurse, collecting all found values; recursive
+    // wildcards are expanded into concrete paths before we get here.
+    debug_assert!(!matches!(wildcard, Wildcard::Recursive));
+    let selected: Vec<_> = children.into_iter().filter(|(t, i, v)| wildcard.selects(t, *i, v)).collect();
+    trace_event!(trace, path = %head_path, matched = selected.len(), "expanded wildcard");
+    for (t, _, v) in selected {
+        let child_path = head_path.with_trailing_token(t);
+        collect_targets(&child_path, tail, v, mode, opts, res)?;
+    }
+    Ok(())
+}
+
+// Handle a path that doesn't exist in the document according to the mode: return an error, skip it
+// (returning None), or create it, returning the concrete path to (and a reference to) the new
+// value. The last element of the path is created as an empty object if `is_leaf`, and as an empty
+// array otherwise (i.e., if the path is followed by a wildcard).  This is a little weird in the
+// latter case, since we won't iterate over the empty array at all, but I think that's
+// "approximately" fine and less surprising than not creating anything.  `prefix` is the path to
+// `value`, for looking up container hints.
+fn create_missing<'a>(
+    value: &'a mut Value,
+    prefix: &Pointer,
+    path: &Pointer,
+    is_leaf: bool,
+    mode: PatchMode,
+    opts: &PatchOptions,
+) -> Result<Option<(PointerBuf, &'a mut Value)>, PatchError> {
+    if let (PatchMode::Create | PatchMode::CreateArray, Some(max)) = (mode, opts.max_autocreate_depth)
+        && missing_count(value, path) > max
+    {
+        return Err(PatchError::new(PatchErrorKind::AutocreateLimitExceeded(max), path));
+    }
+
+    if matches!(mode, PatchMode::Skip) {
+        trace_event!(trace, path = %prefix.concat(path), "path not found");
+    } else if !matches!(mode, PatchMode::Error) {
+        trace_event!(debug, path = %prefix.concat(path), "created missing parents");
+    }
+
+    let leaf = if is_leaf { json!({}) } else { json!([]) };
+    let hint = |rel: &Pointer| container_hint(opts, &prefix.concat(rel));
+    match mode {
+        PatchMode::Error => Err(PatchError::new(PatchErrorKind::TargetDoesNotExist, path)),
+        PatchMode::Skip => Ok(None),
+        PatchMode::CreateArray if is_leaf => create_path(value, path, json!([]), hint, opts).map(Some),
+        // If the path appends to an array, the new element's index is only known once it's created
+        _ if appends(path)
+            || (is_leaf && opts.autocreate_arrays_for_numeric_tokens)
+            || !opts.container_hints.is_empty() =>
+        {
+            create_path(value, path, leaf, hint, opts).map(Some)
+        },
+        _ => {
+            let literal_path = unescape_path(path);
+            literal_path.assign(value, leaf).map_err(PatchError::at(path))?;
+            Ok(Some((path.to_buf(), literal_path.resolve_mut(value).map_err(PatchError::at(path))?)))
+        },
+    }
+}
+
+// Create all of the missing elements of the path, returning the concrete path to (and a reference
+// to) the element at the end; each missing parent is created as an array if the token that follows
+// it is an array index (or `-`), and as an object otherwise, and the element at the end of the path
+// is created as `leaf`, unless the hint (given the path to the element) says otherwise.
+fn create_path<'a>(
+    obj: &'a mut Value,
+    path: &Pointer,
+    leaf: Value,
+    hint: impl Fn(&Pointer) -> Option<ContainerKind>,
+    opts: &PatchOptions,
+) -> Result<(PointerBuf, &'a mut Value), PatchError> {
+    let tokens: Vec<Token> = path.tokens().collect();
+    let mut cur = obj;
+    let mut cur_path = PointerBuf::new();
+    for (i, t) in tokens.iter().enumerate() {
+        let child = match (hint(&cur_path.with_trailing_token(t.clone())), tokens.get(i + 1)) {
+            (Some(ContainerKind::Array), _) => json!([]),
+            (Some(ContainerKind::Object), _) => json!({}),
+            (None, Some(next)) if is_index(next) => json!([]),
+            (None, Some(_)) => json!({}),
+            (None, None) => leaf.clone(),
+        };
+        cur = match cur {
+            Value::Object(map) => {
+                cur_path.push_back(t.clone());
+                map.entry(literal(t).decoded()).or_insert(child)
+            },
+            Value::Array(vec) => {
+                let idx =
+                    match literal(t).to_index().map_err(PatchError::at(&cur_path.with_trailing_token(t.clone())))? {
+                        Index::Num(idx) => idx,
+                        Index::Next => vec.len(),
+                    };
+                if idx == vec.len() {
+                    check_max_index(idx, &cur_path, opts)?;
+                    vec.push(child);
+                }
+                let Some(v) = vec.get_mut(idx) else {
+                    return Err(PatchError::new(PatchErrorKind::OutOfBounds(idx), &cur_path));
+                };
+                cur_path.push_back(idx);
+                v
+            },
+            _ => return Err(PatchError::new(PatchErrorKind::UnexpectedType, &cur_path)),
+        };
+    }
+    Ok((cur_path, cur))
+}
+
+fn is_index(t: &Token) -> bool {
+    t.to_index().is_ok()
+}
+
+// The container kind hinted for the (concrete) path in the options, if any
+fn container_hint(opts: &PatchOptions, path: &Pointer) -> Option<ContainerKind> {
+    if opts.container_hints.is_empty() {
+        return None;
+    }
+    let tokens: Vec<Token> = path.tokens().collect();
+    opts.container_hints
+        .iter()
+        .find(|(pattern, _)| captures(&pattern.tokens().collect::<Vec<_>>(), &tokens).is_some())
+        .map(|(_, kind)| *kind)
+}
+
+// The number of elements at the end of the path that don't exist in the value
+fn missing_count(value: &Value, path: &Pointer) -> usize {
+    let mut cur = PointerBuf::new();
+    for t in path.tokens() {
+        cur.push_back(t);
+        if resolve_ext(value, &cur).is_none() {
+            return path.count() - cur.count() + 1;
+        }
+    }
+    0
+}
+
+// Whether any segment of the path is `-`, i.e., whether creating it appends a new array element
+fn appends(path: &Pointer) -> bool {
+    path.tokens().any(|t| matches!(t.to_index(), Ok(Index::Next)))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeMap;
+
+    use assertables::*;
+    use rstest::*;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+
+    #[fixture]
+    fn data() -> Value {
+        json!({
+            "foo": [
+                {"baz": {"buzz": 0}},
+                {"baz": {"quzz": 1}},
+                {"baz": {"fixx": 2}},
+            ],
+        })
+    }
+
+    #[rstest]
+    fn test_try_operations() {
+        assert_eq!(assert_ok!(try_add_operation("/foo/*", json!(1))), add_operation(format_ptr!("/foo/*"), json!(1)));
+        assert_eq!(assert_ok!(try_remove_operation("/foo")), remove_operation(format_ptr!("/foo")));
+        assert_eq!(
+            assert_ok!(try_move_operation("/foo", "/bar")),
+            move_operation(format_ptr!("/foo"), format_ptr!("/bar"))
+        );
+    }
+
+    #[rstest]
+    fn test_serialized_operations() {
+        #[derive(Serialize)]
+        struct Port {
+            name: &'static str,
+            port: u16,
+        }
+
+        let op = assert_ok!(add_serialized_operation(format_ptr!("/ports/-"), Port { name: "http", port: 80 }));
+        assert_eq!(op, add_operation(format_ptr!("/ports/-"), json!({"name": "http", "port": 80})));
+        let op = assert_ok!(try_replace_operation("/replicas", 3));
+        assert_eq!(op, replace_operation(format_ptr!("/replicas"), json!(3)));
+        let op = assert_ok!(try_test_operation("/labels", [("app", "foo")].into_iter().collect::<BTreeMap<_, _>>()));
+        assert_eq!(op, test_operation(format_ptr!("/labels"), json!({"app": "foo"})));
+
+        // Maps with non-string keys can't be represented in JSON
+        let err = assert_err!(test_serialized_operation(format_ptr!("/foo"), BTreeMap::from([((1, 2), 3)])));
+        assert_matches!(err.kind(), PatchErrorKind::SerializeError(_));
+        assert_eq!(err.path(), format_ptr!("/foo"));
+        assert_eq!(err.operation(), Some(OperationKind::Test));
+    }
+
+    #[rstest]
+    #[case(try_add_operation("foo", json!(1)), OperationKind::Add)]
+    #[case(try_copy_operation("/foo", "bar"), OperationKind::Copy)]
+    #[case(try_move_operation("/foo~", "/bar"), OperationKind::Move)]
+    #[case(try_remove_operation("foo"), OperationKind::Remove)]
+    #[case(try_replace_operation("foo", json!(1)), OperationKind::Replace)]
+    #[case(try_test_operation("foo", json!(1)), OperationKind::Test)]
+    fn test_try_operations_err(#[case] res: Result<PatchOperation, PatchError>, #[case] kind: OperationKind) {
+        let err = assert_err!(res);
+        assert_matches!(err.kind(), PatchErrorKind::ParseError(_));
+        assert_eq!(err.operation(), Some(kind));
+    }
 
-    // This is a little weird; if mode == Create, and the subpath up to this point doesn't exist,
-    // we'll create an empty array which we won't iterate over at all.  I think that's
-    // "approximately" fine and less surprising that not creating anything.
-    if head.resolve(value).is_err() {
-        match mode {
-            PatchMode::Error => return Err(PatchError::TargetDoesNotExist(path.as_str().into())),
-            PatchMode::Create => {
-                path.assign(value, json!([]))?;
-            },
-            PatchMode::Skip => return Ok(vec![]),
+    #[rstest]
+    fn test_resolve_matches(data: Value) {
+        let m = resolve_matches(format_ptr!("/foo/:2/baz/*").as_ptr(), &data);
+        assert_eq!(m, vec![(&json!(0), format_ptr!("/foo/0/baz/buzz")), (&json!(1), format_ptr!("/foo/1/baz/quzz"))]);
+    }
+
+    #[rstest]
+    fn test_resolve_matches_mut(mut data: Value) {
+        let m = assert_ok!(resolve_matches_mut(&format_ptr!("/foo/*/baz/*"), &mut data));
+        let paths: Vec<_> = m.iter().map(|(_, p)| p.to_string()).collect();
+        assert_eq!(paths, vec!["/foo/0/baz/buzz", "/foo/1/baz/quzz", "/foo/2/baz/fixx"]);
+        for (v, _) in m {
+            *v = json!(v.as_i64().unwrap() + 10);
         }
+        assert_eq!(data["foo"][2], json!({"baz": {"fixx": 12}}));
+
+        let m = assert_ok!(resolve_matches_mut(&format_ptr!("/foo/*/bar"), &mut data));
+        assert_is_empty!(m);
+
+        let err = assert_err!(resolve_matches_mut(&format_ptr!("/**/baz"), &mut data));
+        assert_matches!(err.kind(), PatchErrorKind::InvalidWildcard);
     }
 
-    // Head now points at what we believe is an array; if not, it's an error.
-    let next_array_val =
-        head.resolve_mut(value)?.as_array_mut().ok_or(PatchError::UnexpectedType(head.as_str().into()))?;
+    #[rstest]
+    #[case::concrete(format_ptr!("/foo/1/baz"), vec![json!({"quzz": 1})])]
+    #[case::wildcard(format_ptr!("/foo/*/baz/*"), vec![json!(0), json!(1), json!(2)])]
+    #[case::no_matches(format_ptr!("/foo/*/bar"), vec![])]
+    fn test_get_all(data: Value, #[case] path: PointerBuf, #[case] expected: Vec<Value>) {
+        let found = assert_ok!(get_all(&data, &path));
+        assert_eq!(found, expected.iter().collect::<Vec<_>>());
+    }
 
-    // Iterate over all the array values and recurse, returning all found values
-    for v in next_array_val {
-        // The cons pointer either looks like /* or /*/something, so we need to split_front
-        // to get the array marker out, and either return the current value if there's nothing
-        // else, or recurse and return all the found values
-        if let Some((_, c)) = cons.split_front() {
-            res.extend(patch_ext_helper(c, v, mode)?);
-        } else {
-            unreachable!("cons can't be root");
-        }
+    #[rstest]
+    fn test_get_first(data: Value) {
+        assert_eq!(assert_ok!(get_first(&data, &format_ptr!("/foo/1:/baz"))), &json!({"quzz": 1}));
+        assert_eq!(assert_ok!(get_first(&data, &format_ptr!("/**/fixx"))), &json!(2));
+
+        let err = assert_err!(get_first(&data, &format_ptr!("/foo/*/bar")));
+        assert_matches!(err.kind(), PatchErrorKind::TargetDoesNotExist);
+        let err = assert_err!(get_all(&data, &format_ptr!("/foo/3")));
+        assert_matches!(err.kind(), PatchErrorKind::TargetDoesNotExist);
+        assert_eq!(err.path(), format_ptr!("/foo/3"));
     }
-    Ok(res)
-}
 
-#[cfg(test)]
-mod tests {
-    use assertables::*;
-    use rstest::*;
+    #[rstest]
+    #[case::concrete(format_ptr!("/foo/2"), vec![json!({"baz": {"fixx": 2}})])]
+    #[case::wildcard(format_ptr!("/foo/*/baz/*"), vec![json!(0), json!(1), json!(2)])]
+    #[case::slice(format_ptr!("/foo/1:"), vec![json!({"baz": {"quzz": 1}}), json!({"baz": {"fixx": 2}})])]
+    #[case::recursive(format_ptr!("/**/fixx"), vec![json!(2)])]
+    #[case::no_matches(format_ptr!("/foo/*/bar"), vec![])]
+    fn test_remove_ext(mut data: Value, #[case] path: PointerBuf, #[case] expected: Vec<Value>) {
+        let removed = assert_ok!(remove_ext(&mut data, &path));
+        assert_eq!(removed, expected);
+        assert_is_empty!(matches(&path, &data));
+    }
 
-    use super::*;
-    use crate as json_patch_ext; // make the macros work in the tests
+    #[rstest]
+    fn test_remove_ext_root(mut data: Value) {
+        let removed = assert_ok!(remove_ext(&mut data, Pointer::root()));
+        assert_eq!(removed, vec![json!({"foo": [{"baz": {"buzz": 0}}, {"baz": {"quzz": 1}}, {"baz": {"fixx": 2}}]})]);
+        assert_eq!(data, Value::Null);
 
-    #[fixture]
-    fn data() -> Value {
-        json!({
-            "foo": [
-                {"baz": {"buzz": 0}},
-                {"baz": {"quzz": 1}},
-                {"baz": {"fixx": 2}},
-            ],
-        })
+        let removed = assert_ok!(remove_ext(&mut json!({}), &format_ptr!("/missing")));
+        assert_is_empty!(removed);
     }
 
     #[rstest]
@@ -354,7 +2051,115 @@ mod tests {
     }
 
     #[rstest]
-    #[case(format_ptr!("/*"))]
+    fn test_matches_object() {
+        let path = format_ptr!("/foo/*/baz");
+        let data = json!({"foo": {"a": {"baz": 1}, "b/c": {"baz": 2}, "d": {}}});
+        let m: Vec<_> = matches(&path, &data).iter().map(|(p, v)| (p.clone(), (*v).clone())).collect();
+        assert_eq!(m, vec![(format_ptr!("/foo/a/baz"), json!(1)), (format_ptr!("/foo/b~1c/baz"), json!(2))]);
+    }
+
+    #[rstest]
+    fn test_matches_recursive() {
+        let path = format_ptr!("/spec/**/image");
+        let data = json!({
+            "spec": {
+                "image": "a",
+                "containers": [{"image": "b"}, {"name": "c"}],
+                "init": {"containers": {"x": {"image": "d"}}},
+            },
+            "image": "e",
+        });
+        let m: Vec<_> = matches(&path, &data).iter().map(|(p, v)| (p.clone(), (*v).clone())).collect();
+        assert_eq!(
+            m,
+            vec![
+                (format_ptr!("/spec/image"), json!("a")),
+                (format_ptr!("/spec/containers/0/image"), json!("b")),
+                (format_ptr!("/spec/init/containers/x/image"), json!("d")),
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_matches_recursive_nested(data: Value) {
+        let path = format_ptr!("/**/baz/*");
+        let m: Vec<_> = matches(&path, &data).iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(
+            m,
+            vec![format_ptr!("/foo/0/baz/buzz"), format_ptr!("/foo/1/baz/quzz"), format_ptr!("/foo/2/baz/fixx")]
+        );
+    }
+
+    #[rstest]
+    #[case(format_ptr!("/containers/[?name=sidecar]/image"), vec![format_ptr!("/containers/1/image")])]
+    #[case(format_ptr!("/containers/[?replicas=3]/name"), vec![format_ptr!("/containers/0/name")])]
+    #[case(format_ptr!("/containers/[?name=missing]/image"), vec![])]
+    fn test_matches_filter(#[case] path: PointerBuf, #[case] expected: Vec<PointerBuf>) {
+        let data = json!({"containers": [
+            {"name": "app", "image": "a", "replicas": 3},
+            {"name": "sidecar", "image": "b"},
+        ]});
+        let m: Vec<_> = matches(&path, &data).iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(m, expected);
+    }
+
+    #[rstest]
+    #[case(format_ptr!("/foo/1:3/baz"), vec![format_ptr!("/foo/1/baz"), format_ptr!("/foo/2/baz")])]
+    #[case(format_ptr!("/foo/:1/baz"), vec![format_ptr!("/foo/0/baz")])]
+    #[case(format_ptr!("/foo/2:"), vec![format_ptr!("/foo/2")])]
+    #[case(format_ptr!("/foo/:"), vec![format_ptr!("/foo/0"), format_ptr!("/foo/1"), format_ptr!("/foo/2")])]
+    #[case(format_ptr!("/foo/5:7"), vec![])]
+    #[case(format_ptr!("/foo/0/0:1"), vec![])]
+    fn test_matches_slice(data: Value, #[case] path: PointerBuf, #[case] expected: Vec<PointerBuf>) {
+        let m: Vec<_> = matches(&path, &data).iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(m, expected);
+    }
+
+    #[rstest]
+    #[case::prefix(format_ptr!("/labels/example.com~1*"), vec!["example.com~1a", "example.com~1b"])]
+    #[case::suffix(format_ptr!("/labels/*~1a"), vec!["example.com~1a", "other.io~1a"])]
+    #[case::infix(format_ptr!("/labels/e*m~1*"), vec!["example.com~1a", "example.com~1b"])]
+    #[case::ignore_case(format_ptr!("/labels/(?i)APP"), vec!["App", "app"])]
+    #[case::ignore_case_glob(format_ptr!("/labels/(?i)EXAMPLE.*a"), vec!["example.com~1a"])]
+    #[case::no_match(format_ptr!("/labels/x*"), vec![])]
+    #[case::array(format_ptr!("/list/*0"), vec![])]
+    fn test_matches_glob(#[case] path: PointerBuf, #[case] expected: Vec<&str>) {
+        let data = json!({
+            "labels": {"example.com/a": 1, "example.com/b": 2, "other.io/a": 3, "App": 4, "app": 5},
+            "list": [10, 20],
+        });
+        let m: Vec<_> = matches(&path, &data).iter().map(|(p, _)| p.clone()).collect();
+        let expected: Vec<_> = expected.into_iter().map(|k| format_ptr!("/labels/{k}")).collect();
+        assert_eq!(m, expected);
+    }
+
+    #[rstest]
+    #[case::empty("", "", true)]
+    #[case::exact("abc", "abc", true)]
+    #[case::star("*", "", true)]
+    #[case::prefix("ab*", "abc", true)]
+    #[case::overlap("a*a", "a", false)]
+    #[case::middle("a*b*c", "axxbyyc", true)]
+    #[case::middle_missing("a*b*c", "axxc", false)]
+    #[case::case_sensitive("A*", "abc", false)]
+    fn test_glob(#[case] pattern: &str, #[case] key: &str, #[case] expected: bool) {
+        assert_eq!(glob(pattern, key, false), expected);
+    }
+
+    #[rstest]
+    fn test_patch_ext_remove_glob() {
+        let mut data = json!({"metadata": {"annotations": {"example.com/a": "1", "example.com/b": "2", "keep": "3"}}});
+        assert_ok!(patch_ext(&mut data, remove_operation(format_ptr!("/metadata/annotations/example.com~1*"))));
+        assert_eq!(data, json!({"metadata": {"annotations": {"keep": "3"}}}));
+
+        // A literal key containing `*` can still be referenced by escaping it
+        let mut data = json!({"foo*": 1, "foobar": 2});
+        assert_ok!(patch_ext(&mut data, remove_operation(format_ptr!("/\\foo*"))));
+        assert_eq!(data, json!({"foobar": 2}));
+    }
+
+    #[rstest]
+    #[case(format_ptr!("/foo/*/qux"))]
     #[case(format_ptr!("/food"))]
     #[case(format_ptr!("/foo/3/baz"))]
     #[case(format_ptr!("/foo/bar/baz"))]
@@ -381,6 +2186,55 @@ mod tests {
         );
     }
 
+    #[rstest]
+    fn test_patch_ext_add_object_wildcard() {
+        let path = format_ptr!("/containers/*/image");
+        let mut data = json!({"containers": {"app": {"image": "foo"}, "sidecar": {}}});
+        let res = patch_ext(&mut data, add_operation(path, json!("bar")));
+        assert_ok!(res);
+        assert_eq!(data, json!({"containers": {"app": {"image": "bar"}, "sidecar": {"image": "bar"}}}));
+    }
+
+    #[rstest]
+    fn test_patch_ext_add_wildcard_err() {
+        let path = format_ptr!("/containers/*/image");
+        let mut data = json!({"containers": "foo"});
+        let res = patch_ext(&mut data, add_operation(path, json!("bar")));
+        assert_err!(res);
+    }
+
+    #[rstest]
+    #[case::all(format_ptr!("/foo/*"))]
+    #[case::glob(format_ptr!("/foo/a*"))]
+    #[case::filter(format_ptr!("/foo/[?a=1]"))]
+    #[case::slice(format_ptr!("/foo/0:1"))]
+    fn test_patch_ext_add_trailing_wildcard(#[case] path: PointerBuf) {
+        let mut data = json!({"foo": {"a": 1}});
+        let err = assert_err!(patch_ext(&mut data, add_operation(path, json!(2))));
+        assert_matches!(err.kind(), PatchErrorKind::InvalidWildcard);
+        assert_eq!(data, json!({"foo": {"a": 1}}));
+
+        // An escaped wildcard is just a key
+        assert_ok!(patch_ext(&mut data, add_operation(format_ptr!("/foo/\\*"), json!(2))));
+        assert_eq!(data, json!({"foo": {"a": 1, "*": 2}}));
+    }
+
+    #[rstest]
+    fn test_patch_ext_add_recursive() {
+        let path = format_ptr!("/spec/**/image");
+        let mut data = json!({"spec": {"containers": [{"image": "a"}, {"name": "b"}], "image": {"image": "c"}}});
+        let res = patch_ext(&mut data, add_operation(path, json!("foo")));
+        assert_ok!(res);
+        assert_eq!(data, json!({"spec": {"containers": [{"image": "foo"}, {"name": "b"}], "image": "foo"}}));
+    }
+
+    #[rstest]
+    fn test_patch_ext_add_root(mut data: Value) {
+        let res = patch_ext(&mut data, add_operation(format_ptr!(""), json!([1, 2])));
+        assert_ok!(res);
+        assert_eq!(data, json!([1, 2]));
+    }
+
     #[rstest]
     fn test_patch_ext_add_vec1(mut data: Value) {
         let path = format_ptr!("/foo/1");
@@ -417,6 +2271,39 @@ mod tests {
         );
     }
 
+    #[rstest]
+    fn test_patch_ext_add_vec_end(mut data: Value) {
+        let res = patch_ext_traced(&mut data, add_operation(format_ptr!("/foo/3"), json!(42)));
+        assert_eq!(assert_ok!(res), vec![format_ptr!("/foo/3")]);
+        assert_eq!(data["foo"][3], json!(42));
+
+        let res = patch_ext(&mut data, add_operation(format_ptr!("/foo/5"), json!(42)));
+        assert_err!(res);
+    }
+
+    #[rstest]
+    #[case::existing(json!({"foo": [1]}), format_ptr!("/foo/-/bar"), json!({"foo": [1, {"bar": 42}]}))]
+    #[case::missing(json!({}), format_ptr!("/foo/-/bar"), json!({"foo": [{"bar": 42}]}))]
+    #[case::nested(json!({"foo": [[]]}), format_ptr!("/foo/0/-/bar"), json!({"foo": [[{"bar": 42}]]}))]
+    #[case::wildcard(
+        json!({"foo": [{}, {}]}),
+        format_ptr!("/foo/*/x/-/y"),
+        json!({"foo": [{"x": [{"y": 42}]}, {"x": [{"y": 42}]}]}),
+    )]
+    #[case::wildcard_after(json!({}), format_ptr!("/foo/-/bar/*/baz"), json!({"foo": [{"bar": []}]}))]
+    fn test_patch_ext_add_append_parent(#[case] mut data: Value, #[case] path: PointerBuf, #[case] expected: Value) {
+        let res = patch_ext_traced(&mut data, add_operation(path, json!(42)));
+        assert_ok!(res);
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    fn test_patch_ext_add_append_parent_traced(mut data: Value) {
+        let res = patch_ext_traced(&mut data, add_operation(format_ptr!("/foo/-/baz"), json!(42)));
+        assert_eq!(assert_ok!(res), vec![format_ptr!("/foo/3/baz")]);
+        assert_eq!(data["foo"][3], json!({"baz": 42}));
+    }
+
     #[rstest]
     fn test_patch_ext_add_vec_err(mut data: Value) {
         let path = format_ptr!("/foo/a");
@@ -485,6 +2372,62 @@ mod tests {
         );
     }
 
+    #[rstest]
+    fn test_patch_ext_replace_recursive(mut data: Value) {
+        let path = format_ptr!("/**/quzz");
+        let res = patch_ext(&mut data, replace_operation(path, json!(42)));
+        assert_ok!(res);
+        assert_eq!(
+            data,
+            json!({
+                "foo": [
+                    {"baz": {"buzz": 0}},
+                    {"baz": {"quzz": 42}},
+                    {"baz": {"fixx": 2}},
+                ],
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_patch_ext_replace_filter() {
+        let path = format_ptr!("/containers/[?name=sidecar]/image");
+        let mut data = json!({"containers": {
+            "a": {"name": "app", "image": "foo"},
+            "b": {"name": "sidecar", "image": "foo"},
+        }});
+        let res = patch_ext(&mut data, replace_operation(path, json!("bar")));
+        assert_ok!(res);
+        assert_eq!(
+            data,
+            json!({"containers": {
+                "a": {"name": "app", "image": "foo"},
+                "b": {"name": "sidecar", "image": "bar"},
+            }})
+        );
+    }
+
+    #[rstest]
+    #[case::all(format_ptr!("/foo/*"), json!([42, 42, 42]))]
+    #[case::slice(format_ptr!("/foo/1:"), json!([{"baz": {"buzz": 0}}, 42, 42]))]
+    fn test_patch_ext_replace_wildcard_end(mut data: Value, #[case] path: PointerBuf, #[case] expected: Value) {
+        let modified = assert_ok!(patch_ext_traced(&mut data, replace_operation(path, json!(42))));
+        assert_eq!(data["foo"], expected);
+        assert_len_eq_x!(modified, expected.as_array().unwrap().iter().filter(|v| **v == json!(42)).count());
+    }
+
+    #[rstest]
+    fn test_patch_ext_replace_wildcard_end_other() {
+        let mut data = json!({"labels": {"a": "x", "b": "y"}, "names": [{"name": "a"}, {"name": "b"}], "empty": []});
+        assert_ok!(patch_ext(&mut data, replace_operation(format_ptr!("/labels/*"), json!("z"))));
+        assert_ok!(patch_ext(&mut data, replace_operation(format_ptr!("/names/[?name=b]"), json!("c"))));
+        assert_ok!(patch_ext(&mut data, replace_operation(format_ptr!("/empty/*"), json!("z"))));
+        assert_eq!(data, json!({"labels": {"a": "z", "b": "z"}, "names": [{"name": "a"}, "c"], "empty": []}));
+
+        let err = assert_err!(patch_ext(&mut data, replace_operation(format_ptr!("/labels/a/*"), json!("z"))));
+        assert_matches!(err.kind(), PatchErrorKind::UnexpectedType);
+    }
+
     #[rstest]
     fn test_patch_ext_replace_err(mut data: Value) {
         let path = format_ptr!("/foo/*/baz/buzz");
@@ -492,6 +2435,342 @@ mod tests {
         assert_err!(res);
     }
 
+    #[rstest]
+    fn test_patch_ext_all(mut data: Value) {
+        let p = Patch(vec![
+            add_operation(format_ptr!("/foo/*/baz/buzz"), json!(42)),
+            remove_operation(format_ptr!("/foo/1")),
+            move_operation(format_ptr!("/foo/1/baz/fixx"), format_ptr!("/bar")),
+        ]);
+        let res = patch_ext_all(&mut data, &p);
+        assert_ok!(res);
+        assert_eq!(
+            data,
+            json!({
+                "foo": [
+                    {"baz": {"buzz": 42}},
+                    {"baz": {"buzz": 42}},
+                ],
+                "bar": 2,
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(remove_operation(format_ptr!("/foo/5")))]
+    #[case(replace_operation(format_ptr!("/foo/*/baz/quzz"), json!(42)))]
+    #[case(test_operation(format_ptr!("/bar"), json!(1)))]
+    fn test_patch_ext_all_rollback(mut data: Value, #[case] bad_op: PatchOperation) {
+        let orig = data.clone();
+        let p = Patch(vec![
+            add_operation(format_ptr!("/foo/*/baz/buzz"), json!(42)),
+            add_operation(format_ptr!("/bar/baz"), json!(1)),
+            remove_operation(format_ptr!("/foo/0")),
+            bad_op,
+        ]);
+        let res = patch_ext_all(&mut data, &p);
+        assert_err!(res);
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    #[case::same_value(vec![replace_operation(format_ptr!("/foo/0/baz/buzz"), json!(0))], false)]
+    #[case::same_object(vec![add_operation(format_ptr!("/foo/1/baz"), json!({"quzz": 1}))], false)]
+    #[case::same_element(vec![replace_operation(format_ptr!("/foo/-1"), json!({"baz": {"fixx": 2}}))], false)]
+    #[case::same_root(
+        vec![replace_operation(
+            PointerBuf::root(),
+            json!({"foo": [{"baz": {"buzz": 0}}, {"baz": {"quzz": 1}}, {"baz": {"fixx": 2}}]}),
+        )],
+        false,
+    )]
+    #[case::missing_remove(vec![remove_operation(format_ptr!("/foo/*/bar"))], false)]
+    #[case::test(vec![test_operation(format_ptr!("/foo/0/baz/buzz"), json!(0))], false)]
+    #[case::new_value(vec![replace_operation(format_ptr!("/foo/0/baz/buzz"), json!(1))], true)]
+    #[case::insert(vec![add_operation(format_ptr!("/foo/0"), json!({"baz": {"buzz": 0}}))], true)]
+    #[case::some_wildcards(vec![add_operation(format_ptr!("/foo/*/baz/quzz"), json!(1))], true)]
+    fn test_patch_ext_changed(mut data: Value, #[case] ops: Vec<PatchOperation>, #[case] changed: bool) {
+        let orig = data.clone();
+        assert_eq!(assert_ok!(patch_ext_changed(&mut data, &Patch(ops))), changed);
+        assert_eq!(data != orig, changed);
+    }
+
+    #[rstest]
+    fn test_patch_ext_changed_rollback(mut data: Value) {
+        let orig = data.clone();
+        let p = Patch(vec![
+            replace_operation(format_ptr!("/foo/0/baz/buzz"), json!(1)),
+            replace_operation(format_ptr!("/missing"), json!(1)),
+        ]);
+        let err = assert_err!(patch_ext_changed(&mut data, &p));
+        assert_eq!(err.operation_index(), Some(1));
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    #[case::add(
+        add_operation(format_ptr!("/foo/*/baz/buzz"), json!(42)),
+        vec!["/foo/0/baz/buzz", "/foo/1/baz/buzz", "/foo/2/baz/buzz"],
+    )]
+    #[case::add_vec(add_operation(format_ptr!("/foo/-"), json!(42)), vec!["/foo/3"])]
+    #[case::replace(replace_operation(format_ptr!("/**/quzz"), json!(42)), vec!["/foo/1/baz/quzz"])]
+    #[case::remove(remove_operation(format_ptr!("/foo/*/baz/fixx")), vec!["/foo/2/baz/fixx"])]
+    #[case::remove_wildcard(remove_operation(format_ptr!("/foo/*")), vec!["/foo/0", "/foo/1", "/foo/2"])]
+    #[case::remove_missing(remove_operation(format_ptr!("/bar")), vec![])]
+    #[case::move_op(move_operation(format_ptr!("/foo/0"), format_ptr!("/bar")), vec!["/foo/0", "/bar"])]
+    #[case::test_op(test_operation(format_ptr!("/foo/0/baz/buzz"), json!(0)), vec![])]
+    fn test_patch_ext_traced(mut data: Value, #[case] op: PatchOperation, #[case] expected: Vec<&str>) {
+        let paths = patch_ext_traced(&mut data, op).unwrap();
+        assert_eq!(paths, expected.iter().map(|p| PointerBuf::parse(*p).unwrap()).collect::<Vec<_>>());
+    }
+
+    #[rstest]
+    fn test_validate_patch(data: Value) {
+        let orig = data.clone();
+        let p = Patch(vec![
+            add_operation(format_ptr!("/foo/*/baz/buzz"), json!(42)),
+            remove_operation(format_ptr!("/foo/*/baz/quzz")),
+            test_operation(format_ptr!("/foo/1/baz/buzz"), json!(42)),
+            remove_operation(format_ptr!("/bar")),
+            copy_operation(format_ptr!("/foo/0"), format_ptr!("/bar")),
+        ]);
+        let report = validate_patch(&data, &p).unwrap();
+        assert_eq!(report.operations.iter().map(|r| r.modified).collect::<Vec<_>>(), vec![3, 1, 0, 0, 1]);
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_patch_ext_report(mut data: Value) {
+        let p = Patch(vec![
+            add_operation(format_ptr!("/foo/*/baz/new"), json!(42)),
+            remove_operation(format_ptr!("/foo/*/missing")),
+            replace_operation(format_ptr!("/foo/*/baz/buzz"), json!(42)),
+            test_operation(format_ptr!("/foo/0/baz/new"), json!(42)),
+        ]);
+        let report = patch_ext_report(&mut data, &p);
+        assert!(!report.is_ok());
+
+        let outcomes: Vec<_> = report.operations.iter().map(|r| (&r.outcome, r.modified)).collect();
+        assert_matches!(outcomes[0], (OperationOutcome::Applied, 3));
+        assert_matches!(outcomes[1], (OperationOutcome::Skipped, 0));
+        assert_matches!(outcomes[2], (OperationOutcome::Failed(_), 0));
+        assert_matches!(outcomes[3], (OperationOutcome::Applied, 0));
+
+        let failures: Vec<_> = report.failures().collect();
+        assert_len_eq_x!(&failures, 1);
+        assert_eq!(failures[0].operation_index(), Some(2));
+        assert_eq!(failures[0].path(), format_ptr!("/foo/1/baz/buzz"));
+
+        // the failed replace was rolled back, but everything else was applied
+        assert_eq!(data["foo"][0]["baz"], json!({"buzz": 0, "new": 42}));
+        assert_eq!(data["foo"][1]["baz"], json!({"quzz": 1, "new": 42}));
+    }
+
+    #[rstest]
+    fn test_patch_ext_lenient(mut data: Value) {
+        let p = Patch(vec![
+            replace_operation(format_ptr!("/foo/*/baz/buzz"), json!(42)),
+            add_operation(format_ptr!("/foo/0/baz/new"), json!(1)),
+            replace_operation(format_ptr!("/foo/0/baz/buzz/x"), json!(1)),
+            remove_operation(format_ptr!("/foo/5")),
+            move_operation(format_ptr!("/missing"), format_ptr!("/bar")),
+            add_operation(format_ptr!("/foo/-"), json!(3)),
+        ]);
+        let skipped = assert_ok!(patch_ext_lenient(&mut data, &p, &ErrorPolicy::default()));
+        let skipped: Vec<_> = skipped.iter().map(|err| (err.operation_index(), err.path().to_string())).collect();
+        assert_eq!(
+            skipped,
+            vec![
+                (Some(0), "/foo/1/baz/buzz".into()),
+                (Some(2), "/foo/0/baz/buzz".into()),
+                (Some(3), "/foo".into()),
+                (Some(4), "/bar".into()),
+            ]
+        );
+        assert_eq!(
+            data,
+            json!({"foo": [{"baz": {"buzz": 0, "new": 1}}, {"baz": {"quzz": 1}}, {"baz": {"fixx": 2}}, 3]})
+        );
+    }
+
+    #[rstest]
+    #[case::missing(ErrorPolicy { skip_missing_target: false, ..Default::default() }, 1)]
+    #[case::out_of_bounds(ErrorPolicy { skip_out_of_bounds: false, ..Default::default() }, 2)]
+    #[case::test_failed(ErrorPolicy::default(), 3)]
+    fn test_patch_ext_lenient_abort(mut data: Value, #[case] policy: ErrorPolicy, #[case] idx: usize) {
+        let orig = data.clone();
+        let p = Patch(vec![
+            add_operation(format_ptr!("/foo/0/baz/new"), json!(1)),
+            replace_operation(format_ptr!("/foo/0/missing"), json!(1)),
+            remove_operation(format_ptr!("/foo/5")),
+            test_operation(format_ptr!("/foo/0/baz/buzz"), json!(1)),
+        ]);
+        let err = assert_err!(patch_ext_lenient(&mut data, &p, &policy));
+        assert_eq!(err.operation_index(), Some(idx));
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_validate_patch_err(data: Value) {
+        let orig = data.clone();
+        let p = Patch(vec![
+            remove_operation(format_ptr!("/foo/*/baz/buzz")),
+            replace_operation(format_ptr!("/foo/0/baz/buzz"), json!(42)),
+        ]);
+        let err = validate_patch(&data, &p).unwrap_err();
+        assert_matches!(err.kind(), PatchErrorKind::TargetDoesNotExist);
+        assert_eq!(err.operation_index(), Some(1));
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    #[case::missing(
+        replace_operation(format_ptr!("/foo/*/baz/buzz"), json!(42)),
+        format_ptr!("/foo/1/baz/buzz"),
+        OperationKind::Replace
+    )]
+    #[case::missing_parent(
+        replace_operation(format_ptr!("/foo/*/bar/buzz"), json!(42)),
+        format_ptr!("/foo/0/bar"),
+        OperationKind::Replace
+    )]
+    #[case::bad_type(
+        add_operation(format_ptr!("/foo/*/baz/buzz/qux"), json!(42)),
+        format_ptr!("/foo/0/baz/buzz"),
+        OperationKind::Add
+    )]
+    #[case::bad_index(remove_operation(format_ptr!("/foo/7")), format_ptr!("/foo"), OperationKind::Remove)]
+    #[case::upstream(test_operation(format_ptr!("/foo/0"), json!(42)), format_ptr!("/foo/0"), OperationKind::Test)]
+    fn test_patch_ext_err_context(
+        mut data: Value,
+        #[case] op: PatchOperation,
+        #[case] path: PointerBuf,
+        #[case] kind: OperationKind,
+    ) {
+        let p = Patch(vec![add_operation(format_ptr!("/bar"), json!(1)), op]);
+        let err = patch_ext_all(&mut data, &p).unwrap_err();
+        assert_eq!(err.path(), &*path);
+        assert_eq!(err.operation(), Some(kind));
+        assert_eq!(err.operation_index(), Some(1));
+    }
+
+    #[rstest]
+    #[case::all(format_ptr!("/foo/*/baz"), json!({"buzz": 0}), TestMode::All, Some(format_ptr!("/foo/1/baz")))]
+    #[case::all_matches(format_ptr!("/foo/*/baz/quzz"), json!(1), TestMode::All, None)]
+    #[case::all_recursive(format_ptr!("/**/fixx"), json!(2), TestMode::All, None)]
+    #[case::all_no_matches(format_ptr!("/foo/*/qux"), json!(1), TestMode::All, None)]
+    #[case::any(format_ptr!("/foo/*/baz"), json!({"quzz": 1}), TestMode::Any, None)]
+    #[case::any_fails(format_ptr!("/foo/*/baz"), json!({}), TestMode::Any, Some(format_ptr!("/foo/*/baz")))]
+    #[case::any_no_matches(format_ptr!("/foo/*/qux"), json!(1), TestMode::Any, Some(format_ptr!("/foo/*/qux")))]
+    #[case::no_wildcard(format_ptr!("/foo/2/baz/fixx"), json!(2), TestMode::Any, None)]
+    #[case::no_wildcard_fails(
+        format_ptr!("/foo/2/baz/fixx"),
+        json!(3),
+        TestMode::All,
+        Some(format_ptr!("/foo/2/baz/fixx"))
+    )]
+    fn test_patch_ext_test(
+        data: Value,
+        #[case] path: PointerBuf,
+        #[case] value: Value,
+        #[case] mode: TestMode,
+        #[case] failed_at: Option<PointerBuf>,
+    ) {
+        let res = patch_ext_test(&data, &path, &value, mode);
+        match failed_at {
+            Some(p) => {
+                let err = assert_err!(res);
+                assert_matches!(err.kind(), PatchErrorKind::TestMismatch { expected, .. } if **expected == value);
+                assert_eq!(err.path(), &*p);
+            },
+            None => assert_ok!(res),
+        }
+    }
+
+    #[rstest]
+    #[case::concrete(format_ptr!("/foo/1/baz/quzz"), TestMode::All, format_ptr!("/foo/1/baz/quzz"), Some(json!(1)))]
+    #[case::wildcard(format_ptr!("/foo/*/baz/*"), TestMode::All, format_ptr!("/foo/0/baz/buzz"), Some(json!(0)))]
+    #[case::any(format_ptr!("/foo/*/baz/*"), TestMode::Any, format_ptr!("/foo/*/baz/*"), None)]
+    fn test_patch_ext_test_mismatch(
+        data: Value,
+        #[case] path: PointerBuf,
+        #[case] mode: TestMode,
+        #[case] failed_at: PointerBuf,
+        #[case] expected_actual: Option<Value>,
+    ) {
+        let err = assert_err!(patch_ext_test(&data, &path, &json!(5), mode));
+        let PatchErrorKind::TestMismatch { expected, actual } = err.kind() else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(**expected, json!(5));
+        assert_eq!(actual.as_deref(), expected_actual.as_ref());
+        assert_eq!(err.path(), failed_at);
+    }
+
+    #[rstest]
+    fn test_patch_ext_test_mismatch_display(mut data: Value) {
+        let err = assert_err!(patch_ext(&mut data, test_operation(format_ptr!("/foo/*/baz"), json!({}))));
+        assert_eq!(
+            err.to_string(),
+            r#"test operation failed at path '/foo/0/baz': test failed: expected {}, found {"buzz":0}"#
+        );
+    }
+
+    #[rstest]
+    #[case::copy_elementwise(
+        copy_operation(format_ptr!("/foo/*/baz"), format_ptr!("/foo/*/qux")),
+        json!({"foo": [
+            {"baz": {"buzz": 0}, "qux": {"buzz": 0}},
+            {"baz": {"quzz": 1}, "qux": {"quzz": 1}},
+            {"baz": {"fixx": 2}, "qux": {"fixx": 2}},
+        ]}),
+    )]
+    #[case::copy_new_parent(
+        copy_operation(format_ptr!("/foo/*/baz/buzz"), format_ptr!("/backup/*/buzz")),
+        json!({
+            "foo": [{"baz": {"buzz": 0}}, {"baz": {"quzz": 1}}, {"baz": {"fixx": 2}}],
+            "backup": [{"buzz": 0}],
+        }),
+    )]
+    #[case::move_elementwise(
+        move_operation(format_ptr!("/foo/*/baz"), format_ptr!("/foo/*/qux")),
+        json!({"foo": [{"qux": {"buzz": 0}}, {"qux": {"quzz": 1}}, {"qux": {"fixx": 2}}]}),
+    )]
+    #[case::move_recursive(
+        move_operation(format_ptr!("/**/quzz"), format_ptr!("/**/moved")),
+        json!({"foo": [{"baz": {"buzz": 0}}, {"baz": {"moved": 1}}, {"baz": {"fixx": 2}}]}),
+    )]
+    #[case::numbered_wildcards(
+        move_operation(format_ptr!("/foo/*/baz/*"), format_ptr!("/by_key/*2/*1")),
+        json!({
+            "foo": [{"baz": {}}, {"baz": {}}, {"baz": {}}],
+            "by_key": {"buzz": {"0": 0}, "quzz": {"1": 1}, "fixx": {"2": 2}},
+        }),
+    )]
+    fn test_patch_ext_move_copy(mut data: Value, #[case] op: PatchOperation, #[case] expected: Value) {
+        assert_ok!(patch_ext(&mut data, op));
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    #[case::count(copy_operation(format_ptr!("/foo/*/baz"), format_ptr!("/bar")), PatchErrorKind::InvalidWildcard)]
+    #[case::numbered_range(
+        copy_operation(format_ptr!("/foo/*"), format_ptr!("/bar/*2")),
+        PatchErrorKind::InvalidWildcard
+    )]
+    #[case::numbered_mixed(
+        copy_operation(format_ptr!("/foo/*/baz/*"), format_ptr!("/bar/*/*1")),
+        PatchErrorKind::InvalidWildcard
+    )]
+    #[case::inside_itself(
+        move_operation(format_ptr!("/foo/*"), format_ptr!("/foo/*/bar")),
+        PatchErrorKind::JsonPatchError(json_patch::PatchErrorKind::CannotMoveInsideItself)
+    )]
+    fn test_patch_ext_move_copy_err(mut data: Value, #[case] op: PatchOperation, #[case] kind: PatchErrorKind) {
+        let err = assert_err!(patch_ext(&mut data, op));
+        assert_eq!(err.kind().to_string(), kind.to_string());
+    }
+
     #[rstest]
     fn test_patch_ext_remove_root(mut data: Value) {
         let path = format_ptr!("");
@@ -525,6 +2804,26 @@ mod tests {
         assert_eq!(data, json!({"foo": []}));
     }
 
+    #[rstest]
+    fn test_patch_ext_remove_object_wildcard() {
+        let mut data = json!({"foo": {"a": {"bar": 1, "baz": 2}, "b": {"baz": 3}}});
+        let res = patch_ext(&mut data, remove_operation(format_ptr!("/foo/*/baz")));
+        assert_ok!(res);
+        assert_eq!(data, json!({"foo": {"a": {"bar": 1}, "b": {}}}));
+
+        let res = patch_ext(&mut data, remove_operation(format_ptr!("/foo/*")));
+        assert_ok!(res);
+        assert_eq!(data, json!({"foo": {}}));
+    }
+
+    #[rstest]
+    fn test_patch_ext_remove_filter() {
+        let mut data = json!({"containers": [{"name": "app"}, {"name": "sidecar"}, {"name": "sidecar", "image": "b"}]});
+        let res = patch_ext(&mut data, remove_operation(format_ptr!("/containers/[?name=sidecar]")));
+        assert_ok!(res);
+        assert_eq!(data, json!({"containers": [{"name": "app"}]}));
+    }
+
     #[rstest]
     fn test_patch_ext_remove_nested_wildcards() {
         let mut data = json!([[
@@ -538,6 +2837,22 @@ mod tests {
         assert_eq!(data, json!([[[], [], []]]));
     }
 
+    #[rstest]
+    fn test_patch_ext_remove_recursive() {
+        let mut data = json!({"a": [{"x": 1}, {"x": 2, "y": {"x": 3}}, [{"x": 4}]], "x": 5});
+        let res = patch_ext(&mut data, remove_operation(format_ptr!("/**/x")));
+        assert_ok!(res);
+        assert_eq!(data, json!({"a": [{}, {"y": {}}, [{}]]}));
+    }
+
+    #[rstest]
+    fn test_patch_ext_remove_recursive_vec() {
+        let mut data = json!({"a": [[1, 2], [3]], "b": {"c": [4, 5]}});
+        let res = patch_ext(&mut data, remove_operation(format_ptr!("/**/0")));
+        assert_ok!(res);
+        assert_eq!(data, json!({"a": [[]], "b": {"c": [5]}}));
+    }
+
     #[rstest]
     fn test_patch_ext_remove_vec(mut data: Value) {
         let path = format_ptr!("/foo/1");
@@ -554,10 +2869,141 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "preserve_order")]
     #[rstest]
-    fn test_patch_ext_remove_vec_err(mut data: Value) {
-        let path = format_ptr!("/foo/-");
+    #[case::add(add_operation(format_ptr!("/b"), json!(5)), r#"{"z":0,"a":{"y":1,"x":2,"w":3},"c":4,"b":5}"#)]
+    #[case::replace(replace_operation(format_ptr!("/a/y"), json!(5)), r#"{"z":0,"a":{"y":5,"x":2,"w":3},"c":4}"#)]
+    #[case::remove(remove_operation(format_ptr!("/a/y")), r#"{"z":0,"a":{"x":2,"w":3},"c":4}"#)]
+    #[case::move_key(
+        move_operation(format_ptr!("/z"), format_ptr!("/a/z")),
+        r#"{"a":{"y":1,"x":2,"w":3,"z":0},"c":4}"#
+    )]
+    #[case::move_self(move_operation(format_ptr!("/z"), format_ptr!("/z")), r#"{"z":0,"a":{"y":1,"x":2,"w":3},"c":4}"#)]
+    fn test_patch_ext_preserve_order(#[case] op: PatchOperation, #[case] expected: &str) {
+        let mut data = json!({"z": 0, "a": {"y": 1, "x": 2, "w": 3}, "c": 4});
+        assert_ok!(patch_ext(&mut data, op));
+        assert_eq!(serde_json::to_string(&data).unwrap(), expected);
+    }
+
+    #[rstest]
+    fn test_patch_ext_remove_vec_wildcard() {
+        let mut data = json!({"foo": [[1, 2, 3], [4, 5], [6]]});
+        let res = patch_ext(&mut data, remove_operation(format_ptr!("/foo/*/0")));
+        assert_ok!(res);
+        assert_eq!(data, json!({"foo": [[2, 3], [5], []]}));
+    }
+
+    #[rstest]
+    #[case(format_ptr!("/foo/-"))]
+    #[case(format_ptr!("/foo/3"))]
+    #[case(format_ptr!("/foo/*/baz/buzz/0"))]
+    #[case(format_ptr!("/foo/-4"))]
+    #[case(format_ptr!("/foo/-0"))]
+    fn test_patch_ext_remove_vec_err(mut data: Value, #[case] path: PointerBuf) {
         let res = patch_ext(&mut data, remove_operation(path));
         assert_err!(res);
     }
+
+    #[rstest]
+    #[case::add(add_operation(format_ptr!("/foo/*/-1"), json!(0)), json!({"foo": [[1, 2, 0, 3], [4, 0, 5], [0, 6]]}))]
+    #[case::replace(replace_operation(format_ptr!("/foo/*/-1"), json!(0)), json!({"foo": [[1, 2, 0], [4, 0], [0]]}))]
+    #[case::remove(remove_operation(format_ptr!("/foo/0/-2")), json!({"foo": [[1, 3], [4, 5], [6]]}))]
+    #[case::remove_wildcard(remove_operation(format_ptr!("/foo/*/-1")), json!({"foo": [[1, 2], [4], []]}))]
+    fn test_patch_ext_negative_index(#[case] op: PatchOperation, #[case] expected: Value) {
+        let mut data = json!({"foo": [[1, 2, 3], [4, 5], [6]]});
+        assert_ok!(patch_ext(&mut data, op));
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    #[case::add(add_operation(format_ptr!("/foo/:2/-"), json!(0)), json!({"foo": [[1, 2, 3, 0], [4, 5, 0], [6]]}))]
+    #[case::replace(replace_operation(format_ptr!("/foo/1:/0"), json!(0)), json!({"foo": [[1, 2, 3], [0, 5], [0]]}))]
+    #[case::remove(remove_operation(format_ptr!("/foo/0/1:")), json!({"foo": [[1], [4, 5], [6]]}))]
+    #[case::remove_nested(remove_operation(format_ptr!("/foo/1:2/:1")), json!({"foo": [[1, 2, 3], [5], [6]]}))]
+    fn test_patch_ext_slice(#[case] op: PatchOperation, #[case] expected: Value) {
+        let mut data = json!({"foo": [[1, 2, 3], [4, 5], [6]]});
+        assert_ok!(patch_ext(&mut data, op));
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    fn test_patch_many() {
+        let mut docs = vec![json!({"foo": [1, 2]}), json!({"foo": 1}), json!({"foo": []})];
+        let p =
+            Patch(vec![add_operation(format_ptr!("/foo/-"), json!(3)), add_operation(format_ptr!("/bar"), json!(4))]);
+        let res = patch_many(&mut docs, &p);
+
+        assert_len_eq_x!(&res, 3);
+        assert_ok!(&res[0]);
+        assert_err!(&res[1]);
+        assert_ok!(&res[2]);
+        assert_eq!(docs, vec![json!({"foo": [1, 2, 3], "bar": 4}), json!({"foo": 1}), json!({"foo": [3], "bar": 4})]);
+    }
+
+    #[rstest]
+    fn test_patch_ext_negative_index_traced() {
+        let mut data = json!({"foo": [1, 2, 3]});
+        let res = patch_ext_traced(&mut data, replace_operation(format_ptr!("/foo/-3"), json!(0)));
+        assert_eq!(assert_ok!(res), vec![format_ptr!("/foo/0")]);
+
+        let err = patch_ext(&mut data, replace_operation(format_ptr!("/foo/-4"), json!(0))).unwrap_err();
+        assert_matches!(err.kind(), PatchErrorKind::NegativeIndexOutOfBounds(4));
+        assert_eq!(err.path(), format_ptr!("/foo/-4"));
+    }
+
+    #[rstest]
+    #[case::add(add_operation(format_ptr!("/foo/\\*/bar"), json!(0)), json!({"foo": {"*": {"bar": 0}, "x": {"y": 1}}}))]
+    #[case::add_new(
+        add_operation(format_ptr!("/foo/\\**"), json!(0)),
+        json!({"foo": {"*": {}, "**": 0, "x": {"y": 1}}}),
+    )]
+    #[case::replace(replace_operation(format_ptr!("/foo/\\*"), json!(0)), json!({"foo": {"*": 0, "x": {"y": 1}}}))]
+    #[case::remove(remove_operation(format_ptr!("/foo/\\*")), json!({"foo": {"x": {"y": 1}}}))]
+    #[case::backslash(
+        add_operation(format_ptr!("/foo/\\\\x"), json!(0)),
+        json!({"foo": {"*": {}, "\\x": 0, "x": {"y": 1}}}),
+    )]
+    #[case::unneeded(
+        add_operation(format_ptr!("/foo/\\x/z"), json!(0)),
+        json!({"foo": {"*": {}, "x": {"y": 1, "z": 0}}}),
+    )]
+    #[case::copy(
+        copy_operation(format_ptr!("/foo/\\*"), format_ptr!("/foo/\\1:3")),
+        json!({"foo": {"*": {}, "1:3": {}, "x": {"y": 1}}}),
+    )]
+    fn test_patch_ext_escaped_keys(#[case] op: PatchOperation, #[case] expected: Value) {
+        let mut data = json!({"foo": {"*": {}, "x": {"y": 1}}});
+        assert_ok!(patch_ext(&mut data, op));
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    fn test_escaped_key_matches() {
+        let data = json!({"foo": {"*": {"bar": 1}, "[?a=b]": {"bar": 2}, "x": {"bar": 3}}});
+        let found = matches(&format_ptr!("/foo/*/bar"), &data);
+        let paths: Vec<_> = found.iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![format_ptr!("/foo/\\*/bar"), format_ptr!("/foo/\\[?a=b]/bar"), format_ptr!("/foo/x/bar")]
+        );
+
+        // The matched paths can be passed straight back into the patch functions
+        let mut patched = data.clone();
+        for (p, v) in found {
+            assert_ok!(patch_ext(&mut patched, test_operation(p, v.clone())));
+        }
+    }
+
+    #[rstest]
+    #[case::plain("foo", "foo")]
+    #[case::tilde("a~b/c", "a~0b~1c")]
+    #[case::wildcard("*", "\\*")]
+    #[case::slice("1:3", "\\1:3")]
+    #[case::numbered_wildcard("*1", "\\*1")]
+    #[case::glob("app*", "\\app*")]
+    #[case::ignore_case("(?i)foo", "\\(?i)foo")]
+    #[case::backslash("\\foo", "\\\\foo")]
+    fn test_escape(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(escape(input), expected);
+    }
 }
@@ -1,6 +1,149 @@
+// The macros are expanded in other crates, which might not have `format!` in scope (if they're
+// no_std themselves), so they reference it through this crate instead
+#[doc(hidden)]
+pub use alloc::format as __format;
+use alloc::string::ToString;
+use core::fmt::Display;
+
+use crate::{
+    PointerBuf,
+    key_token,
+};
+
+// Literal pointer templates are checked at compile time (as much as they can be, given that the
+// format arguments aren't known until runtime): an invalid template is a compile error, but a
+// pointer that's only invalid after formatting still panics.
 #[macro_export]
 macro_rules! format_ptr {
+    ($str:literal $($args:tt)*) => {{
+        const _: () = assert!(json_patch_ext::is_valid_ptr_template($str), "invalid JSON pointer");
+        json_patch_ext::PointerBuf::parse(&json_patch_ext::__format!($str $($args)*)).expect("pointer parse error")
+    }};
+}
+
+// Like format_ptr!, but returns a `Result<PointerBuf, PatchError>` instead of panicking
+#[macro_export]
+macro_rules! try_format_ptr {
     ($str:literal $($args:tt)*) => {
-        json_patch_ext::PointerBuf::parse(&format!($str $($args)*)).expect("pointer parse error")
+        json_patch_ext::PointerBuf::parse(json_patch_ext::__format!($str $($args)*))
+            .map_err(json_patch_ext::PatchError::from)
+    };
+}
+
+// Build a pointer from a comma-separated list of segments, e.g. `ptr!["metadata", "labels", key]`.
+// Each segment can be anything that implements Display (so `ptr!["foo", 0]` is `/foo/0`), and is
+// escaped automatically: `ptr!["labels", "example.com/app"]` references the `example.com/app`
+// field, and a `"*"` segment references the field named `*`.  To get a wildcard instead, use a bare
+// `*` or `**` segment: `ptr!["spec", "containers", *, "image"]` is `/spec/containers/*/image`.
+#[macro_export]
+macro_rules! ptr {
+    (@push $p:ident;) => {};
+    (@push $p:ident; * * $(, $($rest:tt)*)?) => {
+        $p.push_back(json_patch_ext::Token::new("**"));
+        json_patch_ext::ptr!(@push $p; $($($rest)*)?);
+    };
+    (@push $p:ident; * $(, $($rest:tt)*)?) => {
+        $p.push_back(json_patch_ext::Token::new("*"));
+        json_patch_ext::ptr!(@push $p; $($($rest)*)?);
     };
+    (@push $p:ident; $seg:expr $(, $($rest:tt)*)?) => {
+        json_patch_ext::push_ptr_segment(&mut $p, $seg);
+        json_patch_ext::ptr!(@push $p; $($($rest)*)?);
+    };
+    ($($segs:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut p = json_patch_ext::PointerBuf::new();
+        json_patch_ext::ptr!(@push p; $($segs)*);
+        p
+    }};
+}
+
+// Append a single (escaped) segment to the pointer; used by ptr!
+#[doc(hidden)]
+pub fn push_ptr_segment(p: &mut PointerBuf, seg: impl Display) {
+    p.push_back(key_token(&seg.to_string()).into_owned());
+}
+
+// Check whether a format string can produce a valid JSON pointer: it must be empty or start with a
+// `/` (or a placeholder), and every `~` must be followed by `0` or `1` (or a placeholder).  The
+// contents of placeholders are skipped, and `{{` is treated as a literal brace.
+#[doc(hidden)]
+pub const fn is_valid_ptr_template(s: &str) -> bool {
+    let b = s.as_bytes();
+    if !b.is_empty() && b[0] != b'/' && !is_placeholder(b, 0) {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < b.len() {
+        if is_placeholder(b, i) {
+            while i < b.len() && b[i] != b'}' {
+                i += 1;
+            }
+        } else if b[i] == b'{' {
+            i += 1;
+        } else if b[i] == b'~' {
+            if is_placeholder(b, i + 1) {
+                i += 1;
+                continue;
+            } else if i + 1 >= b.len() || (b[i + 1] != b'0' && b[i + 1] != b'1') {
+                return false;
+            }
+            i += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn is_placeholder(b: &[u8], i: usize) -> bool {
+    i < b.len() && b[i] == b'{' && !(i + 1 < b.len() && b[i + 1] == b'{')
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::PatchErrorKind;
+
+    #[rstest]
+    #[case("", true)]
+    #[case("/foo/bar", true)]
+    #[case("/foo~0bar/~1", true)]
+    #[case("/foo/{}", true)]
+    #[case("{}/foo", true)]
+    #[case("/foo~{}", true)]
+    #[case("/foo/{{bar}}", true)]
+    #[case("foo", false)]
+    #[case("/foo~2", false)]
+    #[case("/foo~", false)]
+    #[case("{{/foo", false)]
+    fn test_is_valid_ptr_template(#[case] s: &str, #[case] valid: bool) {
+        assert_eq!(is_valid_ptr_template(s), valid);
+    }
+
+    #[rstest]
+    fn test_try_format_ptr() {
+        let key = "bar";
+        assert_eq!(assert_ok!(try_format_ptr!("/foo/{key}")), format_ptr!("/foo/bar"));
+
+        let err = assert_err!(try_format_ptr!("{}", key));
+        assert_matches!(err.kind(), PatchErrorKind::ParseError(_));
+    }
+
+    #[rstest]
+    fn test_ptr() {
+        let key = "example.com/app";
+        let idx = 2;
+        assert_eq!(ptr![], PointerBuf::root());
+        assert_eq!(ptr!["foo", 0], format_ptr!("/foo/0"));
+        assert_eq!(ptr!["metadata", "labels", key], format_ptr!("/metadata/labels/example.com~1app"));
+        assert_eq!(ptr!["spec", "containers", *, "image"], format_ptr!("/spec/containers/*/image"));
+        assert_eq!(ptr![**, "image",], format_ptr!("/**/image"));
+        assert_eq!(ptr!["foo", idx + 1, "~x"], format_ptr!("/foo/3/~0x"));
+        assert_eq!(ptr!["*", "1:3", "\\bar"], format_ptr!("/\\*/\\1:3/\\\\bar"));
+    }
 }
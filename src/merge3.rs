@@ -0,0 +1,228 @@
+//! Three-way merges: given a common base document and two sets of changes made to it
+//! independently ("ours" and "theirs"), combine both sets of changes into one.  A change made on
+//! only one side is always kept, as is a change made identically on both sides; anything that was
+//! changed differently on both sides is a conflict.  Objects are merged field by field, and arrays
+//! are merged element by element as long as neither side changed the array's length (otherwise the
+//! whole array is treated as a single value).
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde_json::{
+    Map,
+    Value,
+};
+use thiserror::Error;
+
+use crate::diff::diff;
+use crate::{
+    Patch,
+    PatchError,
+    PointerBuf,
+    key_token,
+    patch_ext_all,
+};
+
+// A location that was changed differently on each side of the merge; a missing value means that
+// the location doesn't exist (e.g., it was removed) on that side.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MergeConflict {
+    pub path: PointerBuf,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+#[derive(Debug, Error)]
+pub enum MergeError {
+    #[error("could not apply our patch: {0}")]
+    Ours(PatchError),
+
+    #[error("could not apply their patch: {0}")]
+    Theirs(PatchError),
+
+    #[error("{} conflicting change(s)", .0.len())]
+    Conflicts(Vec<MergeConflict>),
+}
+
+// Merge two patches that were both written against `base`, returning a single patch that makes
+// both sets of changes (as a diff against `base`, so it only references concrete paths)
+pub fn merge3(base: &Value, ours: &Patch, theirs: &Patch) -> Result<Patch, MergeError> {
+    let mut our_doc = base.clone();
+    patch_ext_all(&mut our_doc, ours).map_err(MergeError::Ours)?;
+    let mut their_doc = base.clone();
+    patch_ext_all(&mut their_doc, theirs).map_err(MergeError::Theirs)?;
+
+    let merged = merge3_documents(base, &our_doc, &their_doc)?;
+    Ok(diff(base, &merged))
+}
+
+// Merge two documents that were both derived from `base`
+pub fn merge3_documents(base: &Value, ours: &Value, theirs: &Value) -> Result<Value, MergeError> {
+    let mut conflicts = vec![];
+    let merged = merge_values(Some(base), Some(ours), Some(theirs), &mut PointerBuf::new(), &mut conflicts);
+    if !conflicts.is_empty() {
+        return Err(MergeError::Conflicts(conflicts));
+    }
+    Ok(merged.unwrap_or_default())
+}
+
+fn merge_values(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    path: &mut PointerBuf,
+    conflicts: &mut Vec<MergeConflict>,
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+
+    match (base, ours, theirs) {
+        (Some(Value::Object(b)), Some(Value::Object(o)), Some(Value::Object(t))) => {
+            let mut res = Map::new();
+            // Our fields, then any new fields from theirs, then any fields that both sides removed
+            let theirs_only = t.keys().filter(|k| !o.contains_key(*k));
+            let removed = b.keys().filter(|k| !o.contains_key(*k) && !t.contains_key(*k));
+            for k in o.keys().chain(theirs_only).chain(removed) {
+                path.push_back(key_token(k));
+                if let Some(v) = merge_values(b.get(k), o.get(k), t.get(k), path, conflicts) {
+                    res.insert(k.clone(), v);
+                }
+                path.pop_back();
+            }
+            Some(Value::Object(res))
+        },
+        (Some(Value::Array(b)), Some(Value::Array(o)), Some(Value::Array(t)))
+            if b.len() == o.len() && b.len() == t.len() =>
+        {
+            let mut res = vec![];
+            for i in 0..b.len() {
+                path.push_back(i);
+                res.extend(merge_values(b.get(i), o.get(i), t.get(i), path, conflicts));
+                path.pop_back();
+            }
+            Some(Value::Array(res))
+        },
+        _ => {
+            conflicts.push(MergeConflict {
+                path: path.clone(),
+                base: base.cloned(),
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+            });
+            ours.cloned()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        PatchErrorKind,
+        PatchOperation,
+        add_operation,
+        format_ptr,
+        remove_operation,
+        replace_operation,
+    };
+
+    #[fixture]
+    fn base() -> Value {
+        json!({"metadata": {"name": "app", "labels": {"a": "1"}}, "spec": {"replicas": 1, "ports": [80, 443]}})
+    }
+
+    #[rstest]
+    #[case::disjoint(
+        vec![replace_operation(format_ptr!("/spec/replicas"), json!(3))],
+        vec![add_operation(format_ptr!("/metadata/labels/b"), json!("2"))],
+        json!({
+            "metadata": {"name": "app", "labels": {"a": "1", "b": "2"}},
+            "spec": {"replicas": 3, "ports": [80, 443]},
+        }),
+    )]
+    #[case::same_change(
+        vec![replace_operation(format_ptr!("/spec/replicas"), json!(3))],
+        vec![
+            replace_operation(format_ptr!("/spec/replicas"), json!(3)),
+            remove_operation(format_ptr!("/metadata/labels")),
+        ],
+        json!({"metadata": {"name": "app"}, "spec": {"replicas": 3, "ports": [80, 443]}}),
+    )]
+    #[case::array_elements(
+        vec![replace_operation(format_ptr!("/spec/ports/0"), json!(8080))],
+        vec![replace_operation(format_ptr!("/spec/ports/1"), json!(8443))],
+        json!({"metadata": {"name": "app", "labels": {"a": "1"}}, "spec": {"replicas": 1, "ports": [8080, 8443]}}),
+    )]
+    fn test_merge3(
+        base: Value,
+        #[case] ours: Vec<PatchOperation>,
+        #[case] theirs: Vec<PatchOperation>,
+        #[case] expected: Value,
+    ) {
+        let p = assert_ok!(merge3(&base, &Patch(ours), &Patch(theirs)));
+        let mut doc = base;
+        assert_ok!(patch_ext_all(&mut doc, &p));
+        assert_eq!(doc, expected);
+    }
+
+    #[rstest]
+    fn test_merge3_conflicts(base: Value) {
+        let ours = Patch(vec![
+            replace_operation(format_ptr!("/spec/replicas"), json!(3)),
+            add_operation(format_ptr!("/spec/ports/-"), json!(8080)),
+        ]);
+        let theirs = Patch(vec![
+            replace_operation(format_ptr!("/spec/replicas"), json!(5)),
+            remove_operation(format_ptr!("/spec/ports/0")),
+            replace_operation(format_ptr!("/metadata/name"), json!("other")),
+        ]);
+        let err = assert_err!(merge3(&base, &ours, &theirs));
+        let MergeError::Conflicts(conflicts) = err else {
+            panic!("expected conflicts, got {err}");
+        };
+        let mut expected = vec![
+            MergeConflict {
+                path: format_ptr!("/spec/ports"),
+                base: Some(json!([80, 443])),
+                ours: Some(json!([80, 443, 8080])),
+                theirs: Some(json!([443])),
+            },
+            MergeConflict {
+                path: format_ptr!("/spec/replicas"),
+                base: Some(json!(1)),
+                ours: Some(json!(3)),
+                theirs: Some(json!(5)),
+            },
+        ];
+        // With the `preserve_order` feature, the conflicts are listed in document order
+        if cfg!(feature = "preserve_order") {
+            expected.reverse();
+        }
+        assert_eq!(conflicts, expected);
+    }
+
+    #[rstest]
+    fn test_merge3_removed_and_modified(base: Value) {
+        let ours = Patch(vec![remove_operation(format_ptr!("/metadata/labels"))]);
+        let theirs = Patch(vec![add_operation(format_ptr!("/metadata/labels/b"), json!("2"))]);
+        let err = assert_err!(merge3(&base, &ours, &theirs));
+        assert_matches!(err, MergeError::Conflicts(c) if c.len() == 1 && c[0].ours.is_none());
+    }
+
+    #[rstest]
+    fn test_merge3_patch_error(base: Value) {
+        let ours = Patch(vec![replace_operation(format_ptr!("/missing"), json!(1))]);
+        let err = assert_err!(merge3(&base, &ours, &Patch(vec![])));
+        assert_matches!(err, MergeError::Ours(e) if matches!(e.kind(), PatchErrorKind::TargetDoesNotExist));
+    }
+}
@@ -0,0 +1,198 @@
+//! Helpers for addressing array elements by a "merge key" instead of by index, as in a Kubernetes
+//! strategic merge patch: e.g., the containers in a pod spec are identified by their `name` field,
+//! so `add_by_key_operation` can update the `sidecar` container wherever it is in the list (or add
+//! it if it's not there yet).  Index-based paths are fragile for lists like these, because their
+//! order isn't stable.
+//!
+//! The elements can also be referenced by key in any other operation using a filtered wildcard,
+//! e.g. `/spec/containers/[?name=sidecar]/image`.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+
+use crate::{
+    ExtPatchOperation,
+    PatchError,
+    PatchErrorKind,
+    PatchMode,
+    PatchOptions,
+    PointerBuf,
+    bounded_matches,
+    check_max_index,
+    is_recursive,
+    patch_ext_helper,
+};
+
+// Upsert an object into the array at the path, identified by its `key_field`: every element whose
+// `key_field` is equal to `key_value` is replaced by the value, and if there aren't any, the value
+// is appended to the array instead.  The key field of the value is always set to `key_value`, so
+// the new element can be found by the same key later on.  The path can contain wildcards (e.g.
+// `/spec/*/containers`), and a missing array is created (along with its parents) as in an add.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct AddByKeyOperation {
+    pub path: PointerBuf,
+    pub key_field: String,
+    pub key_value: Value,
+    pub value: Value,
+}
+
+pub fn add_by_key_operation(path: PointerBuf, key_field: &str, key_value: Value, value: Value) -> ExtPatchOperation {
+    ExtPatchOperation::AddByKey(AddByKeyOperation {
+        path,
+        key_field: key_field.into(),
+        key_value,
+        value,
+    })
+}
+
+pub(crate) fn add_by_key(
+    obj: &mut Value,
+    op: &AddByKeyOperation,
+    opts: &PatchOptions,
+) -> Result<Vec<PointerBuf>, PatchError> {
+    let mut value = op.value.clone();
+    let Value::Object(map) = &mut value else {
+        return Err(PatchError::new(PatchErrorKind::UnexpectedType, &op.path));
+    };
+    map.insert(op.key_field.clone(), op.key_value.clone());
+
+    // A recursive wildcard only ever matches existing arrays, so it never creates anything
+    let arrays = if is_recursive(&op.path) {
+        bounded_matches(&op.path, obj, opts)?.into_iter().map(|(p, _)| p).collect()
+    } else {
+        vec![op.path.clone()]
+    };
+    let mode = if opts.allow_autocreate_parents { PatchMode::CreateArray } else { PatchMode::Error };
+
+    let mut modified = vec![];
+    for array in arrays {
+        for (path, v) in patch_ext_helper(&array, obj, mode, opts)? {
+            let Value::Array(vec) = v else {
+                return Err(PatchError::new(PatchErrorKind::UnexpectedType, &path));
+            };
+
+            let mut found = false;
+            for (i, elem) in vec.iter_mut().enumerate() {
+                if elem.get(&op.key_field) != Some(&op.key_value) {
+                    continue;
+                }
+                found = true;
+                if !(opts.skip_unchanged_writes && *elem == value) {
+                    *elem = value.clone();
+                    modified.push(path.with_trailing_token(i));
+                }
+            }
+            if !found {
+                check_max_index(vec.len(), &path, opts)?;
+                vec.push(value.clone());
+                modified.push(path.with_trailing_token(vec.len() - 1));
+            }
+        }
+    }
+    Ok(modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        ExtPatch,
+        format_ptr,
+        patch_ext_document,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"spec": {"containers": [
+            {"name": "app", "image": "app:1"},
+            {"name": "sidecar", "image": "proxy:1"},
+        ]}})
+    }
+
+    #[rstest]
+    #[case::existing(
+        add_by_key_operation(format_ptr!("/spec/containers"), "name", json!("sidecar"), json!({"image": "proxy:2"})),
+        json!({"spec": {"containers": [{"name": "app", "image": "app:1"}, {"name": "sidecar", "image": "proxy:2"}]}}),
+    )]
+    #[case::missing(
+        add_by_key_operation(format_ptr!("/spec/containers"), "name", json!("log"), json!({"image": "log:1"})),
+        json!({"spec": {"containers": [
+            {"name": "app", "image": "app:1"},
+            {"name": "sidecar", "image": "proxy:1"},
+            {"name": "log", "image": "log:1"},
+        ]}}),
+    )]
+    #[case::new_array(
+        add_by_key_operation(format_ptr!("/spec/initContainers"), "name", json!("init"), json!({})),
+        json!({"spec": {
+            "containers": [{"name": "app", "image": "app:1"}, {"name": "sidecar", "image": "proxy:1"}],
+            "initContainers": [{"name": "init"}],
+        }}),
+    )]
+    #[case::recursive(
+        add_by_key_operation(format_ptr!("/**/containers"), "name", json!("app"), json!({"image": "app:2"})),
+        json!({"spec": {"containers": [{"name": "app", "image": "app:2"}, {"name": "sidecar", "image": "proxy:1"}]}}),
+    )]
+    fn test_add_by_key(mut data: Value, #[case] op: ExtPatchOperation, #[case] expected: Value) {
+        assert_ok!(patch_ext_document(&mut data, &ExtPatch(vec![op])));
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    fn test_add_by_key_wildcard() {
+        let mut data = json!({"pods": [{"containers": [{"name": "a"}]}, {"containers": [{"name": "b"}]}]});
+        let op = add_by_key_operation(format_ptr!("/pods/*/containers"), "name", json!("b"), json!({"x": 1}));
+        assert_ok!(patch_ext_document(&mut data, &ExtPatch(vec![op])));
+        assert_eq!(
+            data,
+            json!({"pods": [
+                {"containers": [{"name": "a"}, {"name": "b", "x": 1}]},
+                {"containers": [{"name": "b", "x": 1}]},
+            ]})
+        );
+    }
+
+    #[rstest]
+    #[case::not_an_object(format_ptr!("/spec/containers"), json!("sidecar"), format_ptr!("/spec/containers"))]
+    #[case::not_an_array(format_ptr!("/spec"), json!({}), format_ptr!("/spec"))]
+    fn test_add_by_key_err(mut data: Value, #[case] path: PointerBuf, #[case] value: Value, #[case] at: PointerBuf) {
+        let orig = data.clone();
+        let op = add_by_key_operation(path, "name", json!("sidecar"), value);
+        let err = assert_err!(patch_ext_document(&mut data, &ExtPatch(vec![op])));
+        assert_matches!(err.kind(), PatchErrorKind::UnexpectedType);
+        assert_eq!(err.path(), at);
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_add_by_key_serde() {
+        let op: ExtPatchOperation = assert_ok!(serde_json::from_value(json!({
+            "op": "add_by_key",
+            "path": "/spec/containers",
+            "key_field": "name",
+            "key_value": "sidecar",
+            "value": {"image": "proxy:2"},
+        })));
+        assert_eq!(
+            op,
+            add_by_key_operation(
+                format_ptr!("/spec/containers"),
+                "name",
+                json!("sidecar"),
+                json!({"image": "proxy:2"})
+            )
+        );
+    }
+}
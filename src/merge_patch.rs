@@ -0,0 +1,211 @@
+//! Helpers for working with [JSON Merge Patch](https://datatracker.ietf.org/doc/html/rfc7386)
+//! documents.  A merge patch describes the changes to a document by example: every field present
+//! in the patch is set to the corresponding value in the target document, with nested objects
+//! merged recursively, and `null` meaning "remove this field".  One consequence of this is that a
+//! merge patch cannot set a field to `null`, or modify individual array elements (arrays are always
+//! replaced wholesale).
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde_json::{
+    Map,
+    Value,
+};
+
+use crate::{
+    Patch,
+    PatchOperation,
+    PointerBuf,
+    add_operation,
+    key_token,
+    remove_key,
+    remove_operation,
+};
+
+// Apply a merge patch to the document, as in json_patch::merge; but with the `preserve_order`
+// feature, removing a field keeps the rest of the fields in their original order.
+pub fn apply_merge_patch(obj: &mut Value, merge: &Value) {
+    let Value::Object(merge_map) = merge else {
+        *obj = merge.clone();
+        return;
+    };
+
+    if !obj.is_object() {
+        *obj = Value::Object(Map::new());
+    }
+    let Value::Object(map) = obj else { unreachable!() };
+    for (k, v) in merge_map {
+        if v.is_null() {
+            remove_key(map, k);
+        } else {
+            apply_merge_patch(map.entry(k).or_insert(Value::Null), v);
+        }
+    }
+}
+
+// Compute a merge patch that transforms `old` into `new`; since merge patches can't represent
+// `null` values, any fields in `new` that are set to `null` will be removed instead.
+pub fn diff_merge_patch(old: &Value, new: &Value) -> Value {
+    let (Value::Object(old_map), Value::Object(new_map)) = (old, new) else {
+        return new.clone();
+    };
+
+    let mut merge = Map::new();
+    for k in old_map.keys().filter(|k| !new_map.contains_key(*k)) {
+        merge.insert(k.clone(), Value::Null);
+    }
+
+    for (k, new_val) in new_map {
+        match old_map.get(k) {
+            Some(old_val) if old_val == new_val => (),
+            Some(old_val @ Value::Object(_)) if new_val.is_object() => {
+                merge.insert(k.clone(), diff_merge_patch(old_val, new_val));
+            },
+            _ => {
+                merge.insert(k.clone(), new_val.clone());
+            },
+        }
+    }
+    Value::Object(merge)
+}
+
+// Convert a merge patch into an equivalent JSON patch, which can be applied with `patch_ext`; the
+// semantics of a merge patch depend on the shape of the document it's being applied to, so the
+// result is only equivalent for the given document.
+pub fn to_patch(obj: &Value, merge: &Value) -> Patch {
+    let mut ops = vec![];
+    to_patch_impl(Some(obj), merge, &mut PointerBuf::new(), &mut ops);
+    Patch(ops)
+}
+
+fn to_patch_impl(obj: Option<&Value>, merge: &Value, path: &mut PointerBuf, ops: &mut Vec<PatchOperation>) {
+    let Value::Object(merge_map) = merge else {
+        ops.push(add_operation(path.clone(), merge.clone()));
+        return;
+    };
+
+    // If the target isn't an object, the merge patch replaces it with a new object, so we just
+    // compute what that object should be
+    let Some(Value::Object(obj_map)) = obj else {
+        let mut v = Value::Object(Map::new());
+        apply_merge_patch(&mut v, merge);
+        ops.push(add_operation(path.clone(), v));
+        return;
+    };
+
+    for (k, merge_val) in merge_map {
+        path.push_back(key_token(k));
+        match (obj_map.get(k), merge_val) {
+            (Some(_), Value::Null) => ops.push(remove_operation(path.clone())),
+            (None, Value::Null) => (),
+            (v, _) => to_patch_impl(v, merge_val, path, ops),
+        }
+        path.pop_back();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        format_ptr,
+        patch_ext_all,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({
+            "title": "Goodbye!",
+            "author": {"givenName": "John", "familyName": "Doe"},
+            "tags": ["example", "sample"],
+            "content": "This will be unchanged",
+        })
+    }
+
+    #[fixture]
+    fn merge() -> Value {
+        json!({
+            "title": "Hello!",
+            "phoneNumber": "+01-123-456-7890",
+            "author": {"familyName": null},
+            "tags": ["example"],
+            "content": {"text": "new"},
+            "missing": null,
+        })
+    }
+
+    #[rstest]
+    fn test_apply_merge_patch(mut data: Value, merge: Value) {
+        apply_merge_patch(&mut data, &merge);
+        assert_eq!(
+            data,
+            json!({
+                "title": "Hello!",
+                "author": {"givenName": "John"},
+                "tags": ["example"],
+                "content": {"text": "new"},
+                "phoneNumber": "+01-123-456-7890",
+            })
+        );
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[rstest]
+    fn test_apply_merge_patch_preserve_order(mut data: Value, merge: Value) {
+        apply_merge_patch(&mut data, &merge);
+        let keys: Vec<&String> = data.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["title", "author", "tags", "content", "phoneNumber"]);
+    }
+
+    #[rstest]
+    #[case::objects(json!({"title": "Hello!", "author": {"givenName": "Jane", "middleName": "Q"}}))]
+    #[case::arrays(json!({"title": "Goodbye!", "tags": [], "content": [1, 2]}))]
+    #[case::scalar(json!(42))]
+    fn test_diff_merge_patch(data: Value, #[case] new: Value) {
+        let merge = diff_merge_patch(&data, &new);
+        let mut res = data.clone();
+        apply_merge_patch(&mut res, &merge);
+        assert_eq!(res, new);
+    }
+
+    #[rstest]
+    fn test_diff_merge_patch_minimal(data: Value) {
+        let mut new = data.clone();
+        new["author"]["givenName"] = json!("Jane");
+        assert_eq!(diff_merge_patch(&data, &new), json!({"author": {"givenName": "Jane"}}));
+    }
+
+    #[rstest]
+    fn test_to_patch(data: Value, merge: Value) {
+        let p = to_patch(&data, &merge);
+        let title = add_operation(format_ptr!("/title"), json!("Hello!"));
+        let phone = add_operation(format_ptr!("/phoneNumber"), json!("+01-123-456-7890"));
+        let author = remove_operation(format_ptr!("/author/familyName"));
+        let tags = add_operation(format_ptr!("/tags"), json!(["example"]));
+        let content = add_operation(format_ptr!("/content"), json!({"text": "new"}));
+        // The operations follow the order of the fields in the merge patch, which is alphabetical
+        // unless the `preserve_order` feature is enabled
+        if cfg!(feature = "preserve_order") {
+            assert_eq!(p, Patch(vec![title, phone, author, tags, content]));
+        } else {
+            assert_eq!(p, Patch(vec![author, content, phone, tags, title]));
+        }
+
+        let mut patched = data.clone();
+        patch_ext_all(&mut patched, &p).unwrap();
+        let mut merged = data.clone();
+        apply_merge_patch(&mut merged, &merge);
+        assert_eq!(patched, merged);
+    }
+
+    #[rstest]
+    fn test_to_patch_root(data: Value) {
+        let p = to_patch(&data, &json!([1, 2]));
+        assert_eq!(p, Patch(vec![add_operation(format_ptr!(""), json!([1, 2]))]));
+    }
+}
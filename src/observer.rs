@@ -0,0 +1,189 @@
+use serde_json::Value;
+
+use crate::{
+    CopyOperation,
+    MoveOperation,
+    Operation,
+    OperationKind,
+    PatchError,
+    PatchOperation,
+    PatchOptions,
+    Pointer,
+    Snapshot,
+    affected_subtree,
+    apply_operation,
+    matches,
+    resolve_ext,
+    rollback,
+};
+
+// Callbacks that are invoked while `patch_ext_observed` applies an operation, e.g. to record
+// metrics or to capture the changes made to the document; every method does nothing by default, so
+// an observer only needs to implement the ones that it cares about.
+pub trait PatchObserver {
+    // Called (before the operation is applied) with the concrete path of every existing value that
+    // the operation's path matches; for a move or copy, this is every value that its `from` path
+    // matches.
+    fn on_match(&mut self, _path: &Pointer) {}
+
+    // Called (after the operation is applied) for every location that the operation modified, with
+    // the values at that location before and after the operation, either of which may be missing.
+    // These are compared by location, so inserting or removing an array element reports the
+    // element that was shifted into its place as the new (or old) value.
+    fn on_write(&mut self, _path: &Pointer, _old: Option<&Value>, _new: Option<&Value>) {}
+
+    // Called if the operation succeeded without modifying anything, e.g. a wildcard that didn't
+    // match anything; test operations are never skipped.
+    fn on_skip(&mut self, _op: &PatchOperation) {}
+
+    // Called if the operation failed, before the error is returned
+    fn on_error(&mut self, _err: &PatchError) {}
+}
+
+// Like patch_ext, but reports what the operation does to the observer as it's applied.  If the
+// operation fails, any changes that it made (e.g. to some of the locations a wildcard matched) are
+// rolled back, so on_write is only called for operations that succeed.
+pub fn patch_ext_observed(
+    obj: &mut Value,
+    p: PatchOperation,
+    observer: &mut dyn PatchObserver,
+) -> Result<(), PatchError> {
+    let source = match &p {
+        PatchOperation::Move(MoveOperation { from, .. }) | PatchOperation::Copy(CopyOperation { from, .. }) => from,
+        op => op.path(),
+    };
+    for (path, _) in matches(source, obj) {
+        observer.on_match(&path);
+    }
+
+    // affected_subtree only returns paths that exist, so the unwrap is safe
+    let snapshot = affected_subtree(obj, &p).map(|path| {
+        let v = resolve_ext(obj, &path).unwrap().clone();
+        (path, v)
+    });
+
+    let modified = match apply_operation(obj, &p, &PatchOptions::default()) {
+        Ok(modified) => modified,
+        Err(err) => {
+            rollback(obj, snapshot.map(|(path, v)| Snapshot::subtree(path, v)).into_iter().collect());
+            observer.on_error(&err);
+            return Err(err);
+        },
+    };
+
+    if modified.is_empty() && p.kind() != OperationKind::Test {
+        observer.on_skip(&p);
+    }
+    for path in modified {
+        // Every modified path is inside of the snapshotted subtree
+        let old = snapshot.as_ref().and_then(|(prefix, v)| resolve_ext(v, path.strip_prefix(prefix)?));
+        observer.on_write(&path, old, resolve_ext(obj, &path));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        PatchErrorKind,
+        PointerBuf,
+        format_ptr,
+        move_operation,
+        remove_operation,
+        replace_operation,
+        test_operation,
+    };
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Recorder {
+        matched: Vec<PointerBuf>,
+        writes: Vec<(PointerBuf, Option<Value>, Option<Value>)>,
+        skipped: usize,
+        errors: usize,
+    }
+
+    impl PatchObserver for Recorder {
+        fn on_match(&mut self, path: &Pointer) {
+            self.matched.push(path.to_buf());
+        }
+
+        fn on_write(&mut self, path: &Pointer, old: Option<&Value>, new: Option<&Value>) {
+            self.writes.push((path.to_buf(), old.cloned(), new.cloned()));
+        }
+
+        fn on_skip(&mut self, _op: &PatchOperation) {
+            self.skipped += 1;
+        }
+
+        fn on_error(&mut self, _err: &PatchError) {
+            self.errors += 1;
+        }
+    }
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"foo": [{"bar": 1}, {"bar": 2}], "baz": "x"})
+    }
+
+    #[rstest]
+    fn test_patch_ext_observed(mut data: Value) {
+        let mut rec = Recorder::default();
+        assert_ok!(patch_ext_observed(&mut data, replace_operation(format_ptr!("/foo/*/bar"), json!(3)), &mut rec));
+        assert_eq!(
+            rec,
+            Recorder {
+                matched: vec![format_ptr!("/foo/0/bar"), format_ptr!("/foo/1/bar")],
+                writes: vec![
+                    (format_ptr!("/foo/0/bar"), Some(json!(1)), Some(json!(3))),
+                    (format_ptr!("/foo/1/bar"), Some(json!(2)), Some(json!(3))),
+                ],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[rstest]
+    fn test_patch_ext_observed_move(mut data: Value) {
+        let mut rec = Recorder::default();
+        assert_ok!(patch_ext_observed(&mut data, move_operation(format_ptr!("/baz"), format_ptr!("/qux")), &mut rec));
+        assert_eq!(rec.matched, vec![format_ptr!("/baz")]);
+        assert_eq!(
+            rec.writes,
+            vec![(format_ptr!("/baz"), Some(json!("x")), None), (format_ptr!("/qux"), None, Some(json!("x")))]
+        );
+    }
+
+    #[rstest]
+    #[case::remove(remove_operation(format_ptr!("/missing/*")), 1)]
+    #[case::test(test_operation(format_ptr!("/baz"), json!("x")), 0)]
+    fn test_patch_ext_observed_skip(mut data: Value, #[case] op: PatchOperation, #[case] skipped: usize) {
+        let mut rec = Recorder::default();
+        assert_ok!(patch_ext_observed(&mut data, op, &mut rec));
+        assert_eq!(rec.skipped, skipped);
+        assert_is_empty!(rec.writes);
+    }
+
+    #[rstest]
+    fn test_patch_ext_observed_error(mut data: Value) {
+        let orig = data.clone();
+        let mut rec = Recorder::default();
+        let err = assert_err!(patch_ext_observed(
+            &mut data,
+            replace_operation(format_ptr!("/foo/*/bar/x"), json!(3)),
+            &mut rec
+        ));
+        assert_matches!(err.kind(), PatchErrorKind::UnexpectedType);
+        assert_eq!(rec.errors, 1);
+        assert_is_empty!(rec.writes);
+        assert_eq!(data, orig);
+    }
+}
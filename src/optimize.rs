@@ -0,0 +1,299 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use alloc::{
+    format,
+    vec,
+};
+
+use json_patch::{
+    AddOperation,
+    RemoveOperation,
+    ReplaceOperation,
+};
+
+use crate::{
+    Patch,
+    PatchOperation,
+    Pointer,
+    PointerBuf,
+    Token,
+    Wildcard,
+    capture_ref,
+    is_index,
+    patch_ext,
+};
+
+// Simplify a patch by merging redundant operations: an add or replace followed by a replace of the
+// same path becomes a single operation, operations that only modify values inside of a path that's
+// removed later are dropped, and operations inside of a value that was added (or replaced) earlier
+// in the patch are folded into that value.  Wildcard paths are also canonicalized (e.g., `/**/**`
+// becomes `/**`).  The optimized patch has the same effect as the original on any document that the
+// original applies to cleanly; it may also succeed on some documents where the original would fail.
+pub fn optimize_patch(p: &Patch) -> Patch {
+    let mut ops: Vec<PatchOperation> = vec![];
+    for op in p.iter() {
+        let op = canonicalize(op);
+        if let PatchOperation::Remove(RemoveOperation { path }) = &op {
+            drop_shadowed(&mut ops, path);
+        }
+        if !fold(&mut ops, &op) {
+            ops.push(op);
+        }
+    }
+    Patch(ops)
+}
+
+// Move and copy operations are left alone, since their wildcards have to line up between `from`
+// and `path`
+fn canonicalize(op: &PatchOperation) -> PatchOperation {
+    let mut op = op.clone();
+    match &mut op {
+        PatchOperation::Add(AddOperation { path, .. })
+        | PatchOperation::Remove(RemoveOperation { path })
+        | PatchOperation::Replace(ReplaceOperation { path, .. })
+        | PatchOperation::Test(json_patch::TestOperation { path, .. }) => *path = canonical_path(path),
+        PatchOperation::Move(_) | PatchOperation::Copy(_) => (),
+    }
+    op
+}
+
+// Collapse consecutive recursive wildcards into one, and drop the (redundant) start of a slice that
+// begins at 0
+fn canonical_path(path: &Pointer) -> PointerBuf {
+    let mut res = PointerBuf::new();
+    let mut prev_recursive = false;
+    for t in path.tokens() {
+        let wildcard = Wildcard::parse(&t);
+        match wildcard {
+            Some(Wildcard::Recursive) if prev_recursive => continue,
+            Some(Wildcard::Slice(Some(0), end)) => {
+                res.push_back(format!(":{}", end.map(|e| e.to_string()).unwrap_or_default()));
+            },
+            _ => res.push_back(t),
+        }
+        prev_recursive = matches!(wildcard, Some(Wildcard::Recursive));
+    }
+    res
+}
+
+// Drop the earlier operations that only modify values inside of `path`, since it's about to be
+// removed anyway; we can't look past an operation that might interact with `path` in some other
+// way.
+fn drop_shadowed(ops: &mut Vec<PatchOperation>, path: &Pointer) {
+    for k in (0..ops.len()).rev() {
+        match &ops[k] {
+            PatchOperation::Add(AddOperation { path: inner, .. })
+            | PatchOperation::Replace(ReplaceOperation { path: inner, .. })
+            | PatchOperation::Remove(RemoveOperation { path: inner })
+                if is_strictly_inside(inner, path) =>
+            {
+                ops.remove(k);
+            },
+            op if overlaps(op, path) => return,
+            _ => (),
+        }
+    }
+}
+
+// Try to fold an add, replace, or remove into the value of an earlier add or replace whose path
+// contains it; returns true if the operation was folded (and so can be dropped).
+fn fold(ops: &mut [PatchOperation], op: &PatchOperation) -> bool {
+    let path = match op {
+        PatchOperation::Add(AddOperation { path, .. })
+        | PatchOperation::Replace(ReplaceOperation { path, .. })
+        | PatchOperation::Remove(RemoveOperation { path }) => path,
+        _ => return false,
+    };
+
+    for prev in ops.iter_mut().rev() {
+        if let PatchOperation::Add(AddOperation { path: parent, value })
+        | PatchOperation::Replace(ReplaceOperation { path: parent, value }) = prev
+            && is_plain(parent)
+            && path.starts_with(parent)
+        {
+            return fold_into(parent, value, op);
+        }
+        if overlaps(prev, path) {
+            return false;
+        }
+    }
+    false
+}
+
+fn fold_into(parent: &Pointer, value: &mut serde_json::Value, op: &PatchOperation) -> bool {
+    let Some(rel) = op.path().strip_prefix(parent) else {
+        return false;
+    };
+
+    // The same path: a replace always overwrites the earlier value, but an add only does if it
+    // isn't inserting into an array
+    if rel.is_root() {
+        return match op {
+            PatchOperation::Replace(ReplaceOperation { value: new, .. }) => {
+                *value = new.clone();
+                true
+            },
+            PatchOperation::Add(AddOperation { value: new, .. }) if !parent.last().is_some_and(|t| is_index(&t)) => {
+                *value = new.clone();
+                true
+            },
+            _ => false,
+        };
+    }
+
+    let rel_op = match op {
+        PatchOperation::Add(op) => PatchOperation::Add(AddOperation { path: rel.to_buf(), value: op.value.clone() }),
+        PatchOperation::Replace(op) => {
+            PatchOperation::Replace(ReplaceOperation { path: rel.to_buf(), value: op.value.clone() })
+        },
+        PatchOperation::Remove(_) => PatchOperation::Remove(RemoveOperation { path: rel.to_buf() }),
+        _ => return false,
+    };
+    let mut folded = value.clone();
+    if patch_ext(&mut folded, rel_op).is_err() {
+        return false;
+    }
+    *value = folded;
+    true
+}
+
+// Whether any of the paths that the operation reads or writes could overlap with `path`
+pub(crate) fn overlaps(op: &PatchOperation, path: &Pointer) -> bool {
+    match op {
+        PatchOperation::Move(op) => paths_overlap(&op.from, path) || paths_overlap(&op.path, path),
+        PatchOperation::Copy(op) => paths_overlap(&op.from, path) || paths_overlap(&op.path, path),
+        op => paths_overlap(op.path(), path),
+    }
+}
+
+// Two paths overlap if one contains the other, or if they could reference elements of the same
+// array (since adding or removing one of them shifts the indices of the others); we can't tell what
+// a wildcard will match, so any path with a wildcard overlaps with everything.
+fn paths_overlap(a: &Pointer, b: &Pointer) -> bool {
+    if has_wildcard(a) || has_wildcard(b) {
+        return true;
+    }
+    for (x, y) in a.tokens().zip(b.tokens()) {
+        if x != y {
+            return is_index_like(&x) && is_index_like(&y);
+        }
+    }
+    true
+}
+
+pub(crate) fn is_strictly_inside(inner: &Pointer, outer: &Pointer) -> bool {
+    !has_wildcard(outer) && inner != outer && inner.starts_with(outer)
+}
+
+// A path whose meaning doesn't depend on the length of any array along the way, so an operation on
+// a path inside of it always refers to the same value
+fn is_plain(path: &Pointer) -> bool {
+    path.tokens().all(|t| Wildcard::parse(&t).is_none() && !t.decoded().starts_with('-'))
+}
+
+fn is_index_like(t: &Token) -> bool {
+    is_index(t) || t.decoded().strip_prefix('-').is_some_and(|n| n.parse::<usize>().is_ok())
+}
+
+pub(crate) fn has_wildcard(path: &Pointer) -> bool {
+    path.tokens().any(|t| Wildcard::parse(&t).is_some() || capture_ref(&t).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::{
+        Value,
+        json,
+    };
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        add_operation,
+        copy_operation,
+        format_ptr,
+        patch_ext_all,
+        remove_operation,
+        replace_operation,
+        test_operation,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"foo": [{"bar": 1}, {"bar": 2}], "baz": {"qux": 3}})
+    }
+
+    #[rstest]
+    #[case::add_then_replace(
+        vec![add_operation(format_ptr!("/new"), json!(1)), replace_operation(format_ptr!("/new"), json!(2))],
+        vec![add_operation(format_ptr!("/new"), json!(2))],
+    )]
+    #[case::add_children(
+        vec![
+            add_operation(format_ptr!("/new"), json!({})),
+            add_operation(format_ptr!("/new/a"), json!(1)),
+            add_operation(format_ptr!("/new/b"), json!(2)),
+        ],
+        vec![add_operation(format_ptr!("/new"), json!({"a": 1, "b": 2}))],
+    )]
+    #[case::remove_child(
+        vec![replace_operation(format_ptr!("/baz"), json!({"a": 1, "b": 2})), remove_operation(format_ptr!("/baz/a"))],
+        vec![replace_operation(format_ptr!("/baz"), json!({"b": 2}))],
+    )]
+    #[case::remove_parent(
+        vec![
+            remove_operation(format_ptr!("/baz/qux")),
+            add_operation(format_ptr!("/baz/new"), json!(1)),
+            add_operation(format_ptr!("/other"), json!(1)),
+            remove_operation(format_ptr!("/baz")),
+        ],
+        vec![add_operation(format_ptr!("/other"), json!(1)), remove_operation(format_ptr!("/baz"))],
+    )]
+    #[case::canonical_paths(
+        vec![remove_operation(format_ptr!("/**/**/bar")), test_operation(format_ptr!("/foo/0:1"), json!(1))],
+        vec![remove_operation(format_ptr!("/**/bar")), test_operation(format_ptr!("/foo/:1"), json!(1))],
+    )]
+    fn test_optimize_patch(data: Value, #[case] ops: Vec<PatchOperation>, #[case] expected: Vec<PatchOperation>) {
+        let optimized = optimize_patch(&Patch(ops.clone()));
+        assert_eq!(optimized, Patch(expected));
+
+        // Test operations aren't expected to pass here, so only compare the results of the others
+        let ops: Vec<_> = ops.into_iter().filter(|op| !matches!(op, PatchOperation::Test(_))).collect();
+        let optimized: Vec<_> = optimized.0.into_iter().filter(|op| !matches!(op, PatchOperation::Test(_))).collect();
+        let (mut orig, mut opt) = (data.clone(), data);
+        assert_ok!(patch_ext_all(&mut orig, &Patch(ops)));
+        assert_ok!(patch_ext_all(&mut opt, &Patch(optimized)));
+        assert_eq!(orig, opt);
+    }
+
+    #[rstest]
+    #[case::intervening_copy(vec![
+        add_operation(format_ptr!("/new"), json!(1)),
+        copy_operation(format_ptr!("/new"), format_ptr!("/copy")),
+        replace_operation(format_ptr!("/new"), json!(2)),
+    ])]
+    #[case::intervening_test(vec![
+        remove_operation(format_ptr!("/baz/qux")),
+        test_operation(format_ptr!("/baz"), json!({})),
+        remove_operation(format_ptr!("/baz")),
+    ])]
+    #[case::array_insert(vec![
+        add_operation(format_ptr!("/foo/0"), json!(1)),
+        add_operation(format_ptr!("/foo/0"), json!(2)),
+    ])]
+    #[case::array_sibling(vec![
+        add_operation(format_ptr!("/foo/1"), json!({})),
+        remove_operation(format_ptr!("/foo/0")),
+        add_operation(format_ptr!("/foo/1/bar"), json!(3)),
+    ])]
+    #[case::wildcard(vec![
+        add_operation(format_ptr!("/new"), json!({})),
+        add_operation(format_ptr!("/foo/*/bar"), json!(1)),
+        add_operation(format_ptr!("/new/baz"), json!(2)),
+    ])]
+    fn test_optimize_patch_unchanged(#[case] ops: Vec<PatchOperation>) {
+        assert_eq!(optimize_patch(&Patch(ops.clone())), Patch(ops));
+    }
+}
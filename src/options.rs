@@ -0,0 +1,390 @@
+use alloc::collections::BTreeMap;
+
+use crate::{
+    PatchErrorKind,
+    PointerBuf,
+};
+
+// PatchOptions controls how strictly operations are applied by `patch_ext_with`; the defaults
+// match the behavior of `patch_ext`.
+#[derive(Clone, Debug)]
+pub struct PatchOptions {
+    // Create any missing parents of the target path in an add operation (including an empty array
+    // for a missing path that is followed by a wildcard), instead of returning an error.
+    pub allow_autocreate_parents: bool,
+
+    // When auto-creating parents, create an array (instead of an object) for any missing parent
+    // whose child is referenced by an array index or `-`; e.g., adding `/foo/0/bar` to `{}`
+    // results in `{"foo": [{"bar": ...}]}`.
+    pub autocreate_arrays_for_numeric_tokens: bool,
+
+    // Silently skip any part of the path in a remove operation that doesn't exist, instead of
+    // returning an error.
+    pub skip_missing_on_remove: bool,
+
+    // Return an error if a wildcard is applied to an empty array (or object), instead of doing
+    // nothing.
+    pub wildcard_on_empty_array_is_error: bool,
+
+    // Return a `TooManyMatches` error if a wildcard path matches more than this many values,
+    // instead of applying the operation; useful when applying untrusted patches.
+    pub max_matches: Option<usize>,
+
+    // Return a `MaxDepthExceeded` error if a recursive wildcard would need to descend more than
+    // this many levels into the document (counting every `**` in the path); e.g., `/**/foo` needs
+    // a depth of 3 to visit `/a/b/foo`.
+    pub max_depth: Option<usize>,
+
+    // Skip any add or replace that would write a value equal to the one that's already there
+    // (e.g., replacing `/foo` with `1` when it's already `1`), so that it isn't reported as a
+    // modification.  Adding to an array always inserts a new element, so it's never skipped.
+    pub skip_unchanged_writes: bool,
+
+    // The kind of container to create for specific missing parents, keyed by their paths (which
+    // can contain wildcards, e.g. `/spec/*/ports`); any missing parent that isn't listed here is
+    // created as usual.
+    pub container_hints: BTreeMap<PointerBuf, ContainerKind>,
+
+    // Return an `AutocreateLimitExceeded` error if an add operation would need to create more than
+    // this many missing parents (for any one of its targets), e.g. because of a typo in the path.
+    pub max_autocreate_depth: Option<usize>,
+
+    // Return a `PointerTooDeep` error if any path in an operation (including the `from` of a move
+    // or copy, and the path of a test) has more than this many segments, before applying it.
+    pub max_pointer_depth: Option<usize>,
+
+    // Return an `IndexTooLarge` error if an add operation would insert an array element at an
+    // index greater than this (including appending with `-`, or when creating missing parents),
+    // which bounds the length of any array that a patch can grow.
+    pub max_array_index: Option<usize>,
+
+    // Return a `SizeLimitExceeded` error (and roll back the patch) if applying it would grow the
+    // document by more than this many bytes, measured as the approximate size of its compact JSON
+    // serialization.  This is checked after every operation (by measuring only the values that it
+    // modified), so an operation that copies a large value many times fails as soon as it's
+    // applied.  Enforced by `patch_ext_all_with` and `patch_ext_with`.
+    pub max_size_growth: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ContainerKind {
+    Array,
+    Object,
+}
+
+impl Default for PatchOptions {
+    fn default() -> PatchOptions {
+        PatchOptions {
+            allow_autocreate_parents: true,
+            autocreate_arrays_for_numeric_tokens: false,
+            skip_missing_on_remove: true,
+            wildcard_on_empty_array_is_error: false,
+            max_matches: None,
+            max_depth: None,
+            skip_unchanged_writes: false,
+            container_hints: BTreeMap::new(),
+            max_autocreate_depth: None,
+            max_pointer_depth: None,
+            max_array_index: None,
+            max_size_growth: None,
+        }
+    }
+}
+
+// ErrorPolicy controls which kinds of errors `patch_ext_lenient` skips over (rolling back just the
+// failed operation) instead of aborting the whole patch; any other kind of error (e.g., a failed
+// test, or an invalid path) always aborts.  By default, all of these errors are skipped.
+#[derive(Clone, Debug)]
+pub struct ErrorPolicy {
+    // Skip operations whose target (or source) doesn't exist in the document.
+    pub skip_missing_target: bool,
+
+    // Skip operations that encounter a value of the wrong type, e.g., an array index into an
+    // object, or incrementing a string.
+    pub skip_type_mismatch: bool,
+
+    // Skip operations that reference an array index past the end of the array.
+    pub skip_out_of_bounds: bool,
+}
+
+impl ErrorPolicy {
+    // Whether an error of this kind should be skipped
+    pub fn skips(&self, kind: &PatchErrorKind) -> bool {
+        match kind {
+            PatchErrorKind::TargetDoesNotExist
+            | PatchErrorKind::JsonPatchError(
+                json_patch::PatchErrorKind::InvalidPointer | json_patch::PatchErrorKind::InvalidFromPointer,
+            ) => self.skip_missing_target,
+            PatchErrorKind::ResolveError(err) if err.is_not_found() => self.skip_missing_target,
+            PatchErrorKind::UnexpectedType => self.skip_type_mismatch,
+            PatchErrorKind::ResolveError(err) if err.is_unreachable() => self.skip_type_mismatch,
+            PatchErrorKind::OutOfBounds(_) | PatchErrorKind::NegativeIndexOutOfBounds(_) => self.skip_out_of_bounds,
+            PatchErrorKind::ResolveError(err) if err.is_out_of_bounds() => self.skip_out_of_bounds,
+            _ => false,
+        }
+    }
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> ErrorPolicy {
+        ErrorPolicy {
+            skip_missing_target: true,
+            skip_type_mismatch: true,
+            skip_out_of_bounds: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::{
+        Value,
+        json,
+    };
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        Patch,
+        PatchErrorKind,
+        PatchOperation,
+        PointerBuf,
+        add_operation,
+        copy_operation,
+        format_ptr,
+        move_operation,
+        patch_ext_all_with,
+        patch_ext_with,
+        remove_operation,
+        test_operation,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"foo": [{"bar": 1}, {"baz": 2}], "empty": []})
+    }
+
+    #[rstest]
+    #[case::add(add_operation(format_ptr!("/foo/*/qux"), json!(3)))]
+    #[case::add_new_parent(add_operation(format_ptr!("/new/qux"), json!(3)))]
+    #[case::add_wildcard_new_parent(add_operation(format_ptr!("/new/*/qux"), json!(3)))]
+    #[case::remove_missing(remove_operation(format_ptr!("/foo/*/bar")))]
+    #[case::remove_missing_parent(remove_operation(format_ptr!("/new/*/qux")))]
+    #[case::empty_wildcard(add_operation(format_ptr!("/empty/*/qux"), json!(3)))]
+    fn test_default_options(mut data: Value, #[case] op: PatchOperation) {
+        assert_ok!(patch_ext_with(&mut data, op, &PatchOptions::default()));
+    }
+
+    #[rstest]
+    #[case::no_autocreate(
+        add_operation(format_ptr!("/new/qux"), json!(3)),
+        PatchOptions { allow_autocreate_parents: false, ..Default::default() },
+        format_ptr!("/new"),
+    )]
+    #[case::no_autocreate_wildcard(
+        add_operation(format_ptr!("/new/*/qux"), json!(3)),
+        PatchOptions { allow_autocreate_parents: false, ..Default::default() },
+        format_ptr!("/new"),
+    )]
+    #[case::remove_missing(
+        remove_operation(format_ptr!("/foo/*/bar")),
+        PatchOptions { skip_missing_on_remove: false, ..Default::default() },
+        format_ptr!("/foo/1/bar"),
+    )]
+    #[case::remove_missing_parent(
+        remove_operation(format_ptr!("/new/*/qux")),
+        PatchOptions { skip_missing_on_remove: false, ..Default::default() },
+        format_ptr!("/new"),
+    )]
+    #[case::empty_wildcard(
+        add_operation(format_ptr!("/empty/*/qux"), json!(3)),
+        PatchOptions { wildcard_on_empty_array_is_error: true, ..Default::default() },
+        format_ptr!("/empty"),
+    )]
+    #[case::empty_wildcard_new_parent(
+        add_operation(format_ptr!("/new/*/qux"), json!(3)),
+        PatchOptions { wildcard_on_empty_array_is_error: true, ..Default::default() },
+        format_ptr!("/new"),
+    )]
+    fn test_strict_options(
+        mut data: Value,
+        #[case] op: PatchOperation,
+        #[case] opts: PatchOptions,
+        #[case] path: PointerBuf,
+    ) {
+        let err = assert_err!(patch_ext_with(&mut data, op, &opts));
+        assert_matches!(err.kind(), PatchErrorKind::TargetDoesNotExist);
+        assert_eq!(err.path(), path);
+    }
+
+    #[rstest]
+    #[case::index(format_ptr!("/foo/0/bar"), json!({"foo": [{"bar": 1}]}))]
+    #[case::next(format_ptr!("/foo/-/bar"), json!({"foo": [{"bar": 1}]}))]
+    #[case::leaf(format_ptr!("/foo/0"), json!({"foo": [1]}))]
+    #[case::leaf_next(format_ptr!("/foo/bar/-"), json!({"foo": {"bar": [1]}}))]
+    #[case::nested(format_ptr!("/foo/0/0"), json!({"foo": [[1]]}))]
+    #[case::key(format_ptr!("/foo/bar"), json!({"foo": {"bar": 1}}))]
+    fn test_autocreate_arrays(#[case] path: PointerBuf, #[case] expected: Value) {
+        let opts = PatchOptions {
+            autocreate_arrays_for_numeric_tokens: true,
+            ..Default::default()
+        };
+        let mut data = json!({});
+        assert_ok!(patch_ext_with(&mut data, add_operation(path, json!(1)), &opts));
+        assert_eq!(data, expected);
+    }
+
+    #[rstest]
+    fn test_autocreate_arrays_existing(mut data: Value) {
+        let opts = PatchOptions {
+            autocreate_arrays_for_numeric_tokens: true,
+            ..Default::default()
+        };
+        assert_ok!(patch_ext_with(&mut data, add_operation(format_ptr!("/foo/-/qux/0"), json!(3)), &opts));
+        assert_eq!(data, json!({"foo": [{"bar": 1}, {"baz": 2}, {"qux": [3]}], "empty": []}));
+
+        let err = patch_ext_with(&mut data, add_operation(format_ptr!("/empty/1/qux"), json!(3)), &opts).unwrap_err();
+        assert_matches!(err.kind(), PatchErrorKind::OutOfBounds(1));
+    }
+
+    #[rstest]
+    fn test_autocreate_objects_by_default() {
+        let mut data = json!({});
+        assert_ok!(patch_ext_with(&mut data, add_operation(format_ptr!("/foo/0"), json!(1)), &PatchOptions::default()));
+        assert_eq!(data, json!({"foo": {"0": 1}}));
+    }
+
+    #[rstest]
+    #[case::add(add_operation(format_ptr!("/foo/*/qux"), json!(3)))]
+    #[case::remove(remove_operation(format_ptr!("/foo/*")))]
+    #[case::recursive(remove_operation(format_ptr!("/**/bar")))]
+    #[case::test(test_operation(format_ptr!("/foo/*/bar"), json!(1)))]
+    fn test_max_matches(mut data: Value, #[case] op: PatchOperation) {
+        let opts = PatchOptions { max_matches: Some(2), ..Default::default() };
+        assert_ok!(patch_ext_with(&mut data, op.clone(), &opts));
+
+        let mut data = json!({"foo": [{"bar": 1}, {"bar": 1}, {"bar": 1}]});
+        let orig = data.clone();
+        let err = assert_err!(patch_ext_with(&mut data, op, &opts));
+        assert_matches!(err.kind(), PatchErrorKind::TooManyMatches(2));
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    #[case::array(format_ptr!("/new/items"), ContainerKind::Array, format_ptr!("/new/items/-"), json!({"items": [1]}))]
+    #[case::object(format_ptr!("/new"), ContainerKind::Object, format_ptr!("/new/0"), json!({"0": 1}))]
+    #[case::leaf(format_ptr!("/new/a"), ContainerKind::Array, format_ptr!("/new/a/0"), json!({"a": [1]}))]
+    #[case::wildcard(format_ptr!("/new/*"), ContainerKind::Array, format_ptr!("/new/a/-"), json!({"a": [1]}))]
+    #[case::unhinted(format_ptr!("/other"), ContainerKind::Array, format_ptr!("/new/x"), json!({"x": 1}))]
+    fn test_container_hints(
+        mut data: Value,
+        #[case] hinted: PointerBuf,
+        #[case] kind: ContainerKind,
+        #[case] path: PointerBuf,
+        #[case] expected: Value,
+    ) {
+        let opts = PatchOptions {
+            container_hints: BTreeMap::from([(hinted, kind)]),
+            ..Default::default()
+        };
+        assert_ok!(patch_ext_with(&mut data, add_operation(path, json!(1)), &opts));
+        assert_eq!(data["new"], expected);
+    }
+
+    #[rstest]
+    fn test_max_autocreate_depth(mut data: Value) {
+        let opts = PatchOptions {
+            max_autocreate_depth: Some(2),
+            ..Default::default()
+        };
+        assert_ok!(patch_ext_with(&mut data, add_operation(format_ptr!("/foo/0/a/b/c"), json!(1)), &opts));
+        assert_eq!(data["foo"][0]["a"], json!({"b": {"c": 1}}));
+
+        let orig = data.clone();
+        let err = assert_err!(patch_ext_with(&mut data, add_operation(format_ptr!("/foo/*/x/y/w/z"), json!(1)), &opts));
+        assert_matches!(err.kind(), PatchErrorKind::AutocreateLimitExceeded(2));
+        assert_eq!(err.path(), format_ptr!("/foo/0/x/y/w"));
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    #[case::add(add_operation(format_ptr!("/foo/0/qux/x"), json!(1)), format_ptr!("/foo/0/qux/x"))]
+    #[case::move_from(move_operation(format_ptr!("/foo/0/bar/x"), format_ptr!("/x")), format_ptr!("/foo/0/bar/x"))]
+    #[case::copy_from(copy_operation(format_ptr!("/foo/0/bar/x"), format_ptr!("/x")), format_ptr!("/foo/0/bar/x"))]
+    #[case::test(test_operation(format_ptr!("/foo/0/bar/x"), json!(1)), format_ptr!("/foo/0/bar/x"))]
+    fn test_max_pointer_depth(mut data: Value, #[case] op: PatchOperation, #[case] path: PointerBuf) {
+        let opts = PatchOptions { max_pointer_depth: Some(3), ..Default::default() };
+        assert_ok!(patch_ext_with(&mut data, add_operation(format_ptr!("/foo/0/qux"), json!(1)), &opts));
+
+        let orig = data.clone();
+        let err = assert_err!(patch_ext_with(&mut data, op, &opts));
+        assert_matches!(err.kind(), PatchErrorKind::PointerTooDeep(3));
+        assert_eq!(err.path(), path);
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    #[case::append(format_ptr!("/foo/-"), 2)]
+    #[case::insert(format_ptr!("/foo/2"), 2)]
+    #[case::autocreate(format_ptr!("/foo/-/bar"), 2)]
+    fn test_max_array_index(mut data: Value, #[case] path: PointerBuf, #[case] idx: usize) {
+        let opts = PatchOptions { max_array_index: Some(1), ..Default::default() };
+        assert_ok!(patch_ext_with(&mut data, add_operation(format_ptr!("/foo/1"), json!(0)), &opts));
+        assert_ok!(patch_ext_with(&mut data, add_operation(format_ptr!("/empty/-"), json!(0)), &opts));
+        assert_ok!(patch_ext_with(&mut data, remove_operation(format_ptr!("/foo/1")), &opts));
+
+        let orig = data.clone();
+        let err = assert_err!(patch_ext_with(&mut data, add_operation(path, json!(0)), &opts));
+        assert_matches!(err.kind(), PatchErrorKind::IndexTooLarge(i) if *i == idx);
+        assert_eq!(err.path(), format_ptr!("/foo"));
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_max_size_growth(mut data: Value) {
+        let opts = PatchOptions { max_size_growth: Some(20), ..Default::default() };
+        let orig = data.clone();
+        let p = Patch(vec![
+            add_operation(format_ptr!("/a"), json!("x")),
+            copy_operation(format_ptr!("/foo"), format_ptr!("/copy")),
+        ]);
+        let err = assert_err!(patch_ext_all_with(&mut data, &p, &opts));
+        assert_matches!(err.kind(), PatchErrorKind::SizeLimitExceeded(20));
+        assert_eq!(err.operation_index(), Some(1));
+        assert_eq!(data, orig);
+
+        // Growth is measured over the whole patch, so shrinking the document makes room for more
+        let p = Patch(vec![
+            remove_operation(format_ptr!("/foo")),
+            copy_operation(format_ptr!("/empty"), format_ptr!("/copy")),
+            add_operation(format_ptr!("/a"), json!("x")),
+        ]);
+        assert_ok!(patch_ext_all_with(&mut data, &p, &opts));
+
+        let orig = data.clone();
+        let err = assert_err!(patch_ext_with(
+            &mut data,
+            add_operation(format_ptr!("/b"), json!("a string that is too long")),
+            &opts
+        ));
+        assert_matches!(err.kind(), PatchErrorKind::SizeLimitExceeded(20));
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_max_depth() {
+        let mut data = json!({"a": {"b": {"c": {"d": 1}}}});
+        let op = remove_operation(format_ptr!("/**/d"));
+
+        let opts = PatchOptions { max_depth: Some(3), ..Default::default() };
+        let err = assert_err!(patch_ext_with(&mut data, op.clone(), &opts));
+        assert_matches!(err.kind(), PatchErrorKind::MaxDepthExceeded(3));
+        assert_eq!(err.path(), format_ptr!("/a/b/c"));
+
+        let opts = PatchOptions { max_depth: Some(4), ..Default::default() };
+        assert_ok!(patch_ext_with(&mut data, op, &opts));
+        assert_eq!(data, json!({"a": {"b": {"c": {}}}}));
+    }
+}
@@ -0,0 +1,323 @@
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::optimize::{
+    has_wildcard,
+    is_strictly_inside,
+    overlaps,
+};
+use crate::{
+    AddOperation,
+    CopyOperation,
+    MoveOperation,
+    Operation,
+    Patch,
+    PatchError,
+    PatchErrorKind,
+    PatchOperation,
+    Pointer,
+    RemoveOperation,
+    ReplaceOperation,
+    TestOperation,
+    array_index,
+};
+
+// Reorder the operations in a patch whose paths were all computed against the same (original)
+// document, e.g., a patch assembled from several independently generated ones: parents are created
+// before anything is added inside of them, values are read before they're removed, and operations
+// on an array element run before anything that shifts that element's index (so removing `/foo/0`
+// and `/foo/1` removes them in reverse order).  Operations that don't depend on each other keep
+// their original relative order, as do any overlapping operations whose paths contain wildcards.
+// Returns a ConflictingOperation error if two of the operations can't both be applied in any order,
+// e.g., if one of them writes inside of a value that the other one removes.
+pub fn order_patch(p: &Patch) -> Result<Patch, PatchError> {
+    let n = p.len();
+    let mut preds = vec![vec![]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let conflict = || {
+                PatchError::new(PatchErrorKind::ConflictingOperation(i), p[j].path())
+                    .with_operation(p[j].kind())
+                    .with_operation_index(j)
+            };
+            match relation(&p[i], &p[j]).ok_or_else(conflict)? {
+                Some(true) => preds[j].push(i),
+                Some(false) => preds[i].push(j),
+                None => (),
+            }
+        }
+    }
+
+    // A topological sort that always picks the earliest operation that's ready, to keep the
+    // original order wherever possible
+    let mut remaining: Vec<usize> = preds.iter().map(Vec::len).collect();
+    let mut succs = vec![vec![]; n];
+    for (j, ps) in preds.iter().enumerate() {
+        for &i in ps {
+            succs[i].push(j);
+        }
+    }
+    let mut ready: BTreeSet<usize> = (0..n).filter(|&i| remaining[i] == 0).collect();
+    let mut ops = vec![];
+    while let Some(i) = ready.pop_first() {
+        ops.push(p[i].clone());
+        for &j in &succs[i] {
+            remaining[j] -= 1;
+            if remaining[j] == 0 {
+                ready.insert(j);
+            }
+        }
+    }
+
+    // Anything left over is part of a cycle
+    if let Some(j) = (0..n).find(|&j| remaining[j] > 0) {
+        let i = preds[j].iter().copied().find(|&i| remaining[i] > 0).unwrap_or(j);
+        return Err(PatchError::new(PatchErrorKind::ConflictingOperation(i), p[j].path())
+            .with_operation(p[j].kind())
+            .with_operation_index(j));
+    }
+    Ok(Patch(ops))
+}
+
+// Whether `a` has to be applied before `b` (Some(true)) or after it (Some(false)), or whether it
+// doesn't matter (None); returns None if they conflict.
+fn relation(a: &PatchOperation, b: &PatchOperation) -> Option<Option<bool>> {
+    let overlapping = paths(b).into_iter().any(|q| overlaps(a, q));
+    if paths(a).into_iter().chain(paths(b)).any(has_wildcard) {
+        return Some(overlapping.then_some(true));
+    }
+
+    let mut dirs = vec![];
+    constraints(a, b, &mut dirs)?;
+    let n = dirs.len();
+    constraints(b, a, &mut dirs)?;
+    for d in &mut dirs[n..] {
+        *d = !*d;
+    }
+
+    match (dirs.contains(&true), dirs.contains(&false)) {
+        (true, true) => None,
+        (true, false) => Some(Some(true)),
+        (false, true) => Some(Some(false)),
+        (false, false) => Some(overlapping.then_some(true)),
+    }
+}
+
+// Collect the constraints that `x` places on the order of `x` and `y` (true if `x` must come
+// first); returns None if they conflict.
+fn constraints(x: &PatchOperation, y: &PatchOperation, dirs: &mut Vec<bool>) -> Option<()> {
+    // `x` creates (or replaces) a parent of something that `y` references
+    if let Some(d) = dest(x)
+        && paths(y).into_iter().any(|q| is_strictly_inside(q, d))
+    {
+        dirs.push(true);
+    }
+
+    // `x` removes something that `y` references: reads have to happen first, and writes conflict;
+    // inserting a new array element at the same index doesn't touch the removed one
+    if let Some(r) = removed(x) {
+        for (q, write) in accesses(y) {
+            if is_strictly_inside(q, r) || (q == r && !is_insert(y, q)) {
+                if write {
+                    return None;
+                }
+                dirs.push(false);
+            }
+        }
+    }
+
+    // Two different values written to the same location
+    if let (Some(v), Some(w)) = (written_value(x), written_value(y))
+        && dest(x) == dest(y)
+        && v != w
+    {
+        return None;
+    }
+
+    // `x` shifts the indices of some array elements, so anything that references those elements
+    // (by their original index) has to come first
+    for (path, insert) in shifts(x) {
+        let (Some(parent), Some(k)) = (path.parent(), path.last().and_then(|t| array_index(&t))) else {
+            continue;
+        };
+        for q in paths(y) {
+            let Some(m) = q.strip_prefix(parent).and_then(|rel| rel.first()).and_then(|t| array_index(&t)) else {
+                continue;
+            };
+            if m > k || (m == k && q == path && insert && !is_insert(y, q)) {
+                dirs.push(false);
+            } else if m == k && q == path && !insert && is_insert(y, q) {
+                dirs.push(true);
+            }
+        }
+    }
+    Some(())
+}
+
+fn paths(op: &PatchOperation) -> Vec<&Pointer> {
+    match op {
+        PatchOperation::Move(MoveOperation { from, path }) | PatchOperation::Copy(CopyOperation { from, path }) => {
+            vec![from, path]
+        },
+        op => vec![op.path()],
+    }
+}
+
+// Every path that the operation references, and whether it's written to
+fn accesses(op: &PatchOperation) -> Vec<(&Pointer, bool)> {
+    match op {
+        PatchOperation::Move(MoveOperation { from, path }) => vec![(from, true), (path, true)],
+        PatchOperation::Copy(CopyOperation { from, path }) => vec![(from, false), (path, true)],
+        PatchOperation::Test(TestOperation { path, .. }) => vec![(path, false)],
+        op => vec![(op.path(), true)],
+    }
+}
+
+// The location that the operation writes a new value to
+fn dest(op: &PatchOperation) -> Option<&Pointer> {
+    match op {
+        PatchOperation::Add(AddOperation { path, .. })
+        | PatchOperation::Replace(ReplaceOperation { path, .. })
+        | PatchOperation::Move(MoveOperation { path, .. })
+        | PatchOperation::Copy(CopyOperation { path, .. }) => Some(path),
+        _ => None,
+    }
+}
+
+fn removed(op: &PatchOperation) -> Option<&Pointer> {
+    match op {
+        PatchOperation::Remove(RemoveOperation { path }) | PatchOperation::Move(MoveOperation { from: path, .. }) => {
+            Some(path)
+        },
+        _ => None,
+    }
+}
+
+// The value that an add or replace writes (except for an add that inserts into an array, since
+// two of those at the same index don't overwrite each other)
+fn written_value(op: &PatchOperation) -> Option<&serde_json::Value> {
+    match op {
+        PatchOperation::Add(AddOperation { path, value }) if !is_insert(op, path) => Some(value),
+        PatchOperation::Replace(ReplaceOperation { value, .. }) => Some(value),
+        _ => None,
+    }
+}
+
+// The array locations where the operation removes (false) or inserts (true) an element
+fn shifts(op: &PatchOperation) -> Vec<(&Pointer, bool)> {
+    match op {
+        PatchOperation::Remove(RemoveOperation { path }) => vec![(path, false)],
+        PatchOperation::Move(MoveOperation { from, path }) => vec![(from, false), (path, true)],
+        PatchOperation::Add(AddOperation { path, .. }) | PatchOperation::Copy(CopyOperation { path, .. }) => {
+            vec![(path, true)]
+        },
+        _ => vec![],
+    }
+}
+
+// Whether the operation inserts a new element into an array at `path`; we can't tell whether the
+// parent is actually an array, but if it's an object, the order doesn't matter anyway.
+fn is_insert(op: &PatchOperation, path: &Pointer) -> bool {
+    shifts(op)
+        .into_iter()
+        .any(|(p, insert)| insert && p == path && p.last().is_some_and(|t| array_index(&t).is_some()))
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        add_operation,
+        copy_operation,
+        format_ptr,
+        patch_ext_all,
+        remove_operation,
+        replace_operation,
+        test_operation,
+    };
+
+    #[rstest]
+    #[case::parents(
+        vec![add_operation(format_ptr!("/a/b"), json!(1)), add_operation(format_ptr!("/a"), json!({}))],
+        vec![1, 0],
+    )]
+    #[case::removals(
+        vec![remove_operation(format_ptr!("/arr/0")), remove_operation(format_ptr!("/arr/2"))],
+        vec![1, 0],
+    )]
+    #[case::shifted_replace(
+        vec![remove_operation(format_ptr!("/arr/0")), replace_operation(format_ptr!("/arr/1/x"), json!(5))],
+        vec![1, 0],
+    )]
+    #[case::inserts(
+        vec![add_operation(format_ptr!("/arr/0"), json!(5)), add_operation(format_ptr!("/arr/2"), json!(6))],
+        vec![1, 0],
+    )]
+    #[case::remove_then_insert(
+        vec![add_operation(format_ptr!("/arr/1"), json!(5)), remove_operation(format_ptr!("/arr/1"))],
+        vec![1, 0],
+    )]
+    #[case::read_before_remove(
+        vec![remove_operation(format_ptr!("/obj")), copy_operation(format_ptr!("/obj/x"), format_ptr!("/y"))],
+        vec![1, 0],
+    )]
+    #[case::independent(
+        vec![remove_operation(format_ptr!("/obj")), add_operation(format_ptr!("/y"), json!(1))],
+        vec![0, 1],
+    )]
+    #[case::wildcard(
+        vec![remove_operation(format_ptr!("/arr/*/x")), remove_operation(format_ptr!("/arr/0"))],
+        vec![0, 1],
+    )]
+    fn test_order_patch(#[case] ops: Vec<PatchOperation>, #[case] order: Vec<usize>) {
+        let ordered = assert_ok!(order_patch(&Patch(ops.clone())));
+        assert_eq!(ordered, Patch(order.into_iter().map(|i| ops[i].clone()).collect()));
+    }
+
+    #[rstest]
+    fn test_order_patch_applies() {
+        // Every operation references the original document; applying them in the original order
+        // fails (the element at index 2 doesn't exist anymore by the time it's tested)
+        let mut data = json!({"arr": [{"x": 0}, {"x": 1}, {"x": 2}]});
+        let p = Patch(vec![
+            remove_operation(format_ptr!("/arr/0")),
+            add_operation(format_ptr!("/new/a"), json!(1)),
+            test_operation(format_ptr!("/arr/2/x"), json!(2)),
+            add_operation(format_ptr!("/new"), json!({})),
+            remove_operation(format_ptr!("/arr/1")),
+        ]);
+        assert_err!(patch_ext_all(&mut data.clone(), &p));
+
+        let ordered = assert_ok!(order_patch(&p));
+        assert_ok!(patch_ext_all(&mut data, &ordered));
+        assert_eq!(data, json!({"arr": [{"x": 2}], "new": {"a": 1}}));
+    }
+
+    #[rstest]
+    #[case::different_values(vec![
+        replace_operation(format_ptr!("/a"), json!(1)),
+        add_operation(format_ptr!("/a"), json!(2)),
+    ])]
+    #[case::write_inside_removed(vec![
+        remove_operation(format_ptr!("/a")),
+        add_operation(format_ptr!("/a/b"), json!(1)),
+    ])]
+    #[case::double_remove(vec![
+        add_operation(format_ptr!("/b"), json!(1)),
+        remove_operation(format_ptr!("/a")),
+        remove_operation(format_ptr!("/a")),
+    ])]
+    fn test_order_patch_conflict(#[case] ops: Vec<PatchOperation>) {
+        let err = assert_err!(order_patch(&Patch(ops.clone())));
+        let idx = ops.len() - 1;
+        assert_matches!(err.kind(), PatchErrorKind::ConflictingOperation(i) if *i == idx - 1);
+        assert_eq!(err.operation_index(), Some(idx));
+        assert_eq!(err.path(), ops[idx].path());
+    }
+}
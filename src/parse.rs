@@ -0,0 +1,164 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::fmt;
+
+use serde::de::{
+    self,
+    DeserializeSeed,
+    Deserializer,
+    MapAccess,
+    SeqAccess,
+    Visitor,
+};
+use serde_json::{
+    Map,
+    Value,
+};
+
+use crate::{
+    PatchError,
+    PatchErrorKind,
+    Pointer,
+    PointerBuf,
+    Token,
+    key_token,
+};
+
+// Parse a JSON document, like serde_json::from_str, except that an object containing the same key
+// more than once is rejected with a DuplicateKey error (at the path of the object), instead of
+// silently keeping the last value.  With the `preserve_order` feature, the keys of every object
+// are kept in the order that they appear in the document.
+pub fn from_str_strict(s: &str) -> Result<Value, PatchError> {
+    let duplicate = Cell::new(None);
+    let mut de = serde_json::Deserializer::from_str(s);
+    let res = StrictValue(&duplicate).deserialize(&mut de).and_then(|v| de.end().map(|_| v));
+    res.map_err(|err| match duplicate.take() {
+        Some((path, key)) => PatchError::new(PatchErrorKind::DuplicateKey(key), &path),
+        None => PatchError::new(PatchErrorKind::InvalidJson(err), Pointer::root()),
+    })
+}
+
+// Deserializes a Value, but fails at the first duplicate key; since a serde error can only carry a
+// message, the duplicate key is stored on the side, along with the path to the object containing
+// it (which is built up one segment at a time as the error propagates back up the document).
+#[derive(Clone, Copy)]
+struct StrictValue<'a>(&'a Cell<Option<(PointerBuf, String)>>);
+
+impl StrictValue<'_> {
+    fn prefix(self, t: impl Into<Token<'static>>) {
+        if let Some((mut path, key)) = self.0.take() {
+            path.push_front(t);
+            self.0.set(Some((path, key)));
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for StrictValue<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for StrictValue<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any valid JSON value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E>(self, n: i64) -> Result<Value, E> {
+        Ok(Value::from(n))
+    }
+
+    fn visit_u64<E>(self, n: u64) -> Result<Value, E> {
+        Ok(Value::from(n))
+    }
+
+    fn visit_f64<E>(self, n: f64) -> Result<Value, E> {
+        Ok(Value::from(n))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::from(s))
+    }
+
+    fn visit_string<E>(self, s: String) -> Result<Value, E> {
+        Ok(Value::String(s))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut vec = Vec::new();
+        while let Some(v) = seq.next_element_seed(self).inspect_err(|_| self.prefix(vec.len()))? {
+            vec.push(v);
+        }
+        Ok(Value::Array(vec))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Value, A::Error> {
+        let mut map = Map::new();
+        while let Some(k) = access.next_key::<String>()? {
+            if map.contains_key(&k) {
+                self.0.set(Some((PointerBuf::root(), k)));
+                return Err(de::Error::custom("duplicate key"));
+            }
+            let v = access.next_value_seed(self).inspect_err(|_| self.prefix(key_token(&k).into_owned()))?;
+            map.insert(k, v);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::format_ptr;
+
+    #[rstest]
+    fn test_from_str_strict() {
+        let s = r#"{"b": [1, -2, 3.5, "x", null, true], "a": {"c": {}, "d": []}}"#;
+        let v = assert_ok!(from_str_strict(s));
+        assert_eq!(v, json!({"b": [1, -2, 3.5, "x", null, true], "a": {"c": {}, "d": []}}));
+        assert_eq!(v, assert_ok!(serde_json::from_str::<Value>(s)));
+    }
+
+    #[rstest]
+    #[case::root(r#"{"a": 1, "b": 2, "a": 3}"#, "a", format_ptr!(""))]
+    #[case::nested(r#"{"a": {"b": [0, {"c": 1, "c": 1}]}}"#, "c", format_ptr!("/a/b/1"))]
+    #[case::escaped(r#"{"x/y": {"*": {"k": 1, "k": 2}}}"#, "k", format_ptr!("/x~1y/\\*"))]
+    fn test_from_str_strict_duplicate(#[case] s: &str, #[case] key: &str, #[case] path: PointerBuf) {
+        let err = assert_err!(from_str_strict(s));
+        assert_matches!(err.kind(), PatchErrorKind::DuplicateKey(k) if k == key);
+        assert_eq!(err.path(), path);
+    }
+
+    #[rstest]
+    #[case::syntax(r#"{"a": 1,}"#)]
+    #[case::trailing(r#"{"a": 1} 2"#)]
+    fn test_from_str_strict_invalid(#[case] s: &str) {
+        let err = assert_err!(from_str_strict(s));
+        assert_matches!(err.kind(), PatchErrorKind::InvalidJson(_));
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[rstest]
+    fn test_from_str_strict_preserve_order() {
+        let v = assert_ok!(from_str_strict(r#"{"z": 1, "a": {"y": 2, "b": 3}}"#));
+        assert_eq!(assert_ok!(serde_json::to_string(&v)), r#"{"z":1,"a":{"y":2,"b":3}}"#);
+    }
+}
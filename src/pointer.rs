@@ -0,0 +1,93 @@
+use alloc::string::ToString;
+use core::fmt::Display;
+
+use crate::{
+    Pointer,
+    PointerBuf,
+    Wildcard,
+    key_token,
+};
+
+// Helpers for building new paths out of existing ones.  Every segment that's added is escaped (as
+// in the `ptr!` macro), so e.g. `example.com/app` is always a single key, and a key named `*` is
+// always referenced literally instead of as a wildcard.
+
+// The path to the child of the value at the path with the given key (or array index)
+pub fn child(path: &Pointer, key: impl Display) -> PointerBuf {
+    let mut res = path.to_buf();
+    res.push_back(key_token(&key.to_string()).into_owned());
+    res
+}
+
+// The path to the array element at the index, inside of the array at the path
+pub fn with_index(path: &Pointer, i: usize) -> PointerBuf {
+    path.with_trailing_token(i)
+}
+
+// The path to the value containing the value at the path, or None if the path is the root
+pub fn parent(path: &Pointer) -> Option<PointerBuf> {
+    path.parent().map(Pointer::to_buf)
+}
+
+// The path to the sibling of the value at the path with the given key, or None if the path is the
+// root (which doesn't have any siblings)
+pub fn sibling(path: &Pointer, key: impl Display) -> Option<PointerBuf> {
+    path.parent().map(|p| child(p, key))
+}
+
+// Fill in the wildcards in the path (of any kind, including `**`) with the segments, in order: e.g.
+// filling in `/foo/*/bar/[?a=b]` with `[1, 2]` results in `/foo/1/bar/2`.  Any wildcards without a
+// corresponding segment are left as they are, and any extra segments are ignored.
+pub fn replace_wildcards<T: Display>(path: &Pointer, segments: impl IntoIterator<Item = T>) -> PointerBuf {
+    let mut segments = segments.into_iter();
+    let mut res = PointerBuf::new();
+    for t in path.tokens() {
+        match Wildcard::parse(&t).and_then(|_| segments.next()) {
+            Some(seg) => res.push_back(key_token(&seg.to_string()).into_owned()),
+            None => res.push_back(t),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use rstest::*;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::format_ptr;
+
+    #[rstest]
+    #[case::key(format_ptr!("/metadata/labels"), "app", format_ptr!("/metadata/labels/app"))]
+    #[case::slash(format_ptr!("/metadata/labels"), "example.com/app", format_ptr!("/metadata/labels/example.com~1app"))]
+    #[case::tilde(format_ptr!("/a"), "~b", format_ptr!("/a/~0b"))]
+    #[case::wildcard(format_ptr!("/a"), "*", format_ptr!("/a/\\*"))]
+    #[case::root(format_ptr!(""), "a", format_ptr!("/a"))]
+    fn test_child(#[case] path: PointerBuf, #[case] key: &str, #[case] expected: PointerBuf) {
+        assert_eq!(child(&path, key), expected);
+    }
+
+    #[rstest]
+    fn test_pointer_helpers() {
+        let path = format_ptr!("/spec/containers/0/image");
+        assert_eq!(with_index(&format_ptr!("/spec/containers"), 2), format_ptr!("/spec/containers/2"));
+        assert_eq!(child(&path, 0), format_ptr!("/spec/containers/0/image/0"));
+        assert_eq!(parent(&path), Some(format_ptr!("/spec/containers/0")));
+        assert_eq!(parent(&PointerBuf::root()), None);
+        assert_eq!(sibling(&path, "name"), Some(format_ptr!("/spec/containers/0/name")));
+        assert_eq!(sibling(&PointerBuf::root(), "name"), None);
+    }
+
+    #[rstest]
+    #[case::indices(format_ptr!("/foo/*/bar/[?a=b]"), vec!["1", "2"], format_ptr!("/foo/1/bar/2"))]
+    #[case::keys(format_ptr!("/**/x/1:"), vec!["a/b", "*"], format_ptr!("/a~1b/x/\\*"))]
+    #[case::too_few(format_ptr!("/foo/*/bar/*"), vec!["0"], format_ptr!("/foo/0/bar/*"))]
+    #[case::escaped(format_ptr!("/\\*/*"), vec!["0", "1"], format_ptr!("/\\*/0"))]
+    fn test_replace_wildcards(#[case] path: PointerBuf, #[case] segs: Vec<&str>, #[case] expected: PointerBuf) {
+        assert_eq!(replace_wildcards(&path, segs), expected);
+    }
+}
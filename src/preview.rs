@@ -0,0 +1,210 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use serde_json::Value;
+
+use crate::{
+    Patch,
+    PatchError,
+    patch_ext_all,
+};
+
+// The number of unchanged lines shown around each change in a unified diff
+const CONTEXT: usize = 3;
+
+// Compute the document that applying the patch would produce, without modifying the original
+pub fn preview(obj: &Value, patch: &Patch) -> Result<Value, PatchError> {
+    let mut after = obj.clone();
+    patch_ext_all(&mut after, patch)?;
+    Ok(after)
+}
+
+// Like preview, but also returns a unified diff between the pretty-printed JSON of the original
+// and the patched document (see unified_diff), e.g. to show to someone who needs to approve the
+// patch before it's applied.
+pub fn preview_diff(obj: &Value, patch: &Patch) -> Result<(Value, String), PatchError> {
+    let after = preview(obj, patch)?;
+    let diff = unified_diff(obj, &after);
+    Ok((after, diff))
+}
+
+// A line-based unified diff (as produced by `diff -u`) between the pretty-printed JSON of the two
+// values, with three lines of context around each change; the result is empty if the values are
+// equal.  Object keys are always printed in the same order, so the diff is deterministic.
+pub fn unified_diff(before: &Value, after: &Value) -> String {
+    // Serializing a Value can't fail, since its keys are always strings
+    let (old, new) = (serde_json::to_string_pretty(before).unwrap(), serde_json::to_string_pretty(after).unwrap());
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let edits = edits(&old_lines, &new_lines);
+
+    let changed: Vec<usize> = (0..edits.len()).filter(|i| !matches!(edits[*i], Edit::Same(_))).collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+
+    // The (0-based) line numbers in the old and new text at the start of each edit
+    let mut positions = vec![(0, 0)];
+    for edit in &edits {
+        let (old_pos, new_pos) = *positions.last().unwrap();
+        positions.push(match edit {
+            Edit::Same(_) => (old_pos + 1, new_pos + 1),
+            Edit::Removed(_) => (old_pos + 1, new_pos),
+            Edit::Added(_) => (old_pos, new_pos + 1),
+        });
+    }
+
+    let mut out = String::from("--- before\n+++ after\n");
+    let mut k = 0;
+    while k < changed.len() {
+        // Changes that are close enough together that their context would overlap are shown in
+        // the same hunk
+        let start = changed[k].saturating_sub(CONTEXT);
+        while k + 1 < changed.len() && changed[k + 1] - changed[k] <= 2 * CONTEXT {
+            k += 1;
+        }
+        let end = (changed[k] + CONTEXT + 1).min(edits.len());
+        k += 1;
+
+        let ((old_start, new_start), (old_end, new_end)) = (positions[start], positions[end]);
+        // As in `diff -u`, an empty range starts at the line before it
+        let first = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+        let _ = writeln!(
+            out,
+            "@@ -{},{} +{},{} @@",
+            first(old_start, old_end - old_start),
+            old_end - old_start,
+            first(new_start, new_end - new_start),
+            new_end - new_start,
+        );
+        for edit in &edits[start..end] {
+            let _ = match edit {
+                Edit::Same(line) => writeln!(out, " {line}"),
+                Edit::Removed(line) => writeln!(out, "-{line}"),
+                Edit::Added(line) => writeln!(out, "+{line}"),
+            };
+        }
+    }
+    out
+}
+
+enum Edit<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+// The shortest edit script that transforms `old` into `new`, from the longest common subsequence
+// of their lines; since a patch usually only touches a small part of the document, the common
+// prefix and suffix are stripped first so that the (quadratic) LCS table only covers the part
+// that changed.  Removals are listed before additions where either order would work.
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut res: Vec<Edit> = old[..prefix].iter().map(|l| Edit::Same(l)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            res.push(Edit::Same(a[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i == a.len() || (j < b.len() && lcs[i][j + 1] > lcs[i + 1][j]) {
+            res.push(Edit::Added(b[j]));
+            j += 1;
+        } else {
+            res.push(Edit::Removed(a[i]));
+            i += 1;
+        }
+    }
+    res.extend(old[old.len() - suffix..].iter().map(|l| Edit::Same(l)));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        PatchErrorKind,
+        add_operation,
+        format_ptr,
+        remove_operation,
+        replace_operation,
+        test_operation,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"a": 1, "b": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10], "c": {"d": true}})
+    }
+
+    #[rstest]
+    fn test_preview(data: Value) {
+        let orig = data.clone();
+        let p = Patch(vec![replace_operation(format_ptr!("/a"), json!(2)), remove_operation(format_ptr!("/c"))]);
+        let after = assert_ok!(preview(&data, &p));
+        assert_eq!(after, json!({"a": 2, "b": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]}));
+        assert_eq!(data, orig);
+
+        let p = Patch(vec![test_operation(format_ptr!("/a"), json!(2))]);
+        let err = assert_err!(preview(&data, &p));
+        assert_matches!(err.kind(), PatchErrorKind::TestMismatch { .. });
+    }
+
+    #[rstest]
+    fn test_preview_diff(data: Value) {
+        let p = Patch(vec![
+            replace_operation(format_ptr!("/a"), json!(2)),
+            add_operation(format_ptr!("/c/e"), json!("x")),
+            remove_operation(format_ptr!("/c/d")),
+        ]);
+        let (after, diff) = assert_ok!(preview_diff(&data, &p));
+        assert_eq!(after["c"], json!({"e": "x"}));
+        assert_eq!(
+            diff,
+            [
+                "--- before",
+                "+++ after",
+                "@@ -1,5 +1,5 @@",
+                " {",
+                "-  \"a\": 1,",
+                "+  \"a\": 2,",
+                "   \"b\": [",
+                "     1,",
+                "     2,",
+                "@@ -13,6 +13,6 @@",
+                "     10",
+                "   ],",
+                "   \"c\": {",
+                "-    \"d\": true",
+                "+    \"e\": \"x\"",
+                "   }",
+                " }",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[rstest]
+    fn test_unified_diff_edges(data: Value) {
+        assert_is_empty!(unified_diff(&data, &data));
+        assert_eq!(unified_diff(&json!(1), &json!({})), "--- before\n+++ after\n@@ -1,1 +1,1 @@\n-1\n+{}\n");
+        assert_eq!(unified_diff(&json!([]), &json!(null)), "--- before\n+++ after\n@@ -1,1 +1,1 @@\n-[]\n+null\n");
+    }
+}
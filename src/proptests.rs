@@ -0,0 +1,155 @@
+// Property-based tests for the traversal code: patches without wildcards should behave exactly like
+// they do in json_patch (whenever json_patch can apply them), and a wildcard patch should behave
+// exactly like the same patch manually expanded to every concrete index.
+use alloc::string::{
+    String,
+    ToString,
+};
+use alloc::vec::Vec;
+use core::slice;
+
+use json_patch::{
+    Patch,
+    PatchOperation,
+};
+use jsonptr::PointerBuf;
+use proptest::prelude::*;
+use serde_json::Value;
+
+use crate::{
+    add_operation,
+    copy_operation,
+    invert_patch,
+    move_operation,
+    patch_ext,
+    patch_ext_all,
+    remove_operation,
+    replace_operation,
+    test_operation,
+};
+
+// A small set of keys, so that generated paths have a decent chance of referencing something that
+// exists in the generated document
+fn key() -> impl Strategy<Value = String> {
+    prop::sample::select(vec!["a", "b", "c"]).prop_map(String::from)
+}
+
+fn leaf() -> impl Strategy<Value = Value> {
+    prop_oneof![Just(Value::Null), any::<bool>().prop_map(Value::from), (0..10i64).prop_map(Value::from)]
+}
+
+fn document() -> impl Strategy<Value = Value> {
+    leaf().prop_recursive(3, 24, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(Value::from),
+            prop::collection::btree_map(key(), inner, 0..4).prop_map(|m| Value::Object(m.into_iter().collect())),
+        ]
+    })
+}
+
+// Paths without any wildcards (or negative indices, which json_patch doesn't support)
+fn path() -> impl Strategy<Value = PointerBuf> {
+    let token = prop_oneof![key(), (0..4usize).prop_map(|i| i.to_string()), Just("-".to_string())];
+    prop::collection::vec(token, 0..4).prop_map(PointerBuf::from_tokens)
+}
+
+fn operation() -> impl Strategy<Value = PatchOperation> {
+    prop_oneof![
+        (path(), document()).prop_map(|(p, v)| add_operation(p, v)),
+        path().prop_map(remove_operation),
+        (path(), document()).prop_map(|(p, v)| replace_operation(p, v)),
+        (path(), path()).prop_map(|(from, p)| move_operation(from, p)),
+        (path(), path()).prop_map(|(from, p)| copy_operation(from, p)),
+        (path(), document()).prop_map(|(p, v)| test_operation(p, v)),
+    ]
+}
+
+// Paths that may also contain wildcards
+fn wildcard_path() -> impl Strategy<Value = PointerBuf> {
+    let token = prop_oneof![key(), (0..4usize).prop_map(|i| i.to_string()), Just("-".into()), Just("*".into())];
+    prop::collection::vec(token, 0..4).prop_map(PointerBuf::from_tokens)
+}
+
+fn wildcard_operation() -> impl Strategy<Value = PatchOperation> {
+    prop_oneof![
+        (wildcard_path(), document()).prop_map(|(p, v)| add_operation(p, v)),
+        wildcard_path().prop_map(remove_operation),
+        (wildcard_path(), document()).prop_map(|(p, v)| replace_operation(p, v)),
+        (wildcard_path(), wildcard_path()).prop_map(|(from, p)| move_operation(from, p)),
+        (wildcard_path(), wildcard_path()).prop_map(|(from, p)| copy_operation(from, p)),
+    ]
+}
+
+// A document with an array of objects at `/items`, for testing wildcard expansion
+fn items() -> impl Strategy<Value = Vec<Value>> {
+    let item = prop::collection::btree_map(key(), leaf(), 0..3).prop_map(|m| Value::Object(m.into_iter().collect()));
+    prop::collection::vec(item, 0..5)
+}
+
+proptest! {
+    #[test]
+    fn test_operation_matches_upstream(doc in document(), op in operation()) {
+        let mut expected = doc.clone();
+        if json_patch::patch(&mut expected, slice::from_ref(&op)).is_ok() {
+            let mut actual = doc;
+            prop_assert!(patch_ext(&mut actual, op).is_ok());
+            prop_assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_patch_matches_upstream(doc in document(), ops in prop::collection::vec(operation(), 1..4)) {
+        // Apply the upstream operations one at a time (to a copy of the document), since undoing a
+        // partially-applied patch can panic in json_patch
+        let mut expected = doc.clone();
+        if ops.iter().all(|op| json_patch::patch(&mut expected, slice::from_ref(op)).is_ok()) {
+            let mut actual = doc;
+            prop_assert!(patch_ext_all(&mut actual, &Patch(ops)).is_ok());
+            prop_assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_patch_rollback(doc in document(), ops in prop::collection::vec(wildcard_operation(), 1..4)) {
+        // The last operation always fails, so the whole patch has to be undone
+        let mut ops = ops;
+        ops.push(test_operation(PointerBuf::from_tokens(["missing"]), Value::Null));
+        let mut actual = doc.clone();
+        prop_assert!(patch_ext_all(&mut actual, &Patch(ops)).is_err());
+        prop_assert_eq!(serde_json::to_string(&actual).unwrap(), serde_json::to_string(&doc).unwrap());
+    }
+
+    #[test]
+    fn test_invert_patch(doc in document(), ops in prop::collection::vec(wildcard_operation(), 1..4)) {
+        let p = Patch(ops);
+        let mut actual = doc.clone();
+        if patch_ext_all(&mut actual, &p).is_ok() {
+            let inverse = invert_patch(&doc, &p).unwrap();
+            prop_assert!(patch_ext_all(&mut actual, &inverse).is_ok());
+            prop_assert_eq!(actual, doc);
+        }
+    }
+
+    #[test]
+    fn test_wildcard_matches_expansion(items in items(), k in key(), v in leaf(), kind in 0..3) {
+        let make_op = |path: PointerBuf| match kind {
+            0 => add_operation(path, v.clone()),
+            1 => replace_operation(path, v.clone()),
+            _ => remove_operation(path),
+        };
+        let n = items.len();
+        let doc = serde_json::json!({"items": items});
+
+        let mut actual = doc.clone();
+        let res = patch_ext(&mut actual, make_op(PointerBuf::from_tokens(["items", "*", &k])));
+
+        let expanded = (0..n).map(|i| make_op(PointerBuf::from_tokens(["items".into(), i.to_string(), k.clone()])));
+        let mut expected = doc;
+        let expected_res = patch_ext_all(&mut expected, &Patch(expanded.collect()));
+
+        prop_assert_eq!(res.is_ok(), expected_res.is_ok());
+        if res.is_ok() {
+            prop_assert_eq!(actual, expected);
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::optimize::has_wildcard;
+use crate::{
+    AddOperation,
+    CopyOperation,
+    MoveOperation,
+    Operation,
+    Patch,
+    PatchError,
+    PatchErrorKind,
+    PatchOperation,
+    Pointer,
+    PointerBuf,
+    RemoveOperation,
+    ReplaceOperation,
+    TestOperation,
+    Token,
+    array_index,
+    literal,
+};
+
+// Rewrite the paths in `patch` (which was computed against some document) so that it can be applied
+// after `onto` has been applied to that same document, without looking at the document itself:
+// array indices are shifted to account for the elements that `onto` inserted or removed, and paths
+// inside of a value that `onto` moved are rewritten to its new location.  Numeric tokens in `onto`
+// are assumed to be array indices.  Paths are only rewritten where they share a literal prefix with
+// the changed location, so e.g. `/foo/*/bar` is left alone when `/foo/0` is removed (since the
+// wildcard will just match one fewer element).
+//
+// Each operation in `patch` applies to the document as the operations before it left it, so the
+// changes that `onto` made are tracked through those operations as well: once the patch inserts an
+// element before one that `onto` removed, the later operations in the patch have to look for the
+// removed element one index further along.
+//
+// Returns a ConflictingOperation error (with the index of the operation in `onto`) if an operation
+// in `patch` references a value that `onto` removed, and an InvalidWildcard error if the paths in
+// `onto` aren't concrete, since the changes they make can't be known without a document.
+pub fn rebase(patch: &Patch, onto: &Patch) -> Result<Patch, PatchError> {
+    let mut changes = vec![];
+    for (i, base) in onto.iter().enumerate() {
+        if let Some(path) = base_paths(base).into_iter().find(|p| has_wildcard(p)) {
+            return Err(PatchError::new(PatchErrorKind::InvalidWildcard, path)
+                .with_operation(base.kind())
+                .with_operation_index(i));
+        }
+        changes.extend(Change::of(base).map(|c| (i, c)));
+    }
+
+    let mut ops = vec![];
+    for (j, op) in patch.iter().enumerate() {
+        // The changes are always relative to the document that the next operation applies to, so
+        // the operation is rebased over each change in turn, and each change is moved past the
+        // operation (as it was before being rebased over that change)
+        let mut op = op.clone();
+        let mut rebased = vec![];
+        for (i, change) in changes {
+            let moved = Change::of(&op).map_or(Some(change.clone()), |c| change.after(&c));
+            if !rebase_operation(&mut op, &change) {
+                return Err(PatchError::new(PatchErrorKind::ConflictingOperation(i), op.path())
+                    .with_operation(op.kind())
+                    .with_operation_index(j));
+            }
+            rebased.extend(moved.map(|c| (i, c)));
+        }
+        changes = rebased;
+        ops.push(op);
+    }
+    Ok(Patch(ops))
+}
+
+// The effect that an operation has on the paths of the values around it; replacing or testing a
+// value doesn't move anything
+#[derive(Clone)]
+enum Change {
+    Insert(PointerBuf),
+    Remove(PointerBuf),
+    Move(PointerBuf, PointerBuf),
+}
+
+impl Change {
+    fn of(op: &PatchOperation) -> Option<Change> {
+        match op {
+            PatchOperation::Add(AddOperation { path, .. }) | PatchOperation::Copy(CopyOperation { path, .. }) => {
+                Some(Change::Insert(path.clone()))
+            },
+            PatchOperation::Remove(RemoveOperation { path }) => Some(Change::Remove(path.clone())),
+            PatchOperation::Move(MoveOperation { from, path }) => Some(Change::Move(from.clone(), path.clone())),
+            PatchOperation::Replace(_) | PatchOperation::Test(_) => None,
+        }
+    }
+
+    // The same change, as it applies to the document after `other` (which applies to the same
+    // document as this change does); returns None if `other` removed everything this change did.
+    // When both changes insert an element at the same index, this one goes first.
+    fn after(&self, other: &Change) -> Option<Change> {
+        match self {
+            Change::Insert(at) => rebase_path(at, other, true, true).map(Change::Insert),
+            Change::Remove(at) => rebase_path(at, other, false, false).map(Change::Remove),
+            Change::Move(from, to) => {
+                match (rebase_path(from, other, false, false), rebase_path(to, other, true, true)) {
+                    (Some(from), Some(to)) => Some(Change::Move(from, to)),
+                    // The moved value is still somewhere inside of what `other` removed, so the
+                    // value only disappears from where it was moved from
+                    (Some(from), None) => Some(Change::Remove(from)),
+                    // If `other` removed exactly the moved value, the rebased version of it
+                    // removes the value from where it was moved to instead (see rebase_path);
+                    // otherwise the value was moved out of something that `other` removed, so it
+                    // just appears at `to`
+                    (None, Some(to)) => match other {
+                        Change::Remove(at) if at == from => None,
+                        _ => Some(Change::Insert(to)),
+                    },
+                    (None, None) => None,
+                }
+            },
+        }
+    }
+}
+
+fn base_paths(op: &PatchOperation) -> Vec<&Pointer> {
+    match op {
+        PatchOperation::Move(MoveOperation { from, path }) | PatchOperation::Copy(CopyOperation { from, path }) => {
+            vec![from, path]
+        },
+        op => vec![op.path()],
+    }
+}
+
+// Rewrite the paths in the operation in place to account for the base change; returns false if
+// the operation references something that the base change removed
+fn rebase_operation(op: &mut PatchOperation, base: &Change) -> bool {
+    let is_add = matches!(op, PatchOperation::Add(_));
+    let paths = match op {
+        PatchOperation::Move(MoveOperation { from, path }) | PatchOperation::Copy(CopyOperation { from, path }) => {
+            vec![(from, false), (path, true)]
+        },
+        PatchOperation::Add(AddOperation { path, .. })
+        | PatchOperation::Remove(RemoveOperation { path })
+        | PatchOperation::Replace(ReplaceOperation { path, .. })
+        | PatchOperation::Test(TestOperation { path, .. }) => vec![(path, true)],
+    };
+
+    for (path, is_target) in paths {
+        match rebase_path(path, base, is_add && is_target, false) {
+            Some(rebased) => *path = rebased,
+            None => return false,
+        }
+    }
+    true
+}
+
+// `adds` is true if the path is the target of an add operation, which doesn't need its target to
+// exist (so it's fine if the base change removed it), and `first` is true if an element inserted at
+// the path goes before one that the base change inserted at the same index
+fn rebase_path(path: &Pointer, base: &Change, adds: bool, first: bool) -> Option<PointerBuf> {
+    match base {
+        Change::Insert(at) => Some(shift_index(path, at, true, first)),
+        Change::Remove(at) => {
+            if strip_prefix(path, at).is_some_and(|rest| !(adds && rest.is_empty())) {
+                return None;
+            }
+            Some(shift_index(path, at, false, first))
+        },
+        // A move removes the value and then adds it at the destination (in the document as it is
+        // after the removal), and anything inside of the value moves along with it
+        Change::Move(from, to) => match strip_prefix(path, from) {
+            Some(rest) => Some(to.concat(&PointerBuf::from_tokens(rest))),
+            None => Some(shift_index(&shift_index(path, from, false, first), to, true, first)),
+        },
+    }
+}
+
+// If `at` is an array index that was inserted (or removed), shift the index in `path` that refers
+// to a later element of the same array (or to the inserted index itself, unless `first` is true)
+fn shift_index(path: &Pointer, at: &Pointer, inserted: bool, first: bool) -> PointerBuf {
+    let Some((parent, last)) = at.split_back() else {
+        return path.to_buf();
+    };
+    let (Some(i), Some(rest)) = (array_index(&last), strip_prefix(path, parent)) else {
+        return path.to_buf();
+    };
+    let Some((next, rest)) = rest.split_first() else {
+        return path.to_buf();
+    };
+
+    let shifted = match array_index(next) {
+        Some(j) if inserted && (j > i || j == i && !first) => match j.checked_add(1) {
+            Some(j) => j,
+            None => return path.to_buf(),
+        },
+        Some(j) if !inserted && j > i => j - 1,
+        _ => return path.to_buf(),
+    };
+    let mut res = parent.to_buf();
+    res.push_back(shifted);
+    res.append(PointerBuf::from_tokens(rest.iter().cloned()));
+    res
+}
+
+// The tokens of `path` after `prefix`, if `path` is (or is inside of) `prefix`
+fn strip_prefix<'a>(path: &'a Pointer, prefix: &Pointer) -> Option<Vec<Token<'a>>> {
+    let mut tokens = path.tokens();
+    for t in prefix.tokens() {
+        if literal(&tokens.next()?) != literal(&t) {
+            return None;
+        }
+    }
+    Some(tokens.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::{
+        Value,
+        json,
+    };
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        OperationKind,
+        add_operation,
+        copy_operation,
+        format_ptr,
+        move_operation,
+        patch_ext_all,
+        remove_operation,
+        replace_operation,
+        test_operation,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"items": [{"name": "a"}, {"name": "b"}, {"name": "c"}], "meta": {"labels": {"x": "1"}}})
+    }
+
+    #[rstest]
+    #[case::insert_before(
+        add_operation(format_ptr!("/items/0"), json!({"name": "new"})),
+        replace_operation(format_ptr!("/items/1/name"), json!("B")),
+        replace_operation(format_ptr!("/items/2/name"), json!("B")),
+    )]
+    #[case::insert_after(
+        add_operation(format_ptr!("/items/2"), json!({"name": "new"})),
+        replace_operation(format_ptr!("/items/1/name"), json!("B")),
+        replace_operation(format_ptr!("/items/1/name"), json!("B")),
+    )]
+    #[case::append(
+        add_operation(format_ptr!("/items/-"), json!({"name": "new"})),
+        replace_operation(format_ptr!("/items/1/name"), json!("B")),
+        replace_operation(format_ptr!("/items/1/name"), json!("B")),
+    )]
+    #[case::remove_before(
+        remove_operation(format_ptr!("/items/0")),
+        test_operation(format_ptr!("/items/2/name"), json!("c")),
+        test_operation(format_ptr!("/items/1/name"), json!("c")),
+    )]
+    #[case::readd_removed(
+        remove_operation(format_ptr!("/meta/labels")),
+        add_operation(format_ptr!("/meta/labels"), json!({})),
+        add_operation(format_ptr!("/meta/labels"), json!({})),
+    )]
+    #[case::moved_subtree(
+        move_operation(format_ptr!("/meta/labels"), format_ptr!("/labels")),
+        replace_operation(format_ptr!("/meta/labels/x"), json!("2")),
+        replace_operation(format_ptr!("/labels/x"), json!("2")),
+    )]
+    #[case::moved_element(
+        move_operation(format_ptr!("/items/0"), format_ptr!("/items/2")),
+        copy_operation(format_ptr!("/items/1/name"), format_ptr!("/items/0/copy")),
+        copy_operation(format_ptr!("/items/0/name"), format_ptr!("/items/2/copy")),
+    )]
+    #[case::copied(
+        copy_operation(format_ptr!("/items/2"), format_ptr!("/items/1")),
+        remove_operation(format_ptr!("/items/2")),
+        remove_operation(format_ptr!("/items/3")),
+    )]
+    #[case::wildcard(
+        remove_operation(format_ptr!("/items/0")),
+        replace_operation(format_ptr!("/items/*/name"), json!("z")),
+        replace_operation(format_ptr!("/items/*/name"), json!("z")),
+    )]
+    fn test_rebase(#[case] base: PatchOperation, #[case] op: PatchOperation, #[case] expected: PatchOperation) {
+        let rebased = assert_ok!(rebase(&Patch(vec![op]), &Patch(vec![base])));
+        assert_eq!(rebased, Patch(vec![expected]));
+    }
+
+    #[rstest]
+    fn test_rebase_apply(mut data: Value) {
+        // Both patches rename the second item, but `onto` lands first and inserts a new item before it
+        let onto = Patch(vec![add_operation(format_ptr!("/items/0"), json!({"name": "new"}))]);
+        let patch = Patch(vec![
+            test_operation(format_ptr!("/items/1/name"), json!("b")),
+            replace_operation(format_ptr!("/items/1/name"), json!("B")),
+        ]);
+        let rebased = assert_ok!(rebase(&patch, &onto));
+        assert_ok!(patch_ext_all(&mut data, &onto));
+        assert_ok!(patch_ext_all(&mut data, &rebased));
+        assert_eq!(data["items"], json!([{"name": "new"}, {"name": "a"}, {"name": "B"}, {"name": "c"}]));
+    }
+
+    #[rstest]
+    fn test_rebase_sequence() {
+        let onto =
+            Patch(vec![remove_operation(format_ptr!("/items/0")), add_operation(format_ptr!("/items/0"), json!(0))]);
+        let patch =
+            Patch(vec![remove_operation(format_ptr!("/items/3")), test_operation(format_ptr!("/items/1"), json!(1))]);
+        assert_eq!(
+            assert_ok!(rebase(&patch, &onto)),
+            Patch(vec![remove_operation(format_ptr!("/items/3")), test_operation(format_ptr!("/items/1"), json!(1))])
+        );
+    }
+
+    #[rstest]
+    #[case::inserted_at_removed(
+        remove_operation(format_ptr!("/items/1")),
+        format_ptr!("/items/1"),
+        json!([{"name": "a"}, {"name": "N"}, {"name": "c"}]),
+    )]
+    #[case::inserted_before_removed(
+        remove_operation(format_ptr!("/items/2")),
+        format_ptr!("/items/0"),
+        json!([{"name": "N"}, {"name": "a"}, {"name": "b"}]),
+    )]
+    #[case::inserted_at_inserted(
+        add_operation(format_ptr!("/items/1"), json!({"name": "x"})),
+        format_ptr!("/items/1"),
+        json!([{"name": "a"}, {"name": "x"}, {"name": "N"}, {"name": "b"}, {"name": "c"}]),
+    )]
+    fn test_rebase_patch_changes(
+        mut data: Value,
+        #[case] base: PatchOperation,
+        #[case] path: PointerBuf,
+        #[case] expected: Value,
+    ) {
+        // The replace refers to the element that the patch itself just inserted, which isn't
+        // affected by anything that `onto` did
+        let patch = Patch(vec![
+            add_operation(path.clone(), json!({"name": "new"})),
+            replace_operation(path.concat(&format_ptr!("/name")), json!("N")),
+        ]);
+        let onto = Patch(vec![base]);
+        let rebased = assert_ok!(rebase(&patch, &onto));
+        assert_ok!(patch_ext_all(&mut data, &onto));
+        assert_ok!(patch_ext_all(&mut data, &rebased));
+        assert_eq!(data["items"], expected);
+    }
+
+    #[rstest]
+    fn test_rebase_patch_changes_conflict() {
+        // After the patch inserts a new first item, the item that `onto` removed is the second one
+        let onto = Patch(vec![remove_operation(format_ptr!("/items/0"))]);
+        let patch = Patch(vec![
+            add_operation(format_ptr!("/items/0"), json!({"name": "new"})),
+            test_operation(format_ptr!("/items/1/name"), json!("a")),
+        ]);
+        let err = assert_err!(rebase(&patch, &onto));
+        assert_matches!(err.kind(), PatchErrorKind::ConflictingOperation(0));
+        assert_eq!(err.operation_index(), Some(1));
+        assert_eq!(err.operation(), Some(OperationKind::Test));
+    }
+
+    #[rstest]
+    #[case::removed(remove_operation(format_ptr!("/meta")))]
+    #[case::removed_element(remove_operation(format_ptr!("/items/1")))]
+    fn test_rebase_conflict(#[case] base: PatchOperation) {
+        let patch = Patch(vec![
+            add_operation(format_ptr!("/other"), json!(1)),
+            copy_operation(format_ptr!("/items/1/name"), format_ptr!("/meta/labels/y")),
+        ]);
+        let onto = Patch(vec![replace_operation(format_ptr!("/other"), json!(2)), base]);
+        let err = assert_err!(rebase(&patch, &onto));
+        assert_matches!(err.kind(), PatchErrorKind::ConflictingOperation(1));
+        assert_eq!(err.operation_index(), Some(1));
+        assert_eq!(err.operation(), Some(OperationKind::Copy));
+    }
+
+    #[rstest]
+    fn test_rebase_wildcard() {
+        let onto = Patch(vec![remove_operation(format_ptr!("/items/*"))]);
+        let err = assert_err!(rebase(&Patch(vec![remove_operation(format_ptr!("/items/0"))]), &onto));
+        assert_matches!(err.kind(), PatchErrorKind::InvalidWildcard);
+        assert_eq!(err.path(), format_ptr!("/items/*"));
+    }
+}
@@ -0,0 +1,58 @@
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::{
+    Annotation,
+    PatchError,
+};
+
+// A summary of what each operation in a patch did (or would do) to a document, in the same order as
+// the operations in the patch.
+#[derive(Debug, Default)]
+pub struct PatchReport {
+    pub operations: Vec<OperationReport>,
+}
+
+impl PatchReport {
+    // Whether every operation in the patch was applied (or skipped) without an error
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    // The errors from all of the operations that failed, in order
+    pub fn failures(&self) -> impl Iterator<Item = &PatchError> {
+        self.operations.iter().filter_map(|r| match &r.outcome {
+            OperationOutcome::Failed(err) => Some(err),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct OperationReport {
+    // Whether the operation succeeded, didn't need to do anything, or failed
+    pub outcome: OperationOutcome,
+
+    // The number of locations in the document that were modified, after expanding wildcards; test
+    // operations never modify anything, and a remove operation that doesn't match anything reports
+    // zero modifications.
+    pub modified: usize,
+
+    // How long it took to apply the operation
+    pub duration: Duration,
+
+    // The annotation attached to the operation, if it was an AnnotatedOperation
+    pub annotation: Option<Annotation>,
+}
+
+#[derive(Debug, Default)]
+pub enum OperationOutcome {
+    #[default]
+    Applied,
+
+    // The operation succeeded without modifying anything, e.g., a remove or a wildcard add that
+    // didn't match anything in the document; test operations are always `Applied` if they pass.
+    Skipped,
+
+    Failed(PatchError),
+}
@@ -0,0 +1,145 @@
+//! Validate patched documents against a [JSON Schema](https://json-schema.org) (requires the
+//! `schema` feature).  The patch is applied as usual, and then the result is checked against the
+//! schema; if it doesn't match, the document is rolled back and a SchemaViolation error is returned
+//! at the path of the first value that doesn't match.  The schema is passed in pre-compiled, so it
+//! can be reused across many patches, e.g. `jsonschema::validator_for(&schema)?`.
+
+use alloc::string::ToString;
+use alloc::vec;
+use core::slice;
+
+use jsonschema::Validator;
+use serde_json::Value;
+
+use crate::{
+    Operation,
+    Patch,
+    PatchError,
+    PatchErrorKind,
+    PatchOperation,
+    PatchOptions,
+    PointerBuf,
+    Snapshot,
+    apply_all,
+    key_token,
+    patch_ext_all,
+    rollback,
+};
+
+// Apply the operation, and roll it back if the resulting document doesn't match the schema
+pub fn patch_ext_checked(obj: &mut Value, op: PatchOperation, schema: &Validator) -> Result<(), PatchError> {
+    let snapshot = Snapshot::new(obj, &op);
+    apply_all(obj, slice::from_ref(&op), &PatchOptions::default())?;
+    if let Err(err) = check(obj, schema) {
+        rollback(obj, vec![snapshot]);
+        return Err(err.with_operation(op.kind()));
+    }
+    Ok(())
+}
+
+// Apply all of the operations in the patch (atomically, as in patch_ext_all), and roll them all
+// back if the final document doesn't match the schema; the intermediate documents aren't checked,
+// so it's fine for an operation to break the schema as long as a later one fixes it.  This has to
+// make a copy of the document in case it needs to be restored.
+pub fn patch_ext_all_checked(obj: &mut Value, p: &Patch, schema: &Validator) -> Result<(), PatchError> {
+    let orig = obj.clone();
+    patch_ext_all(obj, p)?;
+    if let Err(err) = check(obj, schema) {
+        *obj = orig;
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn check(obj: &Value, schema: &Validator) -> Result<(), PatchError> {
+    schema.validate(obj).map_err(|err| {
+        // The instance path is a plain JSON pointer, so any keys that look like wildcards need escaping
+        let path = PointerBuf::parse(err.instance_path().as_str()).unwrap_or_default();
+        let path = PointerBuf::from_tokens(path.tokens().map(|t| key_token(&t.decoded()).into_owned()));
+        PatchError::new(PatchErrorKind::SchemaViolation(err.to_string()), &path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        OperationKind,
+        add_operation,
+        format_ptr,
+        remove_operation,
+        replace_operation,
+    };
+
+    #[fixture]
+    fn schema() -> Validator {
+        let schema = json!({
+            "type": "object",
+            "required": ["replicas"],
+            "properties": {
+                "replicas": {"type": "integer", "minimum": 0},
+                "containers": {"type": "array", "items": {"type": "object", "required": ["image"]}},
+            },
+        });
+        jsonschema::validator_for(&schema).unwrap()
+    }
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"replicas": 1, "containers": [{"image": "a"}, {"image": "b"}]})
+    }
+
+    #[rstest]
+    fn test_patch_ext_checked(mut data: Value, schema: Validator) {
+        assert_ok!(patch_ext_checked(&mut data, replace_operation(format_ptr!("/replicas"), json!(3)), &schema));
+        assert_eq!(data["replicas"], json!(3));
+    }
+
+    #[rstest]
+    #[case::wrong_type(replace_operation(format_ptr!("/replicas"), json!("3")), format_ptr!("/replicas"))]
+    #[case::required(remove_operation(format_ptr!("/replicas")), PointerBuf::root())]
+    #[case::wildcard(remove_operation(format_ptr!("/containers/*/image")), format_ptr!("/containers/0"))]
+    fn test_patch_ext_checked_violation(
+        mut data: Value,
+        schema: Validator,
+        #[case] op: PatchOperation,
+        #[case] path: PointerBuf,
+    ) {
+        let orig = data.clone();
+        let kind = op.kind();
+        let err = assert_err!(patch_ext_checked(&mut data, op, &schema));
+        assert_matches!(err.kind(), PatchErrorKind::SchemaViolation(_));
+        assert_eq!(err.path(), path);
+        assert_eq!(err.operation(), Some(kind));
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_patch_ext_all_checked(mut data: Value, schema: Validator) {
+        // The first operation breaks the schema, but the second one fixes it
+        let p =
+            Patch(vec![remove_operation(format_ptr!("/replicas")), add_operation(format_ptr!("/replicas"), json!(2))]);
+        assert_ok!(patch_ext_all_checked(&mut data, &p, &schema));
+        assert_eq!(data["replicas"], json!(2));
+
+        let orig = data.clone();
+        let p = Patch(vec![
+            replace_operation(format_ptr!("/replicas"), json!(5)),
+            add_operation(format_ptr!("/containers/-"), json!({"name": "c"})),
+        ]);
+        let err = assert_err!(patch_ext_all_checked(&mut data, &p, &schema));
+        assert_matches!(err.kind(), PatchErrorKind::SchemaViolation(_));
+        assert_eq!(err.path(), format_ptr!("/containers/2"));
+        assert_eq!(data, orig);
+
+        let p = Patch(vec![replace_operation(format_ptr!("/missing"), json!(1))]);
+        let err = assert_err!(patch_ext_all_checked(&mut data, &p, &schema));
+        assert_matches!(err.kind(), PatchErrorKind::TargetDoesNotExist);
+        assert_eq!(err.operation(), Some(OperationKind::Replace));
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{
+    Read,
+    Write,
+};
+
+use serde::de::{
+    MapAccess,
+    Visitor,
+};
+use serde::{
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+use serde_json::Value;
+use serde_json::value::RawValue;
+
+use crate::{
+    AddOperation,
+    CopyOperation,
+    MoveOperation,
+    Patch,
+    PatchError,
+    PatchErrorKind,
+    PatchOperation,
+    Pointer,
+    RemoveOperation,
+    ReplaceOperation,
+    TestOperation,
+    Token,
+    Wildcard,
+    capture_ref,
+    literal,
+    patch_ext_all,
+};
+
+// A top-level value in the output document: either the original (unparsed) JSON text, or a value
+// that was modified by the patch
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Entry {
+    Raw(Box<RawValue>),
+    Patched(Value),
+}
+
+// The top-level fields of an object, in the order that they appear in the document; unlike a Map,
+// this keeps every copy of a duplicated key, so that we can reject them.
+struct Fields<T>(Vec<(String, T)>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Fields<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldsVisitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for FieldsVisitor<T> {
+            type Value = Fields<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Fields<T>, A::Error> {
+                let mut fields = vec![];
+                while let Some(entry) = access.next_entry()? {
+                    fields.push(entry);
+                }
+                Ok(Fields(fields))
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor(std::marker::PhantomData))
+    }
+}
+
+impl<T: Serialize> Serialize for Fields<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+    }
+}
+
+// Apply a patch to the JSON document read from `reader`, and write the result to `writer`.  If the
+// document is an object, only the top-level fields that the patch references are parsed; every
+// other field is copied through to the output verbatim.  If the patch references the root of the
+// document, or uses a wildcard in the first segment of a path, the entire document is parsed.
+// Like patch_ext_all, the patch is applied atomically, so if it fails, nothing is written.  The
+// top-level fields are written in the same order as the input (followed by any fields that the
+// patch added), and a top-level key that appears more than once is rejected with a DuplicateKey
+// error.
+pub fn patch_stream<R: Read, W: Write>(reader: R, writer: W, p: &Patch) -> Result<(), PatchError> {
+    let stream_err = |err| PatchError::new(PatchErrorKind::StreamError(err), Pointer::root());
+
+    let raw: Box<RawValue> = serde_json::from_reader(reader).map_err(stream_err)?;
+    let fields = serde_json::from_str::<Fields<Box<RawValue>>>(raw.get()).ok();
+    if let Some(Fields(fields)) = &fields {
+        let mut seen = BTreeSet::new();
+        if let Some((k, _)) = fields.iter().find(|(k, _)| !seen.insert(k)) {
+            return Err(PatchError::new(PatchErrorKind::DuplicateKey(k.clone()), Pointer::root()));
+        }
+    }
+
+    let (Some(keys), Some(Fields(fields))) = (referenced_fields(p), fields) else {
+        let mut doc: Value = serde_json::from_str(raw.get()).map_err(stream_err)?;
+        patch_ext_all(&mut doc, p)?;
+        return serde_json::to_writer(writer, &doc).map_err(stream_err);
+    };
+
+    // Only parse the fields that the patch references, and then apply the patch to the partial
+    // document made up of just those fields
+    let mut doc = serde_json::Map::new();
+    for (k, v) in &fields {
+        if keys.contains(k) {
+            doc.insert(k.clone(), serde_json::from_str(v.get()).map_err(stream_err)?);
+        }
+    }
+    let mut doc = Value::Object(doc);
+    patch_ext_all(&mut doc, p)?;
+    let Value::Object(mut patched) = doc else {
+        unreachable!("the patch can't replace the root, since it doesn't reference it");
+    };
+
+    // Put the patched fields back where they were, dropping the ones that the patch removed, and
+    // then add the new ones at the end
+    let mut out = vec![];
+    for (k, v) in fields {
+        if !keys.contains(&k) {
+            out.push((k, Entry::Raw(v)));
+        } else if let Some(v) = patched.remove(&k) {
+            out.push((k, Entry::Patched(v)));
+        }
+    }
+    out.extend(patched.into_iter().map(|(k, v)| (k, Entry::Patched(v))));
+    serde_json::to_writer(writer, &Fields(out)).map_err(stream_err)
+}
+
+// Collect the top-level fields that any operation in the patch references; returns None if we
+// can't tell, because an operation references the root or its first segment isn't a plain key (a
+// wildcard, or a numbered reference to one).
+fn referenced_fields(p: &Patch) -> Option<BTreeSet<String>> {
+    let mut keys = BTreeSet::new();
+    for op in p.iter() {
+        let paths = match op {
+            PatchOperation::Add(AddOperation { path, .. })
+            | PatchOperation::Remove(RemoveOperation { path })
+            | PatchOperation::Replace(ReplaceOperation { path, .. })
+            | PatchOperation::Test(TestOperation { path, .. }) => vec![path],
+            PatchOperation::Move(MoveOperation { from, path }) | PatchOperation::Copy(CopyOperation { from, path }) => {
+                vec![from, path]
+            },
+        };
+        for path in paths {
+            keys.insert(literal_key(&path.first()?)?);
+        }
+    }
+    Some(keys)
+}
+
+// The object key that a path token refers to, if it's a literal key rather than something that
+// stands in for (some of) the keys in the document
+fn literal_key(t: &Token) -> Option<String> {
+    if Wildcard::parse(t).is_some() || capture_ref(t).is_some() {
+        return None;
+    }
+    Some(literal(t).decoded().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        add_operation,
+        format_ptr,
+        from_str_strict,
+        move_operation,
+        remove_operation,
+        replace_operation,
+    };
+
+    const DOC: &str = r#"{"foo": [{"baz": 1}, {"baz": 2}], "bar": {"image" :  "a"}, "qux": 1.50}"#;
+
+    fn patch_str(p: &Patch) -> Result<String, PatchError> {
+        patch_doc_str(DOC, p)
+    }
+
+    fn patch_doc_str(doc: &str, p: &Patch) -> Result<String, PatchError> {
+        let mut out = vec![];
+        patch_stream(doc.as_bytes(), &mut out, p)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[rstest]
+    fn test_patch_stream_untouched_fields() {
+        let p = Patch(vec![replace_operation(format_ptr!("/foo/*/baz"), json!(3))]);
+        let out = assert_ok!(patch_str(&p));
+        assert_eq!(out, r#"{"foo":[{"baz":3},{"baz":3}],"bar":{"image" :  "a"},"qux":1.50}"#);
+    }
+
+    #[rstest]
+    fn test_patch_stream_order() {
+        let p = Patch(vec![
+            add_operation(format_ptr!("/new"), json!(0)),
+            remove_operation(format_ptr!("/foo")),
+            replace_operation(format_ptr!("/qux"), json!(2)),
+        ]);
+        let out = assert_ok!(patch_str(&p));
+        assert_eq!(out, r#"{"bar":{"image" :  "a"},"qux":2,"new":0}"#);
+    }
+
+    #[rstest]
+    #[case::referenced(Patch(vec![replace_operation(format_ptr!("/a"), json!(3))]))]
+    #[case::untouched(Patch(vec![replace_operation(format_ptr!("/b"), json!(3))]))]
+    #[case::root(Patch(vec![replace_operation(format_ptr!("/*"), json!(3))]))]
+    fn test_patch_stream_duplicate(#[case] p: Patch) {
+        let mut out = vec![];
+        let err = assert_err!(patch_stream(&br#"{"a": 1, "b": 2, "a": 3}"#[..], &mut out, &p));
+        assert_matches!(err.kind(), PatchErrorKind::DuplicateKey(k) if k == "a");
+        assert_is_empty!(out);
+    }
+
+    #[rstest]
+    #[case::many_fields(DOC, Patch(vec![
+        move_operation(format_ptr!("/bar/image"), format_ptr!("/new")),
+        remove_operation(format_ptr!("/qux")),
+    ]))]
+    #[case::recursive(DOC, Patch(vec![replace_operation(format_ptr!("/**/image"), json!("b"))]))]
+    #[case::root(DOC, Patch(vec![add_operation(format_ptr!(""), json!({"a": 1}))]))]
+    #[case::capture_ref(
+        r#"{"items": ["x", "y"], "0": "keep"}"#,
+        Patch(vec![move_operation(format_ptr!("/items/*"), format_ptr!("/*1"))]),
+    )]
+    fn test_patch_stream(#[case] doc: &str, #[case] p: Patch) {
+        let out = assert_ok!(patch_doc_str(doc, &p));
+        let mut expected: Value = serde_json::from_str(doc).unwrap();
+        assert_ok!(patch_ext_all(&mut expected, &p));
+
+        // Parse the output strictly, so that a field that was written twice isn't silently merged
+        assert_eq!(assert_ok!(from_str_strict(&out)), expected);
+    }
+
+    #[rstest]
+    fn test_patch_stream_not_object() {
+        let mut out = vec![];
+        let p = Patch(vec![add_operation(format_ptr!("/-"), json!(3))]);
+        assert_ok!(patch_stream(&b"[1, 2]"[..], &mut out, &p));
+        assert_eq!(out, b"[1,2,3]");
+    }
+
+    #[rstest]
+    fn test_patch_stream_err() {
+        let p = Patch(vec![replace_operation(format_ptr!("/missing/foo"), json!(3))]);
+        let err = assert_err!(patch_str(&p));
+        assert_matches!(err.kind(), PatchErrorKind::TargetDoesNotExist);
+
+        let mut out = vec![];
+        let err = assert_err!(patch_stream(&b"{"[..], &mut out, &p));
+        assert_matches!(err.kind(), PatchErrorKind::StreamError(_));
+        assert_is_empty!(out);
+    }
+}
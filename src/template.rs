@@ -0,0 +1,395 @@
+//! Patch templates: the values and paths in a patch can contain `{{name}}` placeholders, which are
+//! filled in from a caller-supplied map of variables before the patch is applied.  A placeholder
+//! whose name starts with `/`, e.g. `{{/metadata/name}}`, is instead filled in with the value at
+//! that path in the target document (as it was before the patch was applied).
+//!
+//! A string value that consists of a single placeholder is replaced with the variable's value
+//! as-is, so `"{{replicas}}"` can become the number `3`, or an entire object.  Everywhere else
+//! (inside of a longer string, in an object key, or in a path), placeholders are interpolated as
+//! text: string variables are inserted without quotes, and anything else is inserted as JSON.  A
+//! path token that contains a placeholder always references a literal key, so a variable can't
+//! introduce a wildcard into the path.  Placeholders in a path are part of a single token, so any
+//! `/` inside of them has to be escaped: `/spec/{{~1metadata~1name}}` references the field of
+//! `spec` named after the document's `/metadata/name`.
+//!
+//! The value of an add, replace, or test operation whose path contains wildcards can also reference
+//! whatever the wildcards matched: `{{*1}}` is the segment matched by the first wildcard, `{{*2}}`
+//! the second, and so on.  Such an operation is expanded into one operation for each location that
+//! its path matches in the document, so `add /containers/*/env/INDEX "{{*1}}"` sets a different
+//! value in every container.  An array index is filled in as a number, any other key as a string,
+//! and the segments matched by a `**` as a pointer string.
+
+use alloc::string::{
+    String,
+    ToString,
+};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde_json::{
+    Map,
+    Value,
+};
+
+use crate::{
+    AddOperation,
+    CopyOperation,
+    MoveOperation,
+    Operation,
+    Patch,
+    PatchError,
+    PatchErrorKind,
+    PatchOperation,
+    Pointer,
+    PointerBuf,
+    RemoveOperation,
+    ReplaceOperation,
+    TestOperation,
+    Token,
+    Wildcard,
+    array_index,
+    capture_ref,
+    captures,
+    key_token,
+    matches,
+    patch_ext_all,
+    resolve_ext,
+};
+
+// Fill in all of the placeholders in the patch; `obj` is the document that the patch will be
+// applied to, for placeholders that reference it.  Returns an UndefinedVariable error (at the path
+// of the operation) if a placeholder doesn't reference a variable or an existing path.  Operations
+// that reference their wildcards are expanded, so the rendered patch can be longer than the
+// original (but error indices always refer to the original operations).
+pub fn render_patch(p: &Patch, vars: &Map<String, Value>, obj: &Value) -> Result<Patch, PatchError> {
+    let ctx = Context { vars, obj, caps: &[] };
+    let mut ops = vec![];
+    for (i, op) in p.iter().enumerate() {
+        let rendered = ctx
+            .render_operation(op)
+            .map_err(|kind| PatchError::new(kind, op.path()).with_operation(op.kind()).with_operation_index(i))?;
+        ops.extend(rendered);
+    }
+    Ok(Patch(ops))
+}
+
+// Render the patch against the document and apply it (atomically, as in patch_ext_all)
+pub fn patch_ext_template(obj: &mut Value, p: &Patch, vars: &Map<String, Value>) -> Result<(), PatchError> {
+    let rendered = render_patch(p, vars, obj)?;
+    patch_ext_all(obj, &rendered)
+}
+
+// Fill in the placeholders in a single value
+pub(crate) fn render_value(value: &Value, vars: &Map<String, Value>, obj: &Value) -> Result<Value, PatchErrorKind> {
+    Context { vars, obj, caps: &[] }.render_value(value)
+}
+
+#[derive(Clone, Copy)]
+struct Context<'a> {
+    vars: &'a Map<String, Value>,
+    obj: &'a Value,
+
+    // The values of the `{{*1}}`, `{{*2}}`, ... placeholders, when rendering an expanded operation
+    caps: &'a [Value],
+}
+
+impl Context<'_> {
+    fn render_operation(&self, op: &PatchOperation) -> Result<Vec<PatchOperation>, PatchErrorKind> {
+        match op {
+            PatchOperation::Add(AddOperation { value, .. })
+            | PatchOperation::Replace(ReplaceOperation { value, .. })
+            | PatchOperation::Test(TestOperation { value, .. })
+                if references_captures(value) =>
+            {
+                self.expand_operation(op, value)
+            },
+            op => Ok(vec![self.render_single(op)?]),
+        }
+    }
+
+    // Render a separate copy of the operation for every location that its path matches, with the
+    // `{{*n}}` placeholders in its value filled in from the wildcards
+    fn expand_operation(&self, op: &PatchOperation, value: &Value) -> Result<Vec<PatchOperation>, PatchErrorKind> {
+        let path = self.render_path(op.path())?;
+
+        // The last segment of an add doesn't have to exist yet, if it's not a wildcard
+        let targets: Vec<PointerBuf> = match path.split_back() {
+            Some((parent, last)) if matches!(op, PatchOperation::Add(_)) && Wildcard::parse(&last).is_none() => {
+                matches(parent, self.obj).into_iter().map(|(p, _)| p.with_trailing_token(last.clone())).collect()
+            },
+            _ => matches(&path, self.obj).into_iter().map(|(p, _)| p).collect(),
+        };
+
+        let pattern: Vec<Token> = path.tokens().collect();
+        let mut ops = vec![];
+        for target in targets {
+            let tokens: Vec<Token> = target.tokens().collect();
+            let caps: Vec<Value> = captures(&pattern, &tokens).unwrap_or_default().iter().map(capture_value).collect();
+            let value = Context { caps: &caps, ..*self }.render_value(value)?;
+            ops.push(match op {
+                PatchOperation::Add(_) => PatchOperation::Add(AddOperation { path: target, value }),
+                PatchOperation::Replace(_) => PatchOperation::Replace(ReplaceOperation { path: target, value }),
+                _ => PatchOperation::Test(TestOperation { path: target, value }),
+            });
+        }
+        Ok(ops)
+    }
+
+    fn render_single(&self, op: &PatchOperation) -> Result<PatchOperation, PatchErrorKind> {
+        let op = match op {
+            PatchOperation::Add(op) => PatchOperation::Add(AddOperation {
+                path: self.render_path(&op.path)?,
+                value: self.render_value(&op.value)?,
+            }),
+            PatchOperation::Remove(op) => PatchOperation::Remove(RemoveOperation { path: self.render_path(&op.path)? }),
+            PatchOperation::Replace(op) => PatchOperation::Replace(ReplaceOperation {
+                path: self.render_path(&op.path)?,
+                value: self.render_value(&op.value)?,
+            }),
+            PatchOperation::Move(op) => PatchOperation::Move(MoveOperation {
+                from: self.render_path(&op.from)?,
+                path: self.render_path(&op.path)?,
+            }),
+            PatchOperation::Copy(op) => PatchOperation::Copy(CopyOperation {
+                from: self.render_path(&op.from)?,
+                path: self.render_path(&op.path)?,
+            }),
+            PatchOperation::Test(op) => PatchOperation::Test(TestOperation {
+                path: self.render_path(&op.path)?,
+                value: self.render_value(&op.value)?,
+            }),
+        };
+        Ok(op)
+    }
+
+    fn render_path(&self, path: &Pointer) -> Result<PointerBuf, PatchErrorKind> {
+        let mut res = PointerBuf::new();
+        for t in path.tokens() {
+            let decoded = t.decoded();
+            if decoded.contains("{{") {
+                res.push_back(key_token(&self.interpolate(&decoded)?).into_owned());
+            } else {
+                res.push_back(t);
+            }
+        }
+        Ok(res)
+    }
+
+    fn render_value(&self, value: &Value) -> Result<Value, PatchErrorKind> {
+        match value {
+            Value::String(s) => match whole_placeholder(s) {
+                Some(name) => self.lookup(name).cloned(),
+                None => self.interpolate(s).map(Value::String),
+            },
+            Value::Array(vec) => vec.iter().map(|v| self.render_value(v)).collect::<Result<_, _>>().map(Value::Array),
+            Value::Object(map) => {
+                let mut res = Map::new();
+                for (k, v) in map {
+                    res.insert(self.interpolate(k)?, self.render_value(v)?);
+                }
+                Ok(Value::Object(res))
+            },
+            v => Ok(v.clone()),
+        }
+    }
+
+    // Replace every placeholder in the string with the text of its value; a `{{` without a matching
+    // `}}` is left alone.
+    fn interpolate(&self, s: &str) -> Result<String, PatchErrorKind> {
+        let mut res = String::new();
+        let mut rest = s;
+        while let Some((before, after)) = rest.split_once("{{")
+            && let Some((name, tail)) = after.split_once("}}")
+        {
+            res.push_str(before);
+            match self.lookup(name.trim())? {
+                Value::String(v) => res.push_str(v),
+                v => res.push_str(&v.to_string()),
+            }
+            rest = tail;
+        }
+        res.push_str(rest);
+        Ok(res)
+    }
+
+    fn lookup(&self, name: &str) -> Result<&Value, PatchErrorKind> {
+        let found = if name.starts_with('/') {
+            PointerBuf::parse(name).ok().and_then(|path| resolve_ext(self.obj, &path))
+        } else if let Some(n) = capture_ref(&Token::new(name)) {
+            self.caps.get(n - 1)
+        } else {
+            self.vars.get(name)
+        };
+        found.ok_or_else(|| PatchErrorKind::UndefinedVariable(name.into()))
+    }
+}
+
+// Whether any of the strings in the value (or its keys) contain a `{{*n}}` placeholder
+fn references_captures(value: &Value) -> bool {
+    let in_str = |s: &str| {
+        s.split("{{")
+            .skip(1)
+            .filter_map(|rest| rest.split_once("}}"))
+            .any(|(name, _)| capture_ref(&Token::new(name.trim())).is_some())
+    };
+    match value {
+        Value::String(s) => in_str(s),
+        Value::Array(vec) => vec.iter().any(references_captures),
+        Value::Object(map) => map.iter().any(|(k, v)| in_str(k) || references_captures(v)),
+        _ => false,
+    }
+}
+
+// The value of a `{{*n}}` placeholder, given the segment(s) that the wildcard matched
+fn capture_value(cap: &PointerBuf) -> Value {
+    match cap.tokens().collect::<Vec<_>>().as_slice() {
+        [t] => match array_index(t) {
+            Some(idx) => Value::from(idx),
+            None => Value::String(t.decoded().into()),
+        },
+        _ => Value::String(cap.to_string()),
+    }
+}
+
+// The name of the placeholder, if the string consists of nothing but a single placeholder
+fn whole_placeholder(s: &str) -> Option<&str> {
+    let name = s.strip_prefix("{{")?.strip_suffix("}}")?;
+    (!name.contains("{{") && !name.contains("}}")).then(|| name.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        OperationKind,
+        add_operation,
+        copy_operation,
+        format_ptr,
+        remove_operation,
+        replace_operation,
+        test_operation,
+    };
+
+    // format_ptr! would treat the placeholders as format arguments
+    fn ptr(s: &str) -> PointerBuf {
+        PointerBuf::parse(s).unwrap()
+    }
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"metadata": {"name": "app", "labels": {"env": "prod"}}, "spec": {"replicas": 1}})
+    }
+
+    #[fixture]
+    fn vars() -> Map<String, Value> {
+        let vars = json!({"replicas": 3, "image": "app:1.2", "key": "team", "resources": {"cpu": "100m"}});
+        let Value::Object(vars) = vars else { unreachable!() };
+        vars
+    }
+
+    #[rstest]
+    #[case::whole_value(
+        replace_operation(format_ptr!("/spec/replicas"), json!("{{ replicas }}")),
+        replace_operation(format_ptr!("/spec/replicas"), json!(3)),
+    )]
+    #[case::object_value(
+        add_operation(format_ptr!("/spec/resources"), json!("{{resources}}")),
+        add_operation(format_ptr!("/spec/resources"), json!({"cpu": "100m"})),
+    )]
+    #[case::interpolated(
+        add_operation(format_ptr!("/spec/image"), json!("registry/{{image}} x{{replicas}}")),
+        add_operation(format_ptr!("/spec/image"), json!("registry/app:1.2 x3")),
+    )]
+    #[case::nested(
+        add_operation(format_ptr!("/spec/labels"), json!({"{{key}}": ["{{/metadata/name}}"]})),
+        add_operation(format_ptr!("/spec/labels"), json!({"team": ["app"]})),
+    )]
+    #[case::path(
+        add_operation(ptr("/metadata/labels/{{key}}"), json!("{{/metadata/labels/env}}")),
+        add_operation(format_ptr!("/metadata/labels/team"), json!("prod")),
+    )]
+    #[case::move_copy(
+        copy_operation(ptr("/metadata/{{~1metadata~1name}}"), ptr("/*/{{key}}-{{replicas}}")),
+        copy_operation(format_ptr!("/metadata/app"), format_ptr!("/*/team-3")),
+    )]
+    #[case::unterminated(
+        add_operation(format_ptr!("/spec/image"), json!("{{image")),
+        add_operation(format_ptr!("/spec/image"), json!("{{image")),
+    )]
+    fn test_render_patch(
+        data: Value,
+        vars: Map<String, Value>,
+        #[case] op: PatchOperation,
+        #[case] expected: PatchOperation,
+    ) {
+        let rendered = assert_ok!(render_patch(&Patch(vec![op]), &vars, &data));
+        assert_eq!(rendered, Patch(vec![expected]));
+    }
+
+    #[rstest]
+    fn test_render_patch_captures(vars: Map<String, Value>) {
+        let data = json!({"spec": {"containers": [{"name": "a"}, {"name": "b"}]}, "labels": {"x": "1", "y": "2"}});
+        let p = Patch(vec![
+            add_operation(format_ptr!("/spec/containers/*/index"), json!({"i": "{{*1}}", "s": "c{{ *1 }}-{{image}}"})),
+            replace_operation(format_ptr!("/labels/*"), json!("{{*1}}")),
+            test_operation(format_ptr!("/**/name"), json!("{{*1}}")),
+            remove_operation(format_ptr!("/spec/*1")),
+        ]);
+        let rendered = assert_ok!(render_patch(&p, &vars, &data));
+        assert_eq!(
+            rendered,
+            Patch(vec![
+                add_operation(format_ptr!("/spec/containers/0/index"), json!({"i": 0, "s": "c0-app:1.2"})),
+                add_operation(format_ptr!("/spec/containers/1/index"), json!({"i": 1, "s": "c1-app:1.2"})),
+                replace_operation(format_ptr!("/labels/x"), json!("x")),
+                replace_operation(format_ptr!("/labels/y"), json!("y")),
+                test_operation(format_ptr!("/spec/containers/0/name"), json!("/spec/containers/0")),
+                test_operation(format_ptr!("/spec/containers/1/name"), json!("/spec/containers/1")),
+                remove_operation(format_ptr!("/spec/*1")),
+            ])
+        );
+    }
+
+    #[rstest]
+    fn test_render_path_escapes_wildcards(data: Value) {
+        let Value::Object(vars) = json!({"key": "*"}) else {
+            unreachable!()
+        };
+        let p = Patch(vec![remove_operation(ptr("/metadata/labels/{{key}}"))]);
+        let rendered = assert_ok!(render_patch(&p, &vars, &data));
+        assert_eq!(rendered, Patch(vec![remove_operation(format_ptr!("/metadata/labels/\\*"))]));
+    }
+
+    #[rstest]
+    #[case::variable(add_operation(format_ptr!("/spec/image"), json!("{{missing}}")), "missing")]
+    #[case::document(add_operation(ptr("/{{~1spec~1missing}}"), json!(1)), "/spec/missing")]
+    #[case::capture(add_operation(format_ptr!("/spec/*/index"), json!("{{*2}}")), "*2")]
+    fn test_render_patch_undefined(
+        data: Value,
+        vars: Map<String, Value>,
+        #[case] op: PatchOperation,
+        #[case] name: &str,
+    ) {
+        let p = Patch(vec![replace_operation(format_ptr!("/spec/replicas"), json!(2)), op.clone()]);
+        let err = assert_err!(render_patch(&p, &vars, &data));
+        assert_matches!(err.kind(), PatchErrorKind::UndefinedVariable(n) if n == name);
+        assert_eq!(err.path(), op.path());
+        assert_eq!(err.operation(), Some(OperationKind::Add));
+        assert_eq!(err.operation_index(), Some(1));
+    }
+
+    #[rstest]
+    fn test_patch_ext_template(mut data: Value, vars: Map<String, Value>) {
+        let p = Patch(vec![
+            replace_operation(format_ptr!("/spec/replicas"), json!("{{replicas}}")),
+            add_operation(format_ptr!("/spec/container"), json!({"name": "{{/metadata/name}}", "image": "{{image}}"})),
+        ]);
+        assert_ok!(patch_ext_template(&mut data, &p, &vars));
+        assert_eq!(data["spec"], json!({"replicas": 3, "container": {"name": "app", "image": "app:1.2"}}));
+    }
+}
@@ -0,0 +1,125 @@
+// Wrappers around the `tracing` macros that compile to nothing if the `tracing` feature is disabled
+// (without evaluating their arguments), so that the rest of the crate can be instrumented without
+// sprinkling `#[cfg]`s everywhere.
+
+// Enter a debug-level span for the rest of the enclosing block
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _guard = tracing::debug_span!($($arg)*).entered();
+    };
+}
+
+// Record an event at the given level (`trace` or `debug`)
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)*);
+    };
+}
+
+pub(crate) use trace_event;
+pub(crate) use trace_span;
+
+#[cfg(all(test, feature = "tracing", feature = "std"))]
+mod tests {
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+    use core::fmt;
+    use std::sync::Mutex;
+
+    use rstest::*;
+    use serde_json::json;
+    use tracing::field::{
+        Field,
+        Visit,
+    };
+    use tracing::span::{
+        Attributes,
+        Id,
+        Record,
+    };
+    use tracing::{
+        Event,
+        Metadata,
+        Subscriber,
+    };
+
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        add_operation,
+        format_ptr,
+        patch_ext,
+        remove_operation,
+    };
+
+    // A subscriber that records the name of every span and the message (and fields) of every event
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    struct Fields(String);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0 += &format!(" {}={:?}", field.name(), value);
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields(String::from(span.metadata().name()));
+            span.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields(String::new());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    fn record(f: impl FnOnce()) -> Vec<String> {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), f);
+        recorder.0.lock().unwrap().clone()
+    }
+
+    #[rstest]
+    fn test_tracing_wildcard() {
+        let mut data = json!({"foo": [{"bar": 1}, {"baz": 2}], "empty": []});
+        let events = record(|| {
+            patch_ext(&mut data, remove_operation(format_ptr!("/empty/*/bar"))).unwrap();
+            patch_ext(&mut data, add_operation(format_ptr!("/foo/*/new/qux"), json!(0))).unwrap();
+        });
+
+        let expected = [
+            "patch_operation op=remove",
+            " message=expanded wildcard path=/empty matched=0",
+            " message=applied operation modified=0",
+            "patch_operation op=add",
+            " message=expanded wildcard path=/foo matched=2",
+            " message=created missing parents path=/foo/0/new",
+            " message=created missing parents path=/foo/1/new",
+            " message=added value path=/foo/0/new/qux",
+            " message=added value path=/foo/1/new/qux",
+            " message=applied operation modified=2",
+        ];
+        assert_eq!(events, expected);
+    }
+}
@@ -0,0 +1,191 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+use serde_json::Value;
+
+use crate::{
+    PatchError,
+    PatchErrorKind,
+    PatchOperation,
+    PatchOptions,
+    Pointer,
+    Snapshot,
+    apply_owned,
+    rollback,
+};
+
+// A sequence of operations applied to a document that can be undone as a unit, or back to any
+// savepoint along the way.  As in patch_ext_all, only the values that each operation could modify
+// are copied before it's applied (not the whole document).  A transaction that's dropped without
+// being committed is rolled back, so the document is only left modified if `commit` is called.
+pub struct PatchTransaction<'a> {
+    obj: &'a mut Value,
+
+    // The snapshot taken before each operation that's still applied, along with the generation of
+    // the operation (which counts every operation that was ever applied in the transaction, so it
+    // identifies the operation even after earlier ones have been rolled back)
+    snapshots: Vec<(usize, Snapshot)>,
+    generation: usize,
+}
+
+// A point in a transaction that it can be rolled back to: the number of operations that had been
+// applied, and the generation of the last one of them (or zero if there weren't any)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Savepoint {
+    len: usize,
+    generation: usize,
+}
+
+impl<'a> PatchTransaction<'a> {
+    pub fn new(obj: &'a mut Value) -> PatchTransaction<'a> {
+        PatchTransaction { obj, snapshots: vec![], generation: 0 }
+    }
+
+    // The document, with all of the operations applied so far
+    pub fn value(&self) -> &Value {
+        self.obj
+    }
+
+    // Apply the operation to the document; if it fails, only that operation is rolled back, and
+    // the transaction can keep going
+    pub fn apply(&mut self, op: PatchOperation) -> Result<(), PatchError> {
+        let snapshot = Snapshot::new(self.obj, &op);
+        if let Err(err) = apply_owned(self.obj, op, &PatchOptions::default()) {
+            rollback(self.obj, vec![snapshot]);
+            return Err(err);
+        }
+        self.generation += 1;
+        self.snapshots.push((self.generation, snapshot));
+        Ok(())
+    }
+
+    pub fn savepoint(&self) -> Savepoint {
+        let generation = self.snapshots.last().map_or(0, |(generation, _)| *generation);
+        Savepoint { len: self.snapshots.len(), generation }
+    }
+
+    // Undo every operation that was applied after the savepoint was taken; the savepoint (and any
+    // taken before it) can still be used afterwards, but any taken after it can't: rolling back to
+    // one of those returns a StaleSavepoint error (without changing anything), even if more
+    // operations have been applied since then.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) -> Result<(), PatchError> {
+        let current = match savepoint.len {
+            0 => Some(0),
+            n => self.snapshots.get(n - 1).map(|(generation, _)| *generation),
+        };
+        if current != Some(savepoint.generation) {
+            return Err(PatchError::new(PatchErrorKind::StaleSavepoint, Pointer::root()));
+        }
+        let undone = self.snapshots.split_off(savepoint.len);
+        rollback(self.obj, undone.into_iter().map(|(_, snapshot)| snapshot).collect());
+        Ok(())
+    }
+
+    // Keep all of the changes made by the transaction
+    pub fn commit(mut self) {
+        self.snapshots.clear();
+    }
+}
+
+impl Drop for PatchTransaction<'_> {
+    fn drop(&mut self) {
+        let undone = mem::take(&mut self.snapshots);
+        rollback(self.obj, undone.into_iter().map(|(_, snapshot)| snapshot).collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        PatchErrorKind,
+        add_operation,
+        format_ptr,
+        remove_operation,
+        replace_operation,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"foo": [{"bar": 1}, {"bar": 2}], "baz": {"qux": true}})
+    }
+
+    #[rstest]
+    fn test_transaction_commit(mut data: Value) {
+        let mut tx = PatchTransaction::new(&mut data);
+        assert_ok!(tx.apply(replace_operation(format_ptr!("/foo/*/bar"), json!(0))));
+        assert_ok!(tx.apply(remove_operation(format_ptr!("/baz"))));
+        assert_eq!(tx.value(), &json!({"foo": [{"bar": 0}, {"bar": 0}]}));
+        tx.commit();
+        assert_eq!(data, json!({"foo": [{"bar": 0}, {"bar": 0}]}));
+    }
+
+    #[rstest]
+    fn test_transaction_drop(mut data: Value) {
+        let orig = data.clone();
+        {
+            let mut tx = PatchTransaction::new(&mut data);
+            assert_ok!(tx.apply(add_operation(format_ptr!("/new/a"), json!(1))));
+            assert_ok!(tx.apply(remove_operation(format_ptr!("/foo/0"))));
+        }
+        assert_eq!(data, orig);
+    }
+
+    #[rstest]
+    fn test_transaction_savepoints(mut data: Value) {
+        let mut tx = PatchTransaction::new(&mut data);
+        assert_ok!(tx.apply(replace_operation(format_ptr!("/baz/qux"), json!(false))));
+        let first = tx.savepoint();
+        assert_ok!(tx.apply(remove_operation(format_ptr!("/foo/0"))));
+        let second = tx.savepoint();
+        assert_ok!(tx.apply(add_operation(format_ptr!("/foo/-"), json!({"bar": 3}))));
+
+        assert_ok!(tx.rollback_to(second));
+        assert_eq!(tx.value(), &json!({"foo": [{"bar": 2}], "baz": {"qux": false}}));
+        assert_ok!(tx.rollback_to(first));
+        assert_eq!(tx.value(), &json!({"foo": [{"bar": 1}, {"bar": 2}], "baz": {"qux": false}}));
+
+        // A savepoint that's already been rolled back past can't be used again
+        let err = assert_err!(tx.rollback_to(second));
+        assert_matches!(err.kind(), PatchErrorKind::StaleSavepoint);
+        assert_ok!(tx.apply(add_operation(format_ptr!("/done"), json!(true))));
+        tx.commit();
+        assert_eq!(data, json!({"foo": [{"bar": 1}, {"bar": 2}], "baz": {"qux": false}, "done": true}));
+    }
+
+    #[rstest]
+    fn test_transaction_stale_savepoint(mut data: Value) {
+        let mut tx = PatchTransaction::new(&mut data);
+        let first = tx.savepoint();
+        assert_ok!(tx.apply(replace_operation(format_ptr!("/baz/qux"), json!(false))));
+        let second = tx.savepoint();
+        assert_ok!(tx.rollback_to(first));
+
+        // The transaction has applied as many operations as it had when `second` was taken, but
+        // not the same ones, so `second` is still stale
+        assert_ok!(tx.apply(remove_operation(format_ptr!("/foo/0"))));
+        let err = assert_err!(tx.rollback_to(second));
+        assert_matches!(err.kind(), PatchErrorKind::StaleSavepoint);
+        assert_eq!(tx.value(), &json!({"foo": [{"bar": 2}], "baz": {"qux": true}}));
+
+        assert_ok!(tx.rollback_to(first));
+        assert_eq!(tx.value(), &json!({"foo": [{"bar": 1}, {"bar": 2}], "baz": {"qux": true}}));
+    }
+
+    #[rstest]
+    fn test_transaction_failed_operation(mut data: Value) {
+        let mut tx = PatchTransaction::new(&mut data);
+        assert_ok!(tx.apply(replace_operation(format_ptr!("/baz/qux"), json!(false))));
+        let err = assert_err!(tx.apply(replace_operation(format_ptr!("/foo/*/bar/x"), json!(0))));
+        assert_matches!(err.kind(), PatchErrorKind::UnexpectedType);
+        assert_eq!(tx.value(), &json!({"foo": [{"bar": 1}, {"bar": 2}], "baz": {"qux": false}}));
+        tx.commit();
+        assert_eq!(data["baz"]["qux"], json!(false));
+    }
+}
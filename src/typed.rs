@@ -0,0 +1,100 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    Operation,
+    PatchError,
+    PatchErrorKind,
+    PatchOperation,
+    PatchOptions,
+    Pointer,
+    apply_operation,
+};
+
+// Apply an extended patch operation to a strongly-typed object by round-tripping it through a
+// `serde_json::Value`.  If the patched document no longer deserializes into `T`, a
+// DeserializeError is returned (at the operation's path) and `obj` is left unchanged.
+pub fn patch_ext_typed<T: Serialize + DeserializeOwned>(obj: &mut T, p: PatchOperation) -> Result<(), PatchError> {
+    let mut doc = serde_json::to_value(&*obj)
+        .map_err(|err| PatchError::new(PatchErrorKind::SerializeError(err), Pointer::root()))?;
+    apply_operation(&mut doc, &p, &PatchOptions::default())?;
+
+    *obj = serde_json::from_value(doc)
+        .map_err(|err| PatchError::new(PatchErrorKind::DeserializeError(err), p.path()).with_operation(p.kind()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use assertables::*;
+    use rstest::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        PointerBuf,
+        add_operation,
+        format_ptr,
+        remove_operation,
+        replace_operation,
+    };
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Container {
+        name: String,
+        image: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Spec {
+        containers: Vec<Container>,
+        replicas: Option<u32>,
+    }
+
+    #[fixture]
+    fn spec() -> Spec {
+        Spec {
+            containers: vec![
+                Container { name: "app".into(), image: "app:1".into() },
+                Container { name: "sidecar".into(), image: "sidecar:1".into() },
+            ],
+            replicas: None,
+        }
+    }
+
+    #[rstest]
+    fn test_patch_ext_typed(mut spec: Spec) {
+        let p = replace_operation(format_ptr!("/containers/*/image"), json!("foo:2"));
+        assert_ok!(patch_ext_typed(&mut spec, p));
+
+        let p = add_operation(format_ptr!("/replicas"), json!(3));
+        assert_ok!(patch_ext_typed(&mut spec, p));
+
+        assert_eq!(spec.containers[0].image, "foo:2");
+        assert_eq!(spec.containers[1].image, "foo:2");
+        assert_eq!(spec.replicas, Some(3));
+    }
+
+    #[rstest]
+    #[case::wrong_type(replace_operation(format_ptr!("/replicas"), json!("three")), format_ptr!("/replicas"))]
+    #[case::missing_field(remove_operation(format_ptr!("/containers/*/name")), format_ptr!("/containers/*/name"))]
+    fn test_patch_ext_typed_deserialize_error(mut spec: Spec, #[case] p: PatchOperation, #[case] path: PointerBuf) {
+        let err = assert_err!(patch_ext_typed(&mut spec, p));
+        assert_matches!(err.kind(), PatchErrorKind::DeserializeError(_));
+        assert_eq!(err.path(), path);
+        assert_eq!(spec, self::spec());
+    }
+
+    #[rstest]
+    fn test_patch_ext_typed_patch_error(mut spec: Spec) {
+        let p = replace_operation(format_ptr!("/containers/2"), json!({"name": "foo", "image": "foo:2"}));
+        let err = assert_err!(patch_ext_typed(&mut spec, p));
+        assert_matches!(err.kind(), PatchErrorKind::OutOfBounds(2));
+        assert_eq!(spec, self::spec());
+    }
+}
@@ -0,0 +1,460 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use serde_json::Value;
+
+use crate::diff::{
+    DiffOptions,
+    diff_impl,
+};
+use crate::{
+    Operation,
+    PatchOperation,
+    PatchOptions,
+    Pointer,
+    PointerBuf,
+    Segment,
+    Token,
+    Wildcard,
+    add_operation,
+    affected_subtree,
+    aligned_matches,
+    array_index,
+    capture_ref,
+    descend,
+    join,
+    json_size,
+    key_token,
+    literal,
+    remove_key,
+    remove_operation,
+    replace_operation,
+    resolve_ext,
+    resolve_ext_mut,
+    segments,
+    split_segments,
+};
+
+// What it takes to undo a single operation: the values that it could modify, copied before it's
+// applied.  Instead of copying the whole subtree that the operation could modify (which for a
+// top-level path, or one that starts with a wildcard, is the entire document), this only copies
+// the values at the concrete targets of the operation (after wildcard expansion), along with
+// enough information to put back a key or an array element that was added or removed.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Snapshot(Vec<Undo>);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Undo {
+    // Put back the (entire) value at an existing path
+    Restore(PointerBuf, Value),
+
+    // Put back the key in the object at the path (at its original position in the object), along
+    // with the value it had, or remove the key if it didn't exist
+    Key(PointerBuf, String, Option<(usize, Value)>),
+
+    // Undo a change to the element at the index of the array at the path, which had `len` elements
+    // before the operation: if the array got longer, the element was inserted, if it got shorter,
+    // the element was removed, and otherwise it was replaced
+    Element(PointerBuf, usize, usize, Option<Value>),
+}
+
+impl Undo {
+    // The path to everything that this could change when it's undone
+    fn scope(&self) -> PointerBuf {
+        match self {
+            Undo::Restore(path, _) | Undo::Element(path, ..) => path.clone(),
+            Undo::Key(path, key, _) => path.with_trailing_token(key_token(key)),
+        }
+    }
+
+    fn undo(self, obj: &mut Value) {
+        match self {
+            Undo::Restore(path, v) => {
+                if let Some(target) = resolve_ext_mut(obj, &path) {
+                    *target = v;
+                }
+            },
+            Undo::Key(path, key, old) => {
+                let Some(Value::Object(map)) = resolve_ext_mut(obj, &path) else {
+                    return;
+                };
+                remove_key(map, &key);
+                if let Some((_i, v)) = old {
+                    #[cfg(feature = "preserve_order")]
+                    map.shift_insert(_i.min(map.len()), key, v);
+                    #[cfg(not(feature = "preserve_order"))]
+                    map.insert(key, v);
+                }
+            },
+            Undo::Element(path, i, len, old) => {
+                let Some(Value::Array(vec)) = resolve_ext_mut(obj, &path) else {
+                    return;
+                };
+                match (vec.len().cmp(&len), old) {
+                    (Ordering::Greater, _) if i < vec.len() => {
+                        vec.remove(i);
+                    },
+                    (Ordering::Less, Some(v)) if i <= vec.len() => vec.insert(i, v),
+                    (Ordering::Equal, Some(v)) if i < vec.len() => vec[i] = v,
+                    _ => (),
+                }
+            },
+        }
+    }
+}
+
+impl Snapshot {
+    // Copy the values that the operation could modify.  If the concrete targets of the operation
+    // overlap (e.g., a recursive wildcard that matches values nested inside each other, or several
+    // elements of the same array), undoing them one at a time isn't safe, so we fall back to copying
+    // the whole subtree that the operation could modify.
+    pub(crate) fn new<O: Operation>(obj: &Value, op: &O) -> Snapshot {
+        let mut undos = vec![];
+        let collected = op.targets().into_iter().all(|path| match op.transfer() {
+            // The wildcards in the destination of a transfer don't match anything themselves
+            Some((from, dest)) if path == dest => {
+                aligned_matches(obj, from, dest, &PatchOptions::default()).is_ok_and(|matched| {
+                    undos.extend(matched.into_iter().map(|(_, dest, _)| undo_for(obj, &dest)));
+                    true
+                })
+            },
+            _ => collect_undos(obj, Pointer::root(), &segments(path), obj, &mut undos),
+        });
+        if collected {
+            dedup(&mut undos);
+            let scopes: Vec<PointerBuf> = undos.iter().map(Undo::scope).collect();
+            let overlapping = scopes
+                .iter()
+                .enumerate()
+                .any(|(i, a)| scopes[i + 1..].iter().any(|b| a.starts_with(b) || b.starts_with(a)));
+            if !overlapping {
+                // With the `preserve_order` feature, keys have to be put back in their original
+                // order (after removing the new ones), so that each index is valid when we get to it
+                undos.sort_by_key(|undo| match undo {
+                    Undo::Key(_, _, Some((i, _))) => Some(*i),
+                    _ => None,
+                });
+                return Snapshot(undos);
+            }
+        }
+
+        // affected_subtree only returns paths that exist, so the unwrap is safe
+        let undos = affected_subtree(obj, op).map(|path| {
+            let v = resolve_ext(obj, &path).unwrap().clone();
+            Undo::Restore(path, v)
+        });
+        Snapshot(undos.into_iter().collect())
+    }
+
+    // How much the operation that the snapshot was taken for grew the document (which is negative
+    // if it shrank), as measured by json_size; since the snapshot knows where the operation could
+    // have written, this only has to measure the values there instead of the whole document.
+    pub(crate) fn size_delta(&self, obj: &Value) -> isize {
+        let size = |v: Option<&Value>| v.map_or(0, |v| json_size(v) as isize);
+        self.0
+            .iter()
+            .map(|undo| match undo {
+                Undo::Restore(path, old) => size(resolve_ext(obj, path)) - size(Some(old)),
+                Undo::Key(path, key, old) => {
+                    // The key, its quotes, the colon, and the comma that separates it from the next one
+                    let entry = |v: Option<&Value>| v.map_or(0, |v| key.len() as isize + 4 + size(Some(v)));
+                    let new = resolve_ext(obj, path).and_then(|v| v.get(key));
+                    entry(new) - entry(old.as_ref().map(|(_, v)| v))
+                },
+                Undo::Element(path, i, len, old) => {
+                    let Some(Value::Array(vec)) = resolve_ext(obj, path) else {
+                        return 0;
+                    };
+                    match vec.len().cmp(len) {
+                        Ordering::Greater => size(vec.get(*i)) + 1,
+                        Ordering::Less => -(size(old.as_ref()) + 1),
+                        Ordering::Equal => size(vec.get(*i)) - size(old.as_ref()),
+                    }
+                },
+            })
+            .sum()
+    }
+
+    // Push the operations that undo the operation that the snapshot was taken for (given the
+    // document after it was applied) onto `ops`: a key or an array element that the operation added
+    // is removed, one that it removed is added back, and one that it replaced is replaced with the
+    // old value.  A copied subtree (from an operation whose targets overlap) is diffed instead.
+    pub(crate) fn invert(self, obj: &Value, ops: &mut Vec<PatchOperation>) {
+        for undo in self.0 {
+            match undo {
+                Undo::Restore(mut path, old) => {
+                    if let Some(new) = resolve_ext(obj, &path) {
+                        diff_impl(new, &old, &DiffOptions::default(), &mut path, ops);
+                    }
+                },
+                Undo::Key(parent, key, old) => {
+                    let new = resolve_ext(obj, &parent).and_then(|v| v.get(&key));
+                    let path = parent.with_trailing_token(key_token(&key));
+                    match (new, old) {
+                        (Some(new), Some((_, old))) if *new != old => ops.push(replace_operation(path, old)),
+                        (None, Some((_, old))) => ops.push(add_operation(path, old)),
+                        (Some(_), None) => ops.push(remove_operation(path)),
+                        _ => (),
+                    }
+                },
+                Undo::Element(parent, i, len, old) => {
+                    let Some(Value::Array(vec)) = resolve_ext(obj, &parent) else {
+                        continue;
+                    };
+                    let path = parent.with_trailing_token(i);
+                    match (vec.len().cmp(&len), old) {
+                        (Ordering::Greater, _) => ops.push(remove_operation(path)),
+                        (Ordering::Less, Some(old)) => ops.push(add_operation(path, old)),
+                        (Ordering::Equal, Some(old)) if vec.get(i) != Some(&old) => {
+                            ops.push(replace_operation(path, old));
+                        },
+                        _ => (),
+                    }
+                },
+            }
+        }
+    }
+
+    // The value that the path had before the operation that the snapshot was taken for was applied:
+    // None if the operation couldn't have modified the path (so the snapshot doesn't know), and
+    // Some(None) if the path didn't exist yet
+    pub(crate) fn old_value(&self, path: &Pointer) -> Option<Option<&Value>> {
+        self.0.iter().find_map(|undo| {
+            let (scope, old) = match undo {
+                Undo::Restore(scope, v) => (scope.clone(), Some(v)),
+                Undo::Key(parent, key, old) => {
+                    (parent.with_trailing_token(key_token(key)), old.as_ref().map(|(_, v)| v))
+                },
+                Undo::Element(parent, i, _, old) => (parent.with_trailing_token(*i), old.as_ref()),
+            };
+            let rel = path.strip_prefix(&scope)?;
+            Some(old.and_then(|v| resolve_ext(v, rel)))
+        })
+    }
+
+    // A snapshot of the entire subtree at the path
+    pub(crate) fn subtree(path: PointerBuf, v: Value) -> Snapshot {
+        Snapshot(vec![Undo::Restore(path, v)])
+    }
+}
+
+// Drop repeated undos for the same key (e.g., from a move onto itself), keeping the first one;
+// putting back an array element isn't idempotent, so those have to stay (and then overlap).
+fn dedup(undos: &mut Vec<Undo>) {
+    let mut i = 0;
+    while i < undos.len() {
+        if matches!(undos[i], Undo::Key(..)) && undos[..i].contains(&undos[i]) {
+            undos.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+// Push the undos for every concrete target of the segments under `value` (whose path is `prefix`)
+// onto `undos`; returns false if the targets can't be determined ahead of time.  As in
+// collect_targets, the head of the segments (before the first wildcard) may not exist yet.
+fn collect_undos(doc: &Value, prefix: &Pointer, segs: &[Segment], value: &Value, undos: &mut Vec<Undo>) -> bool {
+    if segs.iter().any(|(t, _)| capture_ref(t).is_some()) {
+        return false;
+    }
+
+    let (head, wildcard) = split_segments(segs);
+    let head_path = join(prefix, head);
+    let (Some(head_val), Some((wildcard, tail))) = (descend(value, head), wildcard) else {
+        undos.push(undo_for(doc, &head_path));
+        return true;
+    };
+
+    let children: Vec<(PointerBuf, &Value)> = match (wildcard, head_val) {
+        (Wildcard::Recursive, _) => return false,
+        (_, Value::Array(vec)) => vec
+            .iter()
+            .enumerate()
+            .filter(|(i, v)| wildcard.selects(&Token::from(*i), Some(*i), v))
+            .map(|(i, v)| (head_path.with_trailing_token(i), v))
+            .collect(),
+        (_, Value::Object(map)) => map
+            .iter()
+            .map(|(k, v)| (key_token(k), v))
+            .filter(|(t, v)| wildcard.selects(t, None, v))
+            .map(|(t, v)| (head_path.with_trailing_token(t), v))
+            .collect(),
+        // The operation will fail without modifying anything here
+        _ => vec![],
+    };
+    children.into_iter().all(|(path, v)| collect_undos(doc, &path, tail, v, undos))
+}
+
+// The undo for a single concrete path, which is recorded against the deepest ancestor of the path
+// that already exists (since the operation may create any missing parents)
+fn undo_for(doc: &Value, path: &Pointer) -> Undo {
+    let Some((mut parent, _)) = path.split_back() else {
+        return Undo::Restore(PointerBuf::root(), doc.clone());
+    };
+    while resolve_ext(doc, parent).is_none() {
+        // The root always exists, so this stops before running out of parents
+        parent = parent.parent().unwrap_or(Pointer::root());
+    }
+
+    // parent is a strict ancestor of the path, so the path has a token right after it
+    let t = path.tokens().nth(parent.count()).unwrap();
+    match resolve_ext(doc, parent) {
+        Some(Value::Object(map)) => {
+            let key = literal(&t).decoded().into_owned();
+            let old = map.iter().enumerate().find(|(_, (k, _))| **k == key).map(|(i, (_, v))| (i, v.clone()));
+            Undo::Key(parent.to_buf(), key, old)
+        },
+        Some(Value::Array(vec)) => match (t.encoded(), array_index(&t)) {
+            ("-", _) => Undo::Element(parent.to_buf(), vec.len(), vec.len(), None),
+            (_, Some(i)) => Undo::Element(parent.to_buf(), i, vec.len(), vec.get(i).cloned()),
+            _ => Undo::Restore(parent.to_buf(), Value::Array(vec.clone())),
+        },
+        v => Undo::Restore(parent.to_buf(), v.cloned().unwrap_or_default()),
+    }
+}
+
+// Undo the snapshots, most recent first
+pub(crate) fn rollback(obj: &mut Value, snapshots: Vec<Snapshot>) {
+    for Snapshot(undos) in snapshots.into_iter().rev() {
+        for undo in undos {
+            undo.undo(obj);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        Patch,
+        PatchOperation,
+        add_operation,
+        format_ptr,
+        move_operation,
+        patch_ext_all,
+        remove_operation,
+        replace_operation,
+    };
+
+    #[fixture]
+    fn data() -> Value {
+        json!({"a": 1, "b": {"c": [1, 2, 3], "d": {"e": 4}}, "f": [{"g": 5}, {"g": 6}]})
+    }
+
+    // None of these copy anything besides the values at the concrete targets of the operation
+    #[rstest]
+    #[case::replace(
+        replace_operation(format_ptr!("/a"), json!(2)),
+        vec![Undo::Key(format_ptr!(""), "a".into(), Some((0, json!(1))))],
+    )]
+    #[case::add(add_operation(format_ptr!("/x/y"), json!(2)), vec![Undo::Key(format_ptr!(""), "x".into(), None)])]
+    #[case::append(
+        add_operation(format_ptr!("/b/c/-"), json!(4)),
+        vec![Undo::Element(format_ptr!("/b/c"), 3, 3, None)],
+    )]
+    #[case::remove(
+        remove_operation(format_ptr!("/b/c/1")),
+        vec![Undo::Element(format_ptr!("/b/c"), 1, 3, Some(json!(2)))],
+    )]
+    #[case::array_wildcard(
+        replace_operation(format_ptr!("/f/*/g"), json!(0)),
+        vec![
+            Undo::Key(format_ptr!("/f/0"), "g".into(), Some((0, json!(5)))),
+            Undo::Key(format_ptr!("/f/1"), "g".into(), Some((0, json!(6)))),
+        ],
+    )]
+    #[case::move_op(
+        move_operation(format_ptr!("/a"), format_ptr!("/b/x")),
+        vec![
+            Undo::Key(format_ptr!("/b"), "x".into(), None),
+            Undo::Key(format_ptr!(""), "a".into(), Some((0, json!(1)))),
+        ],
+    )]
+    fn test_snapshot(data: Value, #[case] op: PatchOperation, #[case] expected: Vec<Undo>) {
+        assert_eq!(Snapshot::new(&data, &op), Snapshot(expected));
+    }
+
+    #[rstest]
+    #[case::replaced(replace_operation(format_ptr!("/f/*/g"), json!(0)), format_ptr!("/f/1/g"), Some(Some(json!(6))))]
+    #[case::inside(replace_operation(format_ptr!("/b"), json!(0)), format_ptr!("/b/d/e"), Some(Some(json!(4))))]
+    #[case::missing(add_operation(format_ptr!("/f/*/h/i"), json!(0)), format_ptr!("/f/0/h"), Some(None))]
+    #[case::missing_parent(add_operation(format_ptr!("/f/*/h/i"), json!(0)), format_ptr!("/f/0/h/i"), Some(None))]
+    #[case::above(add_operation(format_ptr!("/f/*/h/i"), json!(0)), format_ptr!("/f/0"), None)]
+    #[case::untouched(add_operation(format_ptr!("/f/*/h/i"), json!(0)), format_ptr!("/a"), None)]
+    fn test_snapshot_old_value(
+        data: Value,
+        #[case] op: PatchOperation,
+        #[case] path: PointerBuf,
+        #[case] expected: Option<Option<Value>>,
+    ) {
+        let snapshot = Snapshot::new(&data, &op);
+        assert_eq!(snapshot.old_value(&path), expected.as_ref().map(Option::as_ref));
+    }
+
+    #[rstest]
+    fn test_snapshot_top_level_wildcard() {
+        let data = json!({"x": {"e": 1, "list": [1, 2, 3]}, "y": {"e": 2}, "z": {}});
+        let op = add_operation(format_ptr!("/*/e"), json!(0));
+        assert_eq!(
+            Snapshot::new(&data, &op),
+            Snapshot(vec![
+                Undo::Key(format_ptr!("/z"), "e".into(), None),
+                Undo::Key(format_ptr!("/x"), "e".into(), Some((0, json!(1)))),
+                Undo::Key(format_ptr!("/y"), "e".into(), Some((0, json!(2)))),
+            ])
+        );
+    }
+
+    // Overlapping targets fall back to copying the subtree that contains all of them
+    #[rstest]
+    #[case::same_array(remove_operation(format_ptr!("/b/c/*")), format_ptr!("/b/c"))]
+    #[case::recursive(replace_operation(format_ptr!("/**/g"), json!(0)), format_ptr!(""))]
+    #[case::move_in_array(move_operation(format_ptr!("/b/c/0"), format_ptr!("/b/c/2")), format_ptr!("/b/c"))]
+    fn test_snapshot_overlapping(data: Value, #[case] op: PatchOperation, #[case] path: PointerBuf) {
+        let expected = resolve_ext(&data, &path).unwrap().clone();
+        assert_eq!(Snapshot::new(&data, &op), Snapshot::subtree(path, expected));
+    }
+
+    #[rstest]
+    #[case::replace(replace_operation(format_ptr!("/a"), json!("abc")))]
+    #[case::add(add_operation(format_ptr!("/x/y"), json!(2)))]
+    #[case::remove(remove_operation(format_ptr!("/b/c/1")))]
+    #[case::append(add_operation(format_ptr!("/b/c/-"), json!([1, 2])))]
+    #[case::wildcard(replace_operation(format_ptr!("/f/*/g"), json!(100)))]
+    #[case::overlapping(remove_operation(format_ptr!("/b/c/*")))]
+    fn test_size_delta(mut data: Value, #[case] op: PatchOperation) {
+        let before = json_size(&data) as isize;
+        let snapshot = Snapshot::new(&data, &op);
+        assert_ok!(op.apply(&mut data, &PatchOptions::default()));
+        assert_eq!(snapshot.size_delta(&data), json_size(&data) as isize - before);
+    }
+
+    #[rstest]
+    #[case::add(add_operation(format_ptr!("/x/y/z"), json!(2)))]
+    #[case::remove(remove_operation(format_ptr!("/a")))]
+    #[case::insert(add_operation(format_ptr!("/f/0"), json!(0)))]
+    #[case::remove_elem(remove_operation(format_ptr!("/b/c/0")))]
+    #[case::replace_elem(replace_operation(format_ptr!("/b/c/2"), json!(0)))]
+    #[case::wildcard(remove_operation(format_ptr!("/f/*/g")))]
+    #[case::move_op(move_operation(format_ptr!("/b/d"), format_ptr!("/f/1")))]
+    #[case::move_into(move_operation(format_ptr!("/f/0/g"), format_ptr!("/f/0")))]
+    #[case::remove_keys(remove_operation(format_ptr!("/*")))]
+    #[case::move_key(move_operation(format_ptr!("/a"), format_ptr!("/z")))]
+    fn test_rollback(mut data: Value, #[case] op: PatchOperation) {
+        let orig = data.clone();
+        let p = Patch(vec![op, replace_operation(format_ptr!("/missing/key"), json!(0))]);
+        assert_err!(patch_ext_all(&mut data, &p));
+        assert_eq!(data, orig);
+
+        // The keys of every object are back in their original order, too
+        assert_eq!(assert_ok!(serde_json::to_string(&data)), assert_ok!(serde_json::to_string(&orig)));
+    }
+}
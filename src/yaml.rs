@@ -0,0 +1,99 @@
+//! Read and write patches as YAML (requires the `yaml` feature), e.g. to keep them alongside the
+//! Kubernetes manifests that they modify.  This works for both standard patches (`Patch`) and
+//! extended ones (`ExtPatch`), and the paths are written as plain strings, so all of the wildcard
+//! syntax survives the round trip.
+
+use alloc::string::String;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    PatchError,
+    PatchErrorKind,
+    Pointer,
+};
+
+// Parse a patch (or anything else that can be deserialized, like an ExtPatch) from a YAML string
+pub fn from_yaml_str<T: DeserializeOwned>(s: &str) -> Result<T, PatchError> {
+    serde_yaml::from_str(s).map_err(|err| PatchError::new(PatchErrorKind::YamlError(err), Pointer::root()))
+}
+
+// Write a patch (or an ExtPatch) as a YAML string
+pub fn to_yaml_string<T: Serialize>(p: &T) -> Result<String, PatchError> {
+    serde_yaml::to_string(p).map_err(|err| PatchError::new(PatchErrorKind::YamlError(err), Pointer::root()))
+}
+
+#[cfg(test)]
+mod tests {
+    use assertables::*;
+    use rstest::*;
+    use serde_json::json;
+
+    use super::*;
+    use crate as json_patch_ext; // make the macros work in the tests
+    use crate::{
+        ExtPatch,
+        Patch,
+        add_operation,
+        copy_operation,
+        format_ptr,
+        increment_operation,
+        remove_operation,
+        replace_operation,
+    };
+
+    const PATCH: &str = r#"
+- op: add
+  path: /spec/template/spec/containers/*/env/-
+  value:
+    name: LOG_LEVEL
+    value: debug
+- op: remove
+  path: /metadata/labels/example.com~1team
+- op: replace
+  path: /**/image
+  value: app:1.2
+- op: copy
+  from: /spec/containers/[?name=app]/image
+  path: /metadata/annotations/\*
+"#;
+
+    #[rstest]
+    fn test_from_yaml_str() {
+        let p: Patch = assert_ok!(from_yaml_str(PATCH));
+        assert_eq!(
+            p,
+            Patch(vec![
+                add_operation(
+                    format_ptr!("/spec/template/spec/containers/*/env/-"),
+                    json!({"name": "LOG_LEVEL", "value": "debug"})
+                ),
+                remove_operation(format_ptr!("/metadata/labels/example.com~1team")),
+                replace_operation(format_ptr!("/**/image"), json!("app:1.2")),
+                copy_operation(
+                    format_ptr!("/spec/containers/[?name=app]/image"),
+                    format_ptr!("/metadata/annotations/\\*")
+                ),
+            ])
+        );
+    }
+
+    #[rstest]
+    fn test_yaml_round_trip() {
+        let p: Patch = assert_ok!(from_yaml_str(PATCH));
+        let yaml = assert_ok!(to_yaml_string(&p));
+        assert_eq!(assert_ok!(from_yaml_str::<Patch>(&yaml)), p);
+
+        let mut ext = ExtPatch::from(p);
+        ext.0.push(increment_operation(format_ptr!("/spec/*/replicas"), 1.into()));
+        let yaml = assert_ok!(to_yaml_string(&ext));
+        assert_eq!(assert_ok!(from_yaml_str::<ExtPatch>(&yaml)), ext);
+    }
+
+    #[rstest]
+    fn test_from_yaml_str_error() {
+        let err = assert_err!(from_yaml_str::<Patch>("- op: frobnicate\n  path: /foo\n"));
+        assert_matches!(err.kind(), PatchErrorKind::YamlError(_));
+    }
+}